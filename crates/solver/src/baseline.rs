@@ -139,10 +139,217 @@ pub fn subtract_rolling_baseline(trace: &mut [f32], window: usize, quantile: f64
     }
 }
 
+/// Replace each sample of `trace` with the median of the centered window
+/// `trace[t - window/2..=t + window/2]`, in place. Windows are truncated at the
+/// edges; an even-sized truncated window takes the mean of its two middle values.
+/// Same O(N log M) Fenwick-tree sliding selection as `subtract_rolling_baseline`.
+/// `window` should be odd; windows below 3 are a no-op.
+pub(crate) fn rolling_median(trace: &mut [f32], window: usize) {
+    let n = trace.len();
+    if n == 0 || window < 3 {
+        return;
+    }
+    let half = window / 2;
+
+    let mut sorted_vals: Vec<OrderedF32> = trace.iter().map(|&v| OrderedF32(v)).collect();
+    sorted_vals.sort_unstable();
+    sorted_vals.dedup();
+    let compress = |v: f32| -> usize { sorted_vals.binary_search(&OrderedF32(v)).unwrap() };
+
+    let mut fenwick = FenwickTree::new(sorted_vals.len());
+    for &v in &trace[..half.min(n)] {
+        fenwick.update(compress(v), 1);
+    }
+
+    let mut medians = Vec::with_capacity(n);
+    for t in 0..n {
+        // Slide the window to [t - half, t + half] ∩ [0, n)
+        if t + half < n {
+            fenwick.update(compress(trace[t + half]), 1);
+        }
+        if t > half {
+            fenwick.update(compress(trace[t - half - 1]), -1);
+        }
+
+        let win_size = (t + half).min(n - 1) + 1 - t.saturating_sub(half);
+        let lo = sorted_vals[fenwick.kth(win_size.div_ceil(2) as i32)].0;
+        let hi = sorted_vals[fenwick.kth((win_size / 2 + 1) as i32)].0;
+        medians.push(if win_size % 2 == 1 {
+            lo
+        } else {
+            0.5 * (lo + hi)
+        });
+    }
+
+    trace.copy_from_slice(&medians);
+}
+
+/// Quantile (`q` in [0, 1]) of the residual `trace - reconvolution`, using
+/// `scratch` as the selection buffer (grown as needed, never shrunk).
+///
+/// Same rank convention as `subtract_rolling_baseline`: index `round((n - 1) * q)`.
+pub(crate) fn residual_quantile(
+    trace: &[f32],
+    reconvolution: &[f32],
+    scratch: &mut Vec<f32>,
+    q: f64,
+) -> f64 {
+    let n = trace.len();
+    if n == 0 {
+        return 0.0;
+    }
+    if scratch.len() < n {
+        scratch.resize(n, 0.0);
+    }
+    let buf = &mut scratch[..n];
+    for i in 0..n {
+        buf[i] = trace[i] - reconvolution[i];
+    }
+    let k = ((n as f64 - 1.0) * q.clamp(0.0, 1.0)).round() as usize;
+    let (_, kth, _) = buf.select_nth_unstable_by(k.min(n - 1), |a, b| a.total_cmp(b));
+    *kth as f64
+}
+
+/// Expectile of the residual `trace - reconvolution` for the asymmetric data
+/// term: the scalar b minimizing `Σ w_i (e_i - b)²` with `e_i = trace - reconvolution`,
+/// `w_i = 1 + asymmetry` where the fit is below the data (`e_i > b`) and
+/// `1 - asymmetry` where above. `asymmetry = 0` gives the mean.
+///
+/// Solved by reweighted means from the mean (monotone, a handful of passes).
+pub(crate) fn residual_expectile(trace: &[f32], reconvolution: &[f32], asymmetry: f64) -> f64 {
+    let n = trace.len();
+    if n == 0 {
+        return 0.0;
+    }
+    let mut b = trace
+        .iter()
+        .zip(reconvolution)
+        .map(|(&y, &k)| (y - k) as f64)
+        .sum::<f64>()
+        / n as f64;
+    for _ in 0..50 {
+        let (mut num, mut den) = (0.0_f64, 0.0_f64);
+        for (&y, &k) in trace.iter().zip(reconvolution) {
+            let e = (y - k) as f64;
+            let w = if e > b {
+                1.0 + asymmetry
+            } else {
+                1.0 - asymmetry
+            };
+            num += w * e;
+            den += w;
+        }
+        let next = num / den;
+        if (next - b).abs() <= 1e-12 * (1.0 + b.abs()) {
+            return next;
+        }
+        b = next;
+    }
+    b
+}
+
+/// Total-variation denoising: `output = argmin_b ½||input - b||² + lambda·Σ|b[t+1] - b[t]|`.
+///
+/// Condat's direct algorithm (IEEE SPL 2013): exact, O(n) in practice, no
+/// allocation. The result is piecewise constant and keeps the mean of `input`;
+/// `lambda <= 0` copies the input. Accumulates in f64.
+pub(crate) fn tv_denoise(input: &[f32], lambda: f64, output: &mut [f32]) {
+    let n = input.len();
+    if n == 0 {
+        return;
+    }
+    if n == 1 || lambda <= 0.0 {
+        output[..n].copy_from_slice(input);
+        return;
+    }
+    let y = |i: usize| input[i] as f64;
+    let (mut k, mut k0, mut kminus, mut kplus) = (0usize, 0usize, 0usize, 0usize);
+    let mut vmin = y(0) - lambda;
+    let mut vmax = y(0) + lambda;
+    let mut umin = lambda;
+    let mut umax = -lambda;
+    loop {
+        if k == n - 1 {
+            // End of signal: close the current segment or backtrack
+            if umin < 0.0 {
+                output[k0..=kminus].fill(vmin as f32);
+                k0 = kminus + 1;
+                k = k0;
+                kminus = k;
+                vmin = y(k);
+                umin = lambda;
+                umax = vmin + umin - vmax;
+            } else if umax > 0.0 {
+                output[k0..=kplus].fill(vmax as f32);
+                k0 = kplus + 1;
+                k = k0;
+                kplus = k;
+                vmax = y(k);
+                umax = -lambda;
+                umin = vmax + umax - vmin;
+            } else {
+                vmin += umin / (k - k0 + 1) as f64;
+                output[k0..=k].fill(vmin as f32);
+                return;
+            }
+            continue;
+        }
+        umin += y(k + 1) - vmin;
+        if umin < -lambda {
+            // Negative jump: emit the segment at vmin
+            output[k0..=kminus].fill(vmin as f32);
+            k0 = kminus + 1;
+            k = k0;
+            kminus = k;
+            kplus = k;
+            vmin = y(k);
+            vmax = vmin + 2.0 * lambda;
+            umin = lambda;
+            umax = -lambda;
+            continue;
+        }
+        umax += y(k + 1) - vmax;
+        if umax > lambda {
+            // Positive jump: emit the segment at vmax
+            output[k0..=kplus].fill(vmax as f32);
+            k0 = kplus + 1;
+            k = k0;
+            kminus = k;
+            kplus = k;
+            vmax = y(k);
+            vmin = vmax - 2.0 * lambda;
+            umin = lambda;
+            umax = -lambda;
+            continue;
+        }
+        k += 1;
+        if umin >= lambda {
+            kminus = k;
+            vmin += (umin - lambda) / (kminus - k0 + 1) as f64;
+            umin = lambda;
+        }
+        if umax <= -lambda {
+            kplus = k;
+            vmax += (umax + lambda) / (kplus - k0 + 1) as f64;
+            umax = -lambda;
+        }
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
 
+    #[test]
+    fn residual_expectile_tilts_toward_weighted_side() {
+        let trace = [0.0_f32, 0.0, 0.0, 4.0];
+        let zeros = [0.0_f32; 4];
+        assert!((residual_expectile(&trace, &zeros, 0.0) - 1.0).abs() < 1e-12);
+        // Weight 3 above, 1 below: 3·(4 - b) = 3b → b = 2
+        assert!((residual_expectile(&trace, &zeros, 0.5) - 2.0).abs() < 1e-9);
+        assert!(residual_expectile(&trace, &zeros, -0.5) < 1.0);
+    }
+
     #[test]
     fn window_size_computation() {
         // tau_d=0.4, fs=30 → kernel_len = ceil(5*0.4*30) = ceil(60) = 60 → window = 300
@@ -187,6 +394,44 @@ mod tests {
         );
     }
 
+    #[test]
+    fn residual_quantile_matches_sorted_rank() {
+        let trace: Vec<f32> = (0..11).map(|i| (10 - i) as f32).collect();
+        let reconv = vec![1.0_f32; 11];
+        let mut scratch = Vec::new();
+        // residuals are -1..=9; the 0.2 quantile is rank round(10 * 0.2) = 2
+        assert_eq!(residual_quantile(&trace, &reconv, &mut scratch, 0.2), 1.0);
+        assert_eq!(residual_quantile(&trace, &reconv, &mut scratch, 0.0), -1.0);
+        assert_eq!(residual_quantile(&trace, &reconv, &mut scratch, 1.0), 9.0);
+        assert_eq!(residual_quantile(&[], &[], &mut scratch, 0.5), 0.0);
+    }
+
+    #[test]
+    fn tv_denoise_shrinks_step_and_keeps_mean() {
+        // Two plateaus of 50 samples: each level moves lambda / 50 toward the other
+        let mut input = vec![0.0_f32; 100];
+        input[50..].fill(1.0);
+        let mut output = vec![0.0_f32; 100];
+        tv_denoise(&input, 1.0, &mut output);
+        for &v in &output[..50] {
+            assert!((v - 0.02).abs() < 1e-6, "low plateau {}", v);
+        }
+        for &v in &output[50..] {
+            assert!((v - 0.98).abs() < 1e-6, "high plateau {}", v);
+        }
+
+        // A lambda larger than the step's TV cost flattens everything to the mean
+        let noisy: Vec<f32> = (0..100).map(|i| (i as f32 * 0.37).sin()).collect();
+        let mean = noisy.iter().map(|&v| v as f64).sum::<f64>() / 100.0;
+        tv_denoise(&noisy, 1e3, &mut output);
+        for &v in &output {
+            assert!((v as f64 - mean).abs() < 1e-5, "{} vs mean {}", v, mean);
+        }
+
+        tv_denoise(&noisy, 0.0, &mut output);
+        assert_eq!(output, noisy);
+    }
+
     #[test]
     fn empty_trace_noop() {
         let mut trace: Vec<f32> = vec![];
@@ -273,6 +518,34 @@ mod tests {
         }
     }
 
+    #[test]
+    fn rolling_median_matches_sorted_windows() {
+        let mut rng_state = 7u64;
+        let trace: Vec<f32> = (0..300)
+            .map(|_| {
+                rng_state = rng_state.wrapping_mul(6364136223846793005).wrapping_add(1);
+                ((rng_state >> 40) % 17) as f32
+            })
+            .collect();
+        for window in [3, 5, 11] {
+            let mut got = trace.clone();
+            rolling_median(&mut got, window);
+            let half = window / 2;
+            for t in 0..trace.len() {
+                let mut buf =
+                    trace[t.saturating_sub(half)..(t + half + 1).min(trace.len())].to_vec();
+                buf.sort_by(|a, b| a.partial_cmp(b).unwrap());
+                let m = buf.len();
+                let want = if m % 2 == 1 {
+                    buf[m / 2]
+                } else {
+                    0.5 * (buf[m / 2 - 1] + buf[m / 2])
+                };
+                assert_eq!(got[t], want, "window {window}, t={t}");
+            }
+        }
+    }
+
     /// Cross-validate on a trace with repeated values (tests dedup handling).
     #[test]
     fn fenwick_matches_reference_repeated_values() {
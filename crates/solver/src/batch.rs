@@ -0,0 +1,155 @@
+use crate::{Constraint, ConvMode, Solver};
+
+#[cfg(feature = "parallel")]
+use rayon::prelude::*;
+
+/// FISTA iterations per `step_batch` call while solving to convergence.
+const BATCH_SIZE: u32 = 100;
+
+/// Shared settings for solving many independent traces.
+#[derive(Clone, Copy, Debug)]
+pub struct BatchConfig {
+    pub tau_rise: f64,
+    pub tau_decay: f64,
+    pub lambda: f64,
+    pub fs: f64,
+    pub conv_mode: ConvMode,
+    pub constraint: Constraint,
+    pub hp_enabled: bool,
+    pub lp_enabled: bool,
+    pub max_iters: u32,
+}
+
+impl Default for BatchConfig {
+    fn default() -> Self {
+        BatchConfig {
+            tau_rise: 0.02,
+            tau_decay: 0.4,
+            lambda: 0.01,
+            fs: 30.0,
+            conv_mode: ConvMode::Fft,
+            constraint: Constraint::NonNegative,
+            hp_enabled: false,
+            lp_enabled: false,
+            max_iters: 2000,
+        }
+    }
+}
+
+/// Per-trace output of `solve_batch`.
+#[derive(Clone, Debug)]
+pub struct BatchResult {
+    pub solution: Vec<f32>,
+    pub baseline: f64,
+    /// Reconvolution with baseline added (K*s + b).
+    pub reconvolution: Vec<f32>,
+    pub iterations: u32,
+    pub converged: bool,
+}
+
+/// Deconvolve independent traces with shared parameters.
+///
+/// Each trace runs the same pipeline as a single solve: optional bandpass filter,
+/// rolling-baseline subtraction, then FISTA to convergence or `max_iters`.
+///
+/// With the `parallel` feature, traces are distributed across the rayon thread
+/// pool; each worker owns a `Solver` (and so its own `FftConvolver` and working
+/// buffers), reused across the traces it processes. Without it, one `Solver`
+/// handles all traces serially — the only option on single-threaded wasm.
+/// Results are in input order either way.
+pub fn solve_batch<T: AsRef<[f32]> + Sync>(traces: &[T], config: &BatchConfig) -> Vec<BatchResult> {
+    #[cfg(feature = "parallel")]
+    {
+        traces
+            .par_iter()
+            .map_init(
+                || configured_solver(config),
+                |solver, trace| solve_one(solver, trace.as_ref(), config),
+            )
+            .collect()
+    }
+    #[cfg(not(feature = "parallel"))]
+    {
+        let mut solver = configured_solver(config);
+        traces
+            .iter()
+            .map(|trace| solve_one(&mut solver, trace.as_ref(), config))
+            .collect()
+    }
+}
+
+/// Run the solver in batches of `BATCH_SIZE` until convergence or `max_iters`
+/// (rounded up to whole batches). Shared with the Python bindings.
+pub(crate) fn run_to_convergence(solver: &mut Solver, max_iters: u32) {
+    let n_batches = max_iters.div_ceil(BATCH_SIZE);
+    for _ in 0..n_batches {
+        if solver.step_batch(BATCH_SIZE) {
+            break;
+        }
+    }
+}
+
+fn configured_solver(config: &BatchConfig) -> Solver {
+    let mut solver = Solver::new();
+    solver.set_params(config.tau_rise, config.tau_decay, config.lambda, config.fs);
+    solver.set_conv_mode(config.conv_mode);
+    solver.set_constraint(config.constraint);
+    solver.set_hp_filter_enabled(config.hp_enabled);
+    solver.set_lp_filter_enabled(config.lp_enabled);
+    solver
+}
+
+fn solve_one(solver: &mut Solver, trace: &[f32], config: &BatchConfig) -> BatchResult {
+    solver.set_trace(trace);
+    if config.hp_enabled || config.lp_enabled {
+        solver.apply_filter();
+    }
+    solver.subtract_baseline();
+    run_to_convergence(solver, config.max_iters);
+
+    BatchResult {
+        solution: solver.get_solution(),
+        baseline: solver.get_baseline(),
+        reconvolution: solver.get_reconvolution_with_baseline(),
+        iterations: solver.iteration_count(),
+        converged: solver.converged(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::kernel::build_kernel;
+
+    #[test]
+    fn batch_matches_individual_solves() {
+        let kernel = build_kernel(0.02, 0.4, 30.0);
+        let traces: Vec<Vec<f32>> = (0..6)
+            .map(|c| {
+                let n = 150 + 40 * c;
+                let mut trace = vec![0.0_f32; n];
+                for s in [10 + 7 * c, 60, n - 50] {
+                    for (k, &kv) in kernel.iter().enumerate() {
+                        if s + k < n {
+                            trace[s + k] += kv;
+                        }
+                    }
+                }
+                trace
+            })
+            .collect();
+
+        let config = BatchConfig::default();
+        let results = solve_batch(&traces, &config);
+        assert_eq!(results.len(), traces.len());
+
+        for (trace, result) in traces.iter().zip(&results) {
+            let mut solver = configured_solver(&config);
+            let expected = solve_one(&mut solver, trace, &config);
+            assert_eq!(result.solution.len(), trace.len());
+            assert_eq!(result.solution, expected.solution);
+            assert_eq!(result.iterations, expected.iterations);
+            assert_eq!(result.converged, expected.converged);
+        }
+    }
+}
@@ -1,3 +1,4 @@
+use crate::FftPadding;
 use realfft::RealFftPlanner;
 use rustfft::num_complex::Complex;
 use std::sync::Arc;
@@ -12,7 +13,9 @@ use std::sync::Arc;
 /// Buffers grow but never shrink to prevent WASM memory fragmentation.
 pub(crate) struct FftConvolver {
     planner: RealFftPlanner<f32>,
-    fft_len: usize, // padded FFT length (power of 2), 0 = uninitialized
+    fft_len: usize, // padded FFT length, 0 = uninitialized
+    padding: FftPadding,
+    kernel_offset: usize, // kernel samples before t = 0 (acausal kernels)
 
     // Cached FFT plans (Arc from planner, avoids hash-map lookup per call)
     plan_fwd: Option<Arc<dyn realfft::RealToComplex<f32>>>,
@@ -28,6 +31,8 @@ pub(crate) struct FftConvolver {
     fft_spectrum: Vec<Complex<f32>>,
     fft_scratch_fwd: Vec<Complex<f32>>,
     fft_scratch_inv: Vec<Complex<f32>>,
+
+    op_count: u64, // forward + inverse transforms run since the last reset
 }
 
 impl FftConvolver {
@@ -35,6 +40,8 @@ impl FftConvolver {
         FftConvolver {
             planner: RealFftPlanner::new(),
             fft_len: 0,
+            padding: FftPadding::PowerOfTwo,
+            kernel_offset: 0,
             plan_fwd: None,
             plan_inv: None,
             kernel_fft: Vec::new(),
@@ -44,6 +51,7 @@ impl FftConvolver {
             fft_spectrum: Vec::new(),
             fft_scratch_fwd: Vec::new(),
             fft_scratch_inv: Vec::new(),
+            op_count: 0,
         }
     }
 
@@ -52,11 +60,28 @@ impl FftConvolver {
         self.fft_len
     }
 
-    /// Invalidate cached FFT length, forcing a full rebuild on next `ensure_buffers`.
-    pub(crate) fn invalidate(&mut self) {
-        self.fft_len = 0;
-        self.plan_fwd = None;
-        self.plan_inv = None;
+    /// Forward and inverse FFTs run since construction or `reset_op_count`
+    /// (kernel spectra included).
+    pub(crate) fn op_count(&self) -> u64 {
+        self.op_count
+    }
+
+    pub(crate) fn reset_op_count(&mut self) {
+        self.op_count = 0;
+    }
+
+    /// Select how `ensure_buffers` pads `signal_len + k_len - 1`. Takes effect on
+    /// the next `ensure_buffers` call.
+    pub(crate) fn set_padding(&mut self, padding: FftPadding) {
+        self.padding = padding;
+    }
+
+    /// Number of leading kernel samples that sit before t = 0. The kernel spectrum
+    /// is built from the kernel rotated left by this many samples, so forward and
+    /// adjoint outputs stay aligned with the source. Takes effect on the next
+    /// `prepare_kernel` (or `ensure_buffers` with a new length).
+    pub(crate) fn set_kernel_offset(&mut self, offset: usize) {
+        self.kernel_offset = offset;
     }
 
     /// Ensure FFT buffers are allocated for the given signal + kernel size.
@@ -69,7 +94,10 @@ impl FftConvolver {
         }
 
         let min_len = signal_len + k_len - 1;
-        let padded_len = min_len.next_power_of_two();
+        let padded_len = match self.padding {
+            FftPadding::PowerOfTwo => min_len.next_power_of_two(),
+            FftPadding::FastSize => next_fast_len(min_len),
+        };
 
         if padded_len == self.fft_len {
             return; // Already set up for this length
@@ -124,9 +152,14 @@ impl FftConvolver {
         let padded_len = self.fft_len;
         let spectrum_len = padded_len / 2 + 1;
 
-        // Zero-pad kernel into fft_input
-        self.fft_input[..k_len].copy_from_slice(&kernel[..k_len]);
-        self.fft_input[k_len..padded_len].fill(0.0);
+        // Zero-pad kernel into fft_input, wrapping the acausal head (samples before
+        // t = 0) to the end of the buffer. The padded length covers the full linear
+        // convolution, so the wrapped head never aliases onto the causal part.
+        let offset = self.kernel_offset.min(k_len);
+        let causal = k_len - offset;
+        self.fft_input[..causal].copy_from_slice(&kernel[offset..k_len]);
+        self.fft_input[causal..padded_len - offset].fill(0.0);
+        self.fft_input[padded_len - offset..padded_len].copy_from_slice(&kernel[..offset]);
 
         // Forward FFT of kernel
         let fwd = self.plan_fwd.as_ref().expect("plans not initialized");
@@ -136,6 +169,7 @@ impl FftConvolver {
             &mut self.fft_scratch_fwd,
         )
         .unwrap();
+        self.op_count += 1;
 
         // Conjugate for adjoint (correlation = convolution with reversed kernel)
         for i in 0..spectrum_len {
@@ -150,7 +184,21 @@ impl FftConvolver {
         signal_len: usize,
         output: &mut [f32],
     ) {
-        self.convolve_impl(source, signal_len, output, false);
+        self.convolve_impl(source, signal_len, output, signal_len, false);
+    }
+
+    /// FFT-based forward convolution writing `output.len()` samples instead of
+    /// truncating at `signal_len` — e.g. the full `signal_len + k_len - 1 - offset`
+    /// linear convolution from t = 0 on. `output.len()` must not exceed the padded
+    /// FFT length minus the kernel offset.
+    pub(crate) fn convolve_forward_full(
+        &mut self,
+        source: &[f32],
+        signal_len: usize,
+        output: &mut [f32],
+    ) {
+        let out_len = output.len();
+        self.convolve_impl(source, signal_len, output, out_len, false);
     }
 
     /// FFT-based adjoint convolution (correlation): output[..signal_len] = (K^T * source)[..signal_len].
@@ -160,16 +208,18 @@ impl FftConvolver {
         signal_len: usize,
         output: &mut [f32],
     ) {
-        self.convolve_impl(source, signal_len, output, true);
+        self.convolve_impl(source, signal_len, output, signal_len, true);
     }
 
     /// Shared FFT convolution implementation.
-    /// `use_conjugate` selects `kernel_conj_fft` (adjoint) or `kernel_fft` (forward).
+    /// `use_conjugate` selects `kernel_conj_fft` (adjoint) or `kernel_fft` (forward);
+    /// the first `out_len` samples of the result are written to `output`.
     fn convolve_impl(
         &mut self,
         source: &[f32],
         signal_len: usize,
         output: &mut [f32],
+        out_len: usize,
         use_conjugate: bool,
     ) {
         let padded_len = self.fft_len;
@@ -187,16 +237,22 @@ impl FftConvolver {
             &mut self.fft_scratch_fwd,
         )
         .unwrap();
+        self.op_count += 1;
 
         // Pointwise multiply with kernel spectrum
-        if use_conjugate {
-            for i in 0..spectrum_len {
-                self.fft_spectrum[i] *= self.kernel_conj_fft[i];
-            }
+        let kernel_spectrum = if use_conjugate {
+            &self.kernel_conj_fft[..spectrum_len]
         } else {
-            for i in 0..spectrum_len {
-                self.fft_spectrum[i] *= self.kernel_fft[i];
-            }
+            &self.kernel_fft[..spectrum_len]
+        };
+        complex_mul_inplace(&mut self.fft_spectrum[..spectrum_len], kernel_spectrum);
+
+        // The DC (and, for even lengths, Nyquist) bins of a real signal are real.
+        // Clear their imaginary part so roundoff — or NaN from a diverging solve —
+        // never makes the inverse transform reject its input.
+        self.fft_spectrum[0].im = 0.0;
+        if padded_len % 2 == 0 {
+            self.fft_spectrum[spectrum_len - 1].im = 0.0;
         }
 
         // Inverse FFT
@@ -207,15 +263,95 @@ impl FftConvolver {
             &mut self.fft_scratch_inv,
         )
         .unwrap();
+        self.op_count += 1;
 
-        // Normalize and copy first signal_len samples to output
+        // Normalize and copy first out_len samples to output
         let scale = 1.0 / padded_len as f32;
-        for i in 0..signal_len {
+        for i in 0..out_len {
             output[i] = self.fft_output[i] * scale;
         }
     }
 }
 
+/// Smallest 5-smooth number (2^a * 3^b * 5^c) >= n. rustfft's mixed-radix
+/// algorithms handle these sizes efficiently, and they sit much closer to `n`
+/// than the next power of two (at most ~1.25x above, vs up to 2x).
+pub(crate) fn next_fast_len(n: usize) -> usize {
+    if n <= 1 {
+        return 1;
+    }
+    let mut best = n.next_power_of_two();
+    let mut p5 = 1;
+    while p5 < best {
+        let mut p35 = p5;
+        while p35 < best {
+            // Smallest power of two that lifts p35 to >= n
+            let mut candidate = p35;
+            while candidate < n {
+                candidate *= 2;
+            }
+            best = best.min(candidate);
+            p35 *= 3;
+        }
+        p5 *= 5;
+    }
+    best
+}
+
+/// Pointwise complex multiply: `dst[i] *= k[i]`.
+///
+/// Uses WASM SIMD (four complex pairs per iteration) when compiled with the
+/// `simd128` target feature, otherwise the scalar loop.
+#[inline]
+fn complex_mul_inplace(dst: &mut [Complex<f32>], k: &[Complex<f32>]) {
+    #[cfg(all(target_arch = "wasm32", target_feature = "simd128"))]
+    complex_mul_simd128(dst, k);
+    #[cfg(not(all(target_arch = "wasm32", target_feature = "simd128")))]
+    complex_mul_scalar(dst, k);
+}
+
+fn complex_mul_scalar(dst: &mut [Complex<f32>], k: &[Complex<f32>]) {
+    for (d, &k) in dst.iter_mut().zip(k) {
+        *d *= k;
+    }
+}
+
+/// SIMD complex multiply. Each v128 holds two interleaved complex values
+/// [a0.re, a0.im, a1.re, a1.im]; two vectors per iteration cover four pairs.
+///
+/// re = a.re*b.re - a.im*b.im, im = a.im*b.re + a.re*b.im, computed as
+/// a*[b.re, b.re] + [a.im, a.re]*[b.im, b.im]*[-1, +1].
+#[cfg(all(target_arch = "wasm32", target_feature = "simd128"))]
+fn complex_mul_simd128(dst: &mut [Complex<f32>], k: &[Complex<f32>]) {
+    use core::arch::wasm32::*;
+
+    let n = dst.len().min(k.len());
+    let chunks = n / 4;
+    let sign = f32x4(-1.0, 1.0, -1.0, 1.0);
+    let d_ptr = dst.as_mut_ptr() as *mut f32;
+    let k_ptr = k.as_ptr() as *const f32;
+
+    for c in 0..chunks {
+        for half in 0..2 {
+            let off = c * 8 + half * 4;
+            // SAFETY: Complex<f32> is #[repr(C)] { re, im }, so the first 4*chunks
+            // complex values are 8*chunks contiguous f32 and off + 4 <= 2 * n.
+            // v128_load/v128_store permit unaligned addresses.
+            unsafe {
+                let a = v128_load(d_ptr.add(off) as *const v128);
+                let b = v128_load(k_ptr.add(off) as *const v128);
+                let b_re = i32x4_shuffle::<0, 0, 2, 2>(b, b);
+                let b_im = i32x4_shuffle::<1, 1, 3, 3>(b, b);
+                let a_swap = i32x4_shuffle::<1, 0, 3, 2>(a, a);
+                let r = f32x4_add(f32x4_mul(a, b_re), f32x4_mul(f32x4_mul(a_swap, b_im), sign));
+                v128_store(d_ptr.add(off) as *mut v128, r);
+            }
+        }
+    }
+
+    complex_mul_scalar(&mut dst[chunks * 4..n], &k[chunks * 4..n]);
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -293,4 +429,129 @@ mod tests {
             rel_err
         );
     }
+
+    /// Acausal kernel: an impulse maps onto the kernel shifted left by the offset,
+    /// and the adjoint identity still holds.
+    #[test]
+    fn kernel_offset_centers_impulse_response() {
+        let kernel: Vec<f32> = vec![0.25, 0.5, 1.0, 0.5, 0.25, 0.1];
+        let offset = 2;
+        let n = 32;
+
+        let mut conv = FftConvolver::new();
+        conv.set_kernel_offset(offset);
+        conv.ensure_buffers(n, &kernel);
+
+        let mut impulse = vec![0.0_f32; n];
+        impulse[10] = 1.0;
+        let mut output = vec![0.0_f32; n];
+        conv.convolve_forward(&impulse, n, &mut output);
+        for (i, &v) in output.iter().enumerate() {
+            let expected = (i + offset)
+                .checked_sub(10)
+                .and_then(|k| kernel.get(k))
+                .copied()
+                .unwrap_or(0.0);
+            assert!(
+                (v - expected).abs() < 1e-5,
+                "index {}: {} vs {}",
+                i,
+                v,
+                expected
+            );
+        }
+
+        let x: Vec<f32> = (0..n).map(|i| (i as f32 * 0.3).sin()).collect();
+        let y: Vec<f32> = (0..n).map(|i| (i as f32 * 0.7 + 1.0).cos()).collect();
+        let mut kx = vec![0.0_f32; n];
+        let mut kty = vec![0.0_f32; n];
+        conv.convolve_forward(&x, n, &mut kx);
+        conv.convolve_adjoint(&y, n, &mut kty);
+        let lhs: f64 = kx.iter().zip(&y).map(|(&a, &b)| a as f64 * b as f64).sum();
+        let rhs: f64 = x.iter().zip(&kty).map(|(&a, &b)| a as f64 * b as f64).sum();
+        assert!(
+            (lhs - rhs).abs() / lhs.abs().max(1e-10) < 1e-4,
+            "{} vs {}",
+            lhs,
+            rhs
+        );
+    }
+
+    #[test]
+    fn next_fast_len_is_smallest_5_smooth() {
+        let is_smooth = |mut m: usize| {
+            for p in [2, 3, 5] {
+                while m % p == 0 {
+                    m /= p;
+                }
+            }
+            m == 1
+        };
+        for n in 1..2000 {
+            let fast = next_fast_len(n);
+            assert!(fast >= n && is_smooth(fast), "n={} fast={}", n, fast);
+            assert!((n..fast).all(|m| !is_smooth(m)), "n={} fast={}", n, fast);
+        }
+        assert_eq!(next_fast_len(1025), 1080);
+    }
+
+    /// FastSize padding gives the same convolution as power-of-two padding.
+    #[test]
+    fn fast_size_padding_matches_power_of_two() {
+        let kernel = build_kernel(0.02, 0.4, 30.0);
+        let n = 1030 - kernel.len(); // min_len just past 1024
+        let x: Vec<f32> = (0..n).map(|i| (i as f32 * 0.13).sin().max(0.0)).collect();
+
+        let mut pow2 = FftConvolver::new();
+        pow2.ensure_buffers(n, &kernel);
+        let mut fast = FftConvolver::new();
+        fast.set_padding(FftPadding::FastSize);
+        fast.ensure_buffers(n, &kernel);
+        assert_eq!(pow2.fft_len(), 2048);
+        assert_eq!(fast.fft_len(), 1080);
+
+        let mut a = vec![0.0_f32; n];
+        let mut b = vec![0.0_f32; n];
+        pow2.convolve_forward(&x, n, &mut a);
+        fast.convolve_forward(&x, n, &mut b);
+        for i in 0..n {
+            assert!((a[i] - b[i]).abs() < 1e-4, "forward mismatch at {}", i);
+        }
+        pow2.convolve_adjoint(&x, n, &mut a);
+        fast.convolve_adjoint(&x, n, &mut b);
+        for i in 0..n {
+            assert!((a[i] - b[i]).abs() < 1e-4, "adjoint mismatch at {}", i);
+        }
+    }
+
+    /// The dispatching multiply (SIMD on wasm32+simd128) matches the scalar loop,
+    /// including the non-multiple-of-four tail. Natively both sides are the
+    /// scalar loop; CI's wasm job runs it under wasm-bindgen-test to cover SIMD.
+    #[cfg_attr(not(target_arch = "wasm32"), test)]
+    #[cfg_attr(target_arch = "wasm32", wasm_bindgen_test::wasm_bindgen_test)]
+    fn complex_mul_matches_scalar() {
+        let n = 37;
+        let a: Vec<Complex<f32>> = (0..n)
+            .map(|i| Complex::new((i as f32 * 0.37).sin(), (i as f32 * 0.11).cos()))
+            .collect();
+        let b: Vec<Complex<f32>> = (0..n)
+            .map(|i| Complex::new((i as f32 * 0.23 + 1.0).cos(), -(i as f32 * 0.53).sin()))
+            .collect();
+
+        let mut expected = a.clone();
+        complex_mul_scalar(&mut expected, &b);
+        let mut actual = a.clone();
+        complex_mul_inplace(&mut actual, &b);
+
+        for i in 0..n {
+            assert!(
+                (actual[i] - expected[i]).norm() < 1e-6,
+                "Complex multiply mismatch at {}: {} vs {}",
+                i,
+                actual[i],
+                expected[i]
+            );
+            assert!((expected[i] - a[i] * b[i]).norm() < 1e-6);
+        }
+    }
 }
@@ -1,3 +1,4 @@
+use crate::{SpectrumWindow, TaperShape};
 use realfft::RealFftPlanner;
 use rustfft::num_complex::Complex;
 use std::f32::consts::PI;
@@ -20,6 +21,10 @@ pub struct BandpassFilter {
     f_lp: f32,
     fs: f32,
     valid: bool,
+    taper: TaperShape,
+    taper_hp: f32,          // cosine transition half-width as a fraction of f_hp
+    taper_lp: f32,          // cosine transition half-width as a fraction of f_lp
+    window: SpectrumWindow, // applied before the power-spectrum FFT only
 
     // FFT infrastructure
     planner: RealFftPlanner<f32>,
@@ -34,8 +39,17 @@ pub struct BandpassFilter {
     spectrum: Vec<Complex<f32>>,
     gain_curve: Vec<f32>,
     power_spectrum: Vec<f32>,
+    power_len: usize, // trace length the cached power spectrum belongs to (0 = none)
     scratch_fwd: Vec<Complex<f32>>,
     scratch_inv: Vec<Complex<f32>>,
+
+    op_count: u64, // forward + inverse transforms run since the last reset
+
+    // Last apply() result, keyed by its input samples; dropped on config changes
+    cache_input: Vec<f32>,
+    cache_output: Vec<f32>,
+    cache_power: Vec<f32>, // power spectrum cached alongside the output
+    cache_len: usize,      // trace length of the cached result (0 = none)
 }
 
 impl BandpassFilter {
@@ -47,6 +61,10 @@ impl BandpassFilter {
             f_lp: 0.0,
             fs: 30.0,
             valid: false,
+            taper: TaperShape::Cosine,
+            taper_hp: 0.5,
+            taper_lp: 0.5,
+            window: SpectrumWindow::Rectangular,
             planner: RealFftPlanner::new(),
             planned_len: 0,
             plan_fwd: None,
@@ -55,15 +73,21 @@ impl BandpassFilter {
             spectrum: Vec::new(),
             gain_curve: Vec::new(),
             power_spectrum: Vec::new(),
+            power_len: 0,
             scratch_fwd: Vec::new(),
             scratch_inv: Vec::new(),
+            op_count: 0,
+            cache_input: Vec::new(),
+            cache_output: Vec::new(),
+            cache_power: Vec::new(),
+            cache_len: 0,
         }
     }
 
     /// Convenience: set both HP and LP together (used by CaTune's single toggle).
     pub fn set_enabled(&mut self, enabled: bool) {
-        self.hp_enabled = enabled;
-        self.lp_enabled = enabled;
+        self.set_hp_enabled(enabled);
+        self.set_lp_enabled(enabled);
     }
 
     /// Returns true if either HP or LP is active.
@@ -71,18 +95,82 @@ impl BandpassFilter {
         self.hp_enabled || self.lp_enabled
     }
 
+    /// Enable or disable the high-pass band; the gain curve is rebuilt on next use.
     pub fn set_hp_enabled(&mut self, enabled: bool) {
-        self.hp_enabled = enabled;
+        if enabled != self.hp_enabled {
+            self.hp_enabled = enabled;
+            self.planned_len = 0;
+            self.cache_len = 0;
+        }
     }
 
+    /// Enable or disable the low-pass band; the gain curve is rebuilt on next use.
     pub fn set_lp_enabled(&mut self, enabled: bool) {
-        self.lp_enabled = enabled;
+        if enabled != self.lp_enabled {
+            self.lp_enabled = enabled;
+            self.planned_len = 0;
+            self.cache_len = 0;
+        }
     }
 
     pub fn is_hp_enabled(&self) -> bool {
         self.hp_enabled
     }
 
+    pub fn is_lp_enabled(&self) -> bool {
+        self.lp_enabled
+    }
+
+    /// Forward and inverse FFTs run since construction or `reset_op_count`.
+    pub fn op_count(&self) -> u64 {
+        self.op_count
+    }
+
+    pub fn reset_op_count(&mut self) {
+        self.op_count = 0;
+    }
+
+    /// Select the transition-band shape; the gain curve is rebuilt on next use.
+    pub fn set_taper_shape(&mut self, shape: TaperShape) {
+        if shape != self.taper {
+            self.taper = shape;
+            self.planned_len = 0;
+            self.cache_len = 0;
+        }
+    }
+
+    pub fn taper_shape(&self) -> TaperShape {
+        self.taper
+    }
+
+    /// Set the cosine transition half-widths as fractions of each cutoff (the HP
+    /// taper spans `f_hp·(1 ± hp)`, the LP taper `f_lp·(1 ± lp)`). Both must lie in
+    /// (0, 1); returns false and keeps the current widths otherwise.
+    pub fn set_taper_fraction(&mut self, hp: f32, lp: f32) -> bool {
+        if !(hp > 0.0 && hp < 1.0 && lp > 0.0 && lp < 1.0) {
+            return false;
+        }
+        if hp != self.taper_hp || lp != self.taper_lp {
+            self.taper_hp = hp;
+            self.taper_lp = lp;
+            self.planned_len = 0;
+            self.cache_len = 0;
+        }
+        true
+    }
+
+    /// Select the power-spectrum window. Drops the cached spectrum and returns
+    /// true when the window changed.
+    pub fn set_spectrum_window(&mut self, window: SpectrumWindow) -> bool {
+        if window == self.window {
+            return false;
+        }
+        self.window = window;
+        self.power_len = 0;
+        self.cache_len = 0;
+        true
+    }
+
     /// Compute bandpass cutoffs from kernel time constants.
     pub fn update_cutoffs(&mut self, tau_rise: f64, tau_decay: f64, fs: f64) {
         self.fs = fs as f32;
@@ -110,8 +198,9 @@ impl BandpassFilter {
         // and let apply() + build_gain_curve() handle the mode-specific check.
         self.valid = self.f_hp > 0.0 && self.f_lp > 0.0;
 
-        // Invalidate cached gain curve and FFT plans
+        // Invalidate cached gain curve, FFT plans, and filtered result
         self.planned_len = 0;
+        self.cache_len = 0;
         self.plan_fwd = None;
         self.plan_inv = None;
     }
@@ -156,7 +245,7 @@ impl BandpassFilter {
         self.planned_len = n;
     }
 
-    /// Build cosine-tapered gain curve for the active filter mode.
+    /// Build the tapered gain curve for the active filter mode and `TaperShape`.
     ///
     /// - HP+LP: full bandpass (HP taper → passband → LP taper)
     /// - HP-only: HP taper → passband to Nyquist (gain=1.0 above HP)
@@ -165,49 +254,89 @@ impl BandpassFilter {
         let spectrum_len = n / 2 + 1;
         let df = self.fs / n as f32;
 
-        let w_hp = self.f_hp * 0.5;
-        let w_lp = self.f_lp * 0.5;
-
         let hp_on = self.hp_enabled;
         let lp_on = self.lp_enabled;
 
         for i in 0..spectrum_len {
             let f = i as f32 * df;
+            // Contributions are 1.0 when the corresponding filter is disabled
+            let hp_gain = if hp_on { self.hp_gain(f) } else { 1.0 };
+            let lp_gain = if lp_on { self.lp_gain(f) } else { 1.0 };
+            self.gain_curve[i] = hp_gain * lp_gain;
+        }
+    }
 
-            // High-pass contribution (1.0 when disabled)
-            let hp_gain = if !hp_on {
-                1.0
-            } else if f < self.f_hp - w_hp {
-                0.0
-            } else if f < self.f_hp + w_hp {
-                let t = (f - (self.f_hp - w_hp)) / (2.0 * w_hp);
-                0.5 * (1.0 - (PI * t).cos())
-            } else {
-                1.0
-            };
-
-            // Low-pass contribution (1.0 when disabled)
-            let lp_gain = if !lp_on {
-                1.0
-            } else if f < self.f_lp - w_lp {
-                1.0
-            } else if f < self.f_lp + w_lp {
-                let t = (f - (self.f_lp - w_lp)) / (2.0 * w_lp);
-                0.5 * (1.0 + (PI * t).cos())
-            } else {
-                0.0
-            };
+    /// High-pass gain at frequency `f`: 0.5 at `f_hp` for either taper shape.
+    fn hp_gain(&self, f: f32) -> f32 {
+        match self.taper {
+            TaperShape::Cosine => {
+                let w_hp = self.f_hp * self.taper_hp;
+                if f < self.f_hp - w_hp {
+                    0.0
+                } else if f < self.f_hp + w_hp {
+                    let t = (f - (self.f_hp - w_hp)) / (2.0 * w_hp);
+                    0.5 * (1.0 - (PI * t).cos())
+                } else {
+                    1.0
+                }
+            }
+            TaperShape::Gaussian { sigma_hz } => {
+                // The Gaussian never reaches 0, so DC is removed explicitly
+                let edge = self.f_hp + gaussian_half_gain_offset(sigma_hz);
+                if f == 0.0 {
+                    0.0
+                } else if f < edge {
+                    gaussian(f - edge, sigma_hz)
+                } else {
+                    1.0
+                }
+            }
+        }
+    }
 
-            self.gain_curve[i] = hp_gain * lp_gain;
+    /// Low-pass gain at frequency `f`: 0.5 at `f_lp` for either taper shape.
+    fn lp_gain(&self, f: f32) -> f32 {
+        match self.taper {
+            TaperShape::Cosine => {
+                let w_lp = self.f_lp * self.taper_lp;
+                if f < self.f_lp - w_lp {
+                    1.0
+                } else if f < self.f_lp + w_lp {
+                    let t = (f - (self.f_lp - w_lp)) / (2.0 * w_lp);
+                    0.5 * (1.0 + (PI * t).cos())
+                } else {
+                    0.0
+                }
+            }
+            TaperShape::Gaussian { sigma_hz } => {
+                let edge = self.f_lp - gaussian_half_gain_offset(sigma_hz);
+                if f > edge {
+                    gaussian(f - edge, sigma_hz)
+                } else {
+                    1.0
+                }
+            }
         }
     }
 
+    /// Drop the cached power spectrum. Called when a new trace is loaded.
+    pub fn invalidate_power_spectrum(&mut self) {
+        self.power_len = 0;
+    }
+
     /// Perform forward FFT and cache power spectrum. Used by both `apply` and `compute_spectrum_only`.
+    ///
+    /// With a non-rectangular window the power spectrum needs its own FFT of the
+    /// windowed trace, done first; `spectrum` then holds the unwindowed FFT.
     fn forward_fft_and_cache_power(&mut self, trace: &[f32]) {
         let n = trace.len();
         self.ensure_buffers(n);
         let spectrum_len = n / 2 + 1;
 
+        if self.power_len != n && self.window != SpectrumWindow::Rectangular {
+            self.cache_windowed_power(trace);
+        }
+
         // Copy trace into fft_input
         self.fft_input[..n].copy_from_slice(trace);
 
@@ -219,30 +348,94 @@ impl BandpassFilter {
             &mut self.scratch_fwd,
         )
         .unwrap();
+        self.op_count += 1;
+
+        // Cache pre-filter power spectrum, keeping an existing one for this trace
+        // so a repeated apply() doesn't overwrite the raw spectrum with a filtered one
+        if self.power_len != n {
+            for (ps, c) in self.power_spectrum[..spectrum_len]
+                .iter_mut()
+                .zip(&self.spectrum[..spectrum_len])
+            {
+                *ps = c.re * c.re + c.im * c.im;
+            }
+            self.power_len = n;
+        }
+    }
 
-        // Cache pre-filter power spectrum
+    /// Power spectrum of the windowed trace, divided by the squared coherent gain
+    /// (mean window value), into the cache. Buffers must already be sized for n.
+    fn cache_windowed_power(&mut self, trace: &[f32]) {
+        let n = trace.len();
+        let spectrum_len = n / 2 + 1;
+        let mut gain_sum = 0.0_f64;
+        for (i, (dst, &v)) in self.fft_input[..n].iter_mut().zip(trace).enumerate() {
+            let w = window_value(self.window, i, n);
+            gain_sum += w as f64;
+            *dst = v * w;
+        }
+        let fwd = self.plan_fwd.as_ref().expect("plans not initialized");
+        fwd.process_with_scratch(
+            &mut self.fft_input[..n],
+            &mut self.spectrum[..spectrum_len],
+            &mut self.scratch_fwd,
+        )
+        .unwrap();
+        self.op_count += 1;
+        let coherent_gain = gain_sum / n as f64;
+        let scale = (1.0 / (coherent_gain * coherent_gain)) as f32;
         for (ps, c) in self.power_spectrum[..spectrum_len]
             .iter_mut()
             .zip(&self.spectrum[..spectrum_len])
         {
-            *ps = c.re * c.re + c.im * c.im;
+            *ps = (c.re * c.re + c.im * c.im) * scale;
         }
+        self.power_len = n;
+    }
+
+    /// Whether the derived band is usable for the enabled filter mode: positive
+    /// cutoffs, and f_hp < f_lp when both HP and LP are on.
+    pub fn is_valid(&self) -> bool {
+        // Mode-specific validity: HP+LP requires f_hp < f_lp
+        self.valid && !(self.hp_enabled && self.lp_enabled && self.f_hp >= self.f_lp)
     }
 
     /// Apply bandpass filter in-place. Caches power spectrum. Returns false if skipped.
+    ///
+    /// The result is cached keyed by the input samples: re-applying with the same
+    /// input and settings (e.g. the UI reloading an unchanged trace to toggle the
+    /// filter view) copies it back without any FFT. Cutoff, band, taper, and
+    /// window changes drop the cache.
+    ///
+    /// Pure spectral filtering: no baseline or percentile shift follows, so with
+    /// HP on the output is zero-mean (DC gain 0) and usable for spectral analysis
+    /// as is. Flooring the trace for the non-negative solve is the separate
+    /// `Solver::subtract_baseline` step.
     pub fn apply(&mut self, trace: &mut [f32]) -> bool {
-        if !self.is_enabled() || !self.valid || trace.len() < 8 {
+        if !self.is_enabled() || !self.is_valid() || trace.len() < 8 {
             return false;
         }
 
-        // Mode-specific validity: HP+LP requires f_hp < f_lp
-        if self.hp_enabled && self.lp_enabled && self.f_hp >= self.f_lp {
-            return false;
+        let n = trace.len();
+        let spectrum_len = n / 2 + 1;
+        if self.cache_len == n && trace == &self.cache_input[..n] {
+            trace.copy_from_slice(&self.cache_output[..n]);
+            if self.power_len != n {
+                self.ensure_buffers(n);
+                self.power_spectrum[..spectrum_len]
+                    .copy_from_slice(&self.cache_power[..spectrum_len]);
+                self.power_len = n;
+            }
+            return true;
+        }
+        if self.cache_input.len() < n {
+            self.cache_input.resize(n, 0.0);
+            self.cache_output.resize(n, 0.0);
+            self.cache_power.resize(spectrum_len, 0.0);
         }
+        self.cache_input[..n].copy_from_slice(trace);
 
-        let n = trace.len();
         self.forward_fft_and_cache_power(trace);
-        let spectrum_len = n / 2 + 1;
 
         // Apply gain curve
         for (s, &g) in self.spectrum[..spectrum_len]
@@ -260,6 +453,7 @@ impl BandpassFilter {
             &mut self.scratch_inv,
         )
         .unwrap();
+        self.op_count += 1;
 
         // Normalize (realfft doesn't normalize)
         let scale = 1.0 / n as f32;
@@ -267,6 +461,9 @@ impl BandpassFilter {
             *t = f * scale;
         }
 
+        self.cache_output[..n].copy_from_slice(trace);
+        self.cache_power[..spectrum_len].copy_from_slice(&self.power_spectrum[..spectrum_len]);
+        self.cache_len = n;
         true
     }
 
@@ -278,10 +475,11 @@ impl BandpassFilter {
         self.forward_fft_and_cache_power(trace);
     }
 
-    /// Get power spectrum (N/2+1 bins of |FFT|²).
+    /// Get the cached power spectrum (N/2+1 bins of |FFT|²), or an empty slice if
+    /// none is cached for a trace of length `n`.
     pub fn get_power_spectrum(&self, n: usize) -> &[f32] {
         let spectrum_len = n / 2 + 1;
-        if self.power_spectrum.len() >= spectrum_len {
+        if self.power_len == n && n > 0 {
             &self.power_spectrum[..spectrum_len]
         } else {
             &[]
@@ -299,6 +497,99 @@ impl BandpassFilter {
     pub fn get_cutoffs(&self) -> [f32; 2] {
         [self.f_hp, self.f_lp]
     }
+
+    /// Equivalent noise bandwidth in Hz of the spectrum window over `n` samples:
+    /// `fs·Σw²/(Σw)²`, i.e. `fs/n` for the rectangular window. 0 for `n < 2`.
+    pub fn enbw(&self, n: usize) -> f32 {
+        if n < 2 {
+            return 0.0;
+        }
+        let (mut sum, mut sum_sq) = (0.0_f64, 0.0_f64);
+        for i in 0..n {
+            let w = window_value(self.window, i, n) as f64;
+            sum += w;
+            sum_sq += w * w;
+        }
+        (self.fs as f64 * sum_sq / (sum * sum)) as f32
+    }
+
+    /// Group delay in samples. Every gain curve here (cosine or Gaussian taper) is
+    /// real-valued, so the filter is zero-phase and this is 0; a causal filter
+    /// mode would report its delay here.
+    pub fn phase_delay(&self) -> f32 {
+        0.0
+    }
+}
+
+/// Log-spaced bin edges from the first non-DC bin `df` to Nyquist `df·(len-1)`,
+/// `n_bins + 1` values. Empty unless there are at least two non-DC bins.
+fn log_bin_edges(spectrum_len: usize, df: f32, n_bins: usize) -> Vec<f64> {
+    if n_bins == 0 || spectrum_len < 3 || df <= 0.0 {
+        return Vec::new();
+    }
+    let lo = df as f64;
+    let ratio = (spectrum_len - 1) as f64; // f_max / f_min
+    (0..=n_bins)
+        .map(|k| lo * ratio.powf(k as f64 / n_bins as f64))
+        .collect()
+}
+
+/// Geometric bin centers (Hz) of `log_rebin` for the same arguments.
+pub(crate) fn log_bin_centers(spectrum_len: usize, df: f32, n_bins: usize) -> Vec<f32> {
+    log_bin_edges(spectrum_len, df, n_bins)
+        .windows(2)
+        .map(|e| (e[0] * e[1]).sqrt() as f32)
+        .collect()
+}
+
+/// Rebin a linear power spectrum (bin spacing `df`, DC at 0) onto `n_bins`
+/// log-spaced bins from `df` to Nyquist, averaging the power of the linear bins
+/// in each. Log bins narrower than `df` (at the low end) take the linear bin
+/// nearest their center. DC is excluded.
+pub(crate) fn log_rebin(power: &[f32], df: f32, n_bins: usize) -> Vec<f32> {
+    let edges = log_bin_edges(power.len(), df, n_bins);
+    let last = power.len() - 1;
+    edges
+        .windows(2)
+        .enumerate()
+        .map(|(k, e)| {
+            // Linear bins i with e[0] <= i·df < e[1] (the final bin includes Nyquist)
+            let start = ((e[0] / df as f64).ceil() as usize).max(1);
+            let end = if k + 1 == n_bins {
+                last + 1
+            } else {
+                ((e[1] / df as f64).ceil() as usize).min(last + 1)
+            };
+            if start < end {
+                let sum: f64 = power[start..end].iter().map(|&p| p as f64).sum();
+                (sum / (end - start) as f64) as f32
+            } else {
+                let center = (e[0] * e[1]).sqrt() / df as f64;
+                power[(center.round() as usize).clamp(1, last)]
+            }
+        })
+        .collect()
+}
+
+/// Symmetric window value at sample `i` of `n` (n >= 2).
+fn window_value(window: SpectrumWindow, i: usize, n: usize) -> f32 {
+    let x = 2.0 * PI * i as f32 / (n - 1) as f32;
+    match window {
+        SpectrumWindow::Rectangular => 1.0,
+        SpectrumWindow::Hann => 0.5 - 0.5 * x.cos(),
+        SpectrumWindow::Hamming => 0.54 - 0.46 * x.cos(),
+        SpectrumWindow::Blackman => 0.42 - 0.5 * x.cos() + 0.08 * (2.0 * x).cos(),
+    }
+}
+
+/// Unit-peak Gaussian exp(-x² / 2σ²).
+fn gaussian(x: f32, sigma: f32) -> f32 {
+    (-0.5 * x * x / (sigma * sigma)).exp()
+}
+
+/// Distance from a Gaussian's peak to its half-gain point: σ·sqrt(2 ln 2).
+fn gaussian_half_gain_offset(sigma: f32) -> f32 {
+    sigma * (2.0 * std::f32::consts::LN_2).sqrt()
 }
 
 #[cfg(test)]
@@ -322,6 +613,30 @@ mod tests {
         assert!((f.f_lp - 15.0).abs() < 0.01);
     }
 
+    #[test]
+    fn test_zero_phase_keeps_impulse_centered() {
+        for shape in [TaperShape::Cosine, TaperShape::Gaussian { sigma_hz: 0.5 }] {
+            let mut f = make_filter(0.02, 0.4, 100.0);
+            f.set_taper_shape(shape);
+            assert_eq!(f.phase_delay(), 0.0);
+
+            let n = 1024;
+            let c = n / 2;
+            let mut trace = vec![0.0_f32; n];
+            trace[c] = 1.0;
+            assert!(f.apply(&mut trace));
+            // Real gain: the response is symmetric about the impulse, no shift
+            for k in 1..50 {
+                assert!(
+                    (trace[c + k] - trace[c - k]).abs() < 1e-4,
+                    "{shape:?} k={k}: {} vs {}",
+                    trace[c + k],
+                    trace[c - k]
+                );
+            }
+        }
+    }
+
     #[test]
     fn test_passband_preservation() {
         let mut f = make_filter(0.02, 0.4, 100.0);
@@ -388,6 +703,33 @@ mod tests {
         assert!(mean.abs() < 0.1, "DC not removed, mean: {}", mean);
     }
 
+    #[test]
+    fn test_cached_result_skips_fft() {
+        let mut f = make_filter(0.02, 0.4, 100.0);
+        let input: Vec<f32> = (0..256).map(|i| (i as f32 * 0.3).sin() + 2.0).collect();
+        let mut first = input.clone();
+        assert!(f.apply(&mut first));
+        let ops = f.op_count();
+
+        // Same input and settings: cached copy, no FFT
+        let mut second = input.clone();
+        assert!(f.apply(&mut second));
+        assert_eq!(second, first);
+        assert_eq!(f.op_count(), ops);
+
+        // Different input, or a band change, filters again
+        let mut other = input.clone();
+        other[10] += 1.0;
+        assert!(f.apply(&mut other));
+        assert!(f.op_count() > ops);
+        let ops = f.op_count();
+        f.set_lp_enabled(false);
+        let mut hp_only = input.clone();
+        assert!(f.apply(&mut hp_only));
+        assert!(f.op_count() > ops);
+        assert_ne!(hp_only, first);
+    }
+
     #[test]
     fn test_round_trip_fft() {
         let mut f = make_filter(0.001, 10.0, 100.0);
@@ -441,8 +783,13 @@ mod tests {
         // tau_rise very large, tau_decay very small -> f_hp > f_lp
         f.update_cutoffs(10.0, 0.001, 30.0);
         f.set_enabled(true); // both HP+LP: requires f_hp < f_lp
+        assert!(!f.is_valid());
         let mut trace = vec![1.0; 64];
         assert!(!f.apply(&mut trace));
+
+        // HP alone only needs a positive cutoff
+        f.set_lp_enabled(false);
+        assert!(f.is_valid());
     }
 
     #[test]
@@ -575,4 +922,123 @@ mod tests {
             filtered_power / original_power
         );
     }
+
+    #[test]
+    fn test_gaussian_taper_half_gain_at_cutoffs() {
+        let mut f = BandpassFilter::new();
+        f.update_cutoffs(0.1, 0.4, 100.0); // f_hp ≈ 0.0249 Hz, f_lp ≈ 6.37 Hz
+        f.set_enabled(true);
+        let n = 4000; // df = 0.025 Hz
+        f.ensure_buffers(n);
+        let cosine = f.gain_curve[..n / 2 + 1].to_vec();
+
+        f.set_taper_shape(TaperShape::Gaussian { sigma_hz: 1.0 });
+        f.ensure_buffers(n);
+        let gain = |f: &BandpassFilter, hz: f32| f.gain_curve[(hz / 0.025).round() as usize];
+        assert_eq!(f.gain_curve[0], 0.0);
+        assert!((gain(&f, 6.375) - 0.5).abs() < 0.01, "{}", gain(&f, 6.375));
+        assert!(gain(&f, 2.0) > 0.99);
+        // Smooth decay instead of the cosine's hard zero one half-width past f_lp
+        let past_cosine_edge = gain(&f, 9.6);
+        assert!(past_cosine_edge > 0.0 && past_cosine_edge < 0.05);
+        assert!(gain(&f, 14.0) < 1e-6);
+
+        // Switching back rebuilds the default curve exactly
+        f.set_taper_shape(TaperShape::Cosine);
+        f.ensure_buffers(n);
+        assert_eq!(&f.gain_curve[..n / 2 + 1], &cosine[..]);
+    }
+
+    #[test]
+    fn test_taper_fraction_widens_transition() {
+        let mut f = BandpassFilter::new();
+        f.update_cutoffs(0.1, 0.4, 100.0); // f_lp ≈ 6.37 Hz
+        f.set_hp_enabled(false);
+        f.set_lp_enabled(true);
+        let n = 4000; // df = 0.025 Hz
+        let bin = |hz: f32| (hz / 0.025).round() as usize;
+
+        // Default 0.5: the LP taper spans f_lp·[0.5, 1.5]
+        f.ensure_buffers(n);
+        assert_eq!(f.gain_curve[bin(3.0)], 1.0);
+        assert_eq!(f.gain_curve[bin(9.8)], 0.0);
+
+        assert!(f.set_taper_fraction(0.5, 0.2));
+        f.ensure_buffers(n);
+        assert_eq!(f.gain_curve[bin(4.5)], 1.0);
+        assert_eq!(f.gain_curve[bin(7.8)], 0.0);
+        assert!((f.gain_curve[bin(6.375)] - 0.5).abs() < 0.01);
+
+        for (hp, lp) in [(0.0, 0.5), (0.5, 1.0), (f32::NAN, 0.5)] {
+            assert!(!f.set_taper_fraction(hp, lp));
+        }
+        assert_eq!((f.taper_hp, f.taper_lp), (0.5, 0.2));
+    }
+
+    #[test]
+    fn test_log_rebin_averages_linear_bins() {
+        // 1 + 8 bins, df = 1 Hz: edges 1, 2, 4, 8 for 3 bins
+        let power: Vec<f32> = (0..9).map(|i| i as f32).collect();
+        let centers = log_bin_centers(9, 1.0, 3);
+        assert_eq!(centers.len(), 3);
+        assert!((centers[0] - 2.0_f32.sqrt()).abs() < 1e-5);
+        let binned = log_rebin(&power, 1.0, 3);
+        // [1, 2) → {1}, [2, 4) → {2, 3}, [4, 8] → {4..8}
+        assert_eq!(binned, vec![1.0, 2.5, 6.0]);
+
+        // More log bins than linear ones at the low end: nearest linear bin
+        let fine = log_rebin(&power, 1.0, 24);
+        assert_eq!(fine.len(), 24);
+        assert_eq!(fine[0], 1.0);
+        assert!(fine.windows(2).all(|w| w[1] >= w[0]));
+
+        assert!(log_rebin(&power[..2], 1.0, 4).is_empty());
+    }
+
+    #[test]
+    fn test_spectrum_window_reduces_leakage() {
+        let n = 512;
+        let fs = 30.0_f32;
+        // Strong off-bin low-frequency component plus an on-bin line at bin 100
+        let trace: Vec<f32> = (0..n)
+            .map(|i| {
+                let t = i as f32 / fs;
+                10.0 * (2.0 * PI * 0.31 * t).sin() + (2.0 * PI * 100.0 * fs / n as f32 * t).sin()
+            })
+            .collect();
+
+        let mut f = BandpassFilter::new();
+        f.update_cutoffs(0.02, 0.4, fs as f64);
+        f.compute_spectrum_only(&trace);
+        let rect = f.get_power_spectrum(n).to_vec();
+
+        assert!(f.set_spectrum_window(SpectrumWindow::Hann));
+        assert!(
+            f.get_power_spectrum(n).is_empty(),
+            "window change drops the cache"
+        );
+        assert!(!f.set_spectrum_window(SpectrumWindow::Hann));
+        f.compute_spectrum_only(&trace);
+        let hann = f.get_power_spectrum(n).to_vec();
+
+        // Far from both lines, leakage drops by orders of magnitude
+        assert!(hann[50] < 1e-3 * rect[50], "{} vs {}", hann[50], rect[50]);
+        // Coherent-gain compensation keeps the on-bin line level comparable
+        assert!(
+            (hann[100] / rect[100] - 1.0).abs() < 0.1,
+            "{} vs {}",
+            hann[100],
+            rect[100]
+        );
+
+        // apply() keeps filtering unwindowed but caches the windowed spectrum
+        let mut filtered = trace.clone();
+        let mut g = make_filter(0.02, 0.4, fs as f64);
+        g.set_spectrum_window(SpectrumWindow::Hann);
+        assert!(g.apply(&mut filtered));
+        assert_eq!(g.get_power_spectrum(n), &hann[..]);
+        let mut plain = trace.clone();
+        make_filter(0.02, 0.4, fs as f64).apply(&mut plain);
+        assert_eq!(filtered, plain);
+    }
 }
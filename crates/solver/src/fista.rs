@@ -1,8 +1,68 @@
-use crate::{Constraint, ConvMode, Solver};
+use crate::{
+    Constraint, ConvMode, ConvergenceMode, ConvergenceStatus, DataTerm, Solver, MIN_POISSON_RATE,
+};
+use std::ops::{Add, Mul, Sub};
+use std::sync::atomic::Ordering;
 
 #[cfg(feature = "jsbindings")]
 use wasm_bindgen::prelude::*;
 
+/// Iterations without rel_change halving before a solve counts as stalled.
+const STALL_ITERS: u32 = 1000;
+
+/// Consecutive runaway batches (see `update_status_tracking`) that count as diverging.
+const DIVERGE_BATCHES: u32 = 3;
+
+/// Iterations per internal `step_batch` call in `step_until_converged`.
+const UNTIL_CONVERGED_CHUNK: u32 = 100;
+
+/// Reweighting floor for `refine_reweighted`, as a fraction of the largest spike.
+/// Samples far below it keep their full penalty; a spike at the floor gets half.
+const REWEIGHT_EPS_FRAC: f32 = 0.1;
+
+/// Diagnostics from one `step_batch_detailed` call.
+///
+/// Exposed to JS through getter methods; fields stay private so wasm-bindgen
+/// hands out an opaque handle instead of copying each field across the boundary.
+#[derive(Clone, Copy, Debug)]
+#[cfg_attr(feature = "jsbindings", wasm_bindgen)]
+pub struct StepResult {
+    iteration: u32,
+    rel_change: f64,
+    restarted: bool,
+    baseline: f64,
+    converged: bool,
+}
+
+#[cfg_attr(feature = "jsbindings", wasm_bindgen)]
+impl StepResult {
+    /// Total FISTA iterations since `set_trace` (or the loaded warm-start state).
+    pub fn iteration(&self) -> u32 {
+        self.iteration
+    }
+
+    /// Relative primal change ||x_{k+1} - x_k|| / ||x_k|| of the last iteration.
+    /// Infinite before the first iteration.
+    pub fn rel_change(&self) -> f64 {
+        self.rel_change
+    }
+
+    /// Whether an adaptive restart fired during this batch.
+    pub fn restarted(&self) -> bool {
+        self.restarted
+    }
+
+    /// Raw (un-smoothed) baseline used in the last iteration.
+    pub fn baseline(&self) -> f64 {
+        self.baseline
+    }
+
+    /// Whether the solver had converged when the batch returned.
+    pub fn converged(&self) -> bool {
+        self.converged
+    }
+}
+
 #[cfg_attr(feature = "jsbindings", wasm_bindgen)]
 impl Solver {
     /// Run n_steps of FISTA iterations. Returns true if converged.
@@ -23,16 +83,40 @@ impl Solver {
         let n = self.active_len;
         if n == 0 {
             self.converged = true;
+            self.last_batch_micros = 0.0;
             return true;
         }
 
-        let step_size = 1.0 / self.lipschitz_constant;
-        let threshold = step_size * self.effective_lambda();
+        let start_micros = crate::timing::now_micros();
+        let start_iteration = self.iteration;
+        self.batch_restarted = false;
+        self.batch_cancelled = false;
+        self.batch_diverged = false;
+        let growth_sq = self.divergence_factor * self.divergence_factor;
+        let mut prev_xk_sq = 0.0_f64;
+
+        // The asymmetric data term scales the gradient's Lipschitz bound by max w
+        let mut step_size = 1.0 / (self.lipschitz_constant * (1.0 + self.asymmetry.abs()));
+        let mut threshold = step_size * self.l1_weight();
+        let poisson = self.data_term == DataTerm::Poisson;
+        let (poisson_scale, trace_max) = if poisson {
+            let trace = self.active_trace();
+            let max = trace.iter().fold(0.0_f64, |m, &y| m.max(y as f64));
+            (self.poisson_scale(), max)
+        } else {
+            (1.0, 0.0)
+        };
         let tol_sq = self.tolerance * self.tolerance;
+        let signal_sq = signal_scale_sq(self.active_trace(), &self.kernel);
 
         for _ in 0..n_steps {
             if self.converged {
-                return true;
+                break;
+            }
+            // Cooperative cancel: checked only between whole iterations
+            if self.cancel_flag.swap(false, Ordering::Relaxed) {
+                self.batch_cancelled = true;
+                break;
             }
 
             // solution_prev holds the extrapolated point y_k
@@ -50,20 +134,87 @@ impl Solver {
                     .convolve_forward(&self.solution_prev[..n], &mut self.reconvolution[..n]),
             }
 
+            // 1a. Optional gain: g = argmin ||g*K*y_k + b - trace||² with the
+            //     previous b, then fold g into the reconvolution so the baseline,
+            //     residual, and objective all see g*K*y_k. Engaged only once the
+            //     g = 1 fit has converged, so g is not fitted to the tiny early
+            //     iterates. The iterates are first rescaled to max 1 (see
+            //     normalize_gain_scale), which leaves the objective unchanged.
+            if self.gain_engaged {
+                self.normalize_gain_scale();
+                self.gain = self.estimate_gain_ls();
+                let g = self.gain as f32;
+                for r in &mut self.reconvolution[..n] {
+                    *r *= g;
+                }
+            }
+
             // 1b. Compute baseline: b = mean(trace - K*y_k)
             //     Skip when bandpass-filtered — DC is already removed, and the baseline
             //     mathematically cancels in the gradient (residual = mean-centered signals).
             //     Computing it anyway would produce pure momentum-oscillation noise.
-            if !self.filtered {
-                let raw =
-                    crate::compute_raw_baseline(&self.trace[..n], &self.reconvolution[..n], n);
+            //     A TV baseline is a per-sample series that does not cancel, so it is
+            //     always re-solved (exactly, for the current y_k). A fixed baseline
+            //     is never re-estimated: b stays at the user's value.
+            if let Some(b) = self.fixed_baseline {
+                self.baseline = b;
+            } else if self.tv_baseline_active() {
+                let mean = self.estimate_baseline_tv();
+                self.update_baseline_ema(mean);
+            } else if !self.baseline_removed() {
+                let raw = self.estimate_baseline();
                 self.update_baseline_ema(raw);
             }
 
             // 2. Compute residual = K * y_k + b - trace
-            let baseline_f32 = self.baseline as f32;
-            for i in 0..n {
-                self.residual_buf[i] = self.reconvolution[i] + baseline_f32 - self.trace[i];
+            let trace = if self.use_filtered_trace {
+                &self.trace[..n]
+            } else {
+                &self.raw_trace[self.window_start..self.window_start + n]
+            };
+            //    Poisson: the scaled NLL gradient's "residual" is c·(1 - y/μ), with
+            //    μ = K*y_k + b and c = poisson_scale, and its curvature is bounded
+            //    by c·L·max(y)/min(b)² (μ >= b) for this b
+            if poisson {
+                let tv = self.tv_baseline_active();
+                let b_min = if tv {
+                    self.baseline_series[..n]
+                        .iter()
+                        .fold(f64::INFINITY, |m, &b| m.min(b as f64))
+                } else {
+                    self.baseline
+                }
+                .max(MIN_POISSON_RATE);
+                let curvature = poisson_scale * trace_max / (b_min * b_min);
+                step_size = 1.0 / (self.lipschitz_constant * curvature.max(1e-12));
+                threshold = step_size * self.l1_weight();
+                let c = poisson_scale as f32;
+                for i in 0..n {
+                    let b = if tv {
+                        self.baseline_series[i]
+                    } else {
+                        self.baseline as f32
+                    };
+                    let mu = (self.reconvolution[i] + b).max(MIN_POISSON_RATE as f32);
+                    self.residual_buf[i] = c * (1.0 - trace[i] / mu);
+                }
+            } else if self.tv_baseline_active() {
+                for i in 0..n {
+                    self.residual_buf[i] =
+                        self.reconvolution[i] + self.baseline_series[i] - trace[i];
+                }
+            } else {
+                let baseline_f32 = self.baseline as f32;
+                for i in 0..n {
+                    self.residual_buf[i] = self.reconvolution[i] + baseline_f32 - trace[i];
+                }
+            }
+            // 2b. Asymmetric data term: gradient of ½·w(r)·r² is w(r)·r
+            if self.asymmetry != 0.0 && !poisson {
+                let (over, under) = ((1.0 - self.asymmetry) as f32, (1.0 + self.asymmetry) as f32);
+                for r in &mut self.residual_buf[..n] {
+                    *r *= if *r > 0.0 { over } else { under };
+                }
             }
 
             // 3. Adjoint convolution: gradient = K^T * residual
@@ -80,72 +231,77 @@ impl Solver {
             // 4. Loop A (fused): save x_k + proximal gradient step
             //    x_{k+1} = prox(y_k - step_size * gradient)
             //    Constraint match hoisted outside inner loop for SIMD auto-vectorization.
-            let step_f32 = step_size as f32;
-            let thresh_f32 = threshold as f32;
+            //    The per-sample lambda map (all-ones by default) scales the threshold.
+            //    A gain g scales the gradient by g and the Lipschitz bound by g², so
+            //    the step along the unscaled K^T residual is step/g; the L1 weight
+            //    applies to g*s, so the threshold is (step/g²)·g = threshold/g.
+            let step_f32 = (step_size / self.gain) as f32;
+            let thresh_f32 = (threshold / self.gain) as f32;
+            let (x, x_old) = (&mut self.solution[..n], &mut self.residual_buf[..n]);
+            let (y, grad, map) = (
+                &self.solution_prev[..n],
+                &self.gradient[..n],
+                &self.lambda_map[..n],
+            );
             match self.constraint {
                 Constraint::NonNegative => {
-                    for i in 0..n {
-                        let x_old = self.solution[i];
-                        self.residual_buf[i] = x_old;
-                        let z = self.solution_prev[i] - step_f32 * self.gradient[i];
-                        self.solution[i] = (z - thresh_f32).max(0.0);
-                    }
+                    prox_step(x, x_old, y, grad, map, step_f32, thresh_f32, |z, t| {
+                        (z - t).max(0.0)
+                    })
                 }
                 Constraint::Box01 => {
-                    for i in 0..n {
-                        let x_old = self.solution[i];
-                        self.residual_buf[i] = x_old;
-                        let z = self.solution_prev[i] - step_f32 * self.gradient[i];
-                        self.solution[i] = (z - thresh_f32).clamp(0.0, 1.0);
+                    prox_step(x, x_old, y, grad, map, step_f32, thresh_f32, |z, t| {
+                        (z - t).clamp(0.0, 1.0)
+                    })
+                }
+                Constraint::Signed => {
+                    prox_step(x, x_old, y, grad, map, step_f32, thresh_f32, |z, t| {
+                        (z.abs() - t).max(0.0).copysign(z)
+                    })
+                }
+            }
+
+            // 4b. Fixed spike support: project onto the allowed locations. x_k and
+            //     y_k are already zero off-support, so the extrapolation stays there.
+            if self.support_active {
+                for i in 0..n {
+                    self.solution[i] *= self.support_mask[i];
+                }
+            }
+
+            // 4c. Optional amplitude floor (heuristic, off by default); engaged
+            //     only after the plain L1 iteration has converged
+            if self.min_spike_engaged {
+                let floor = self.min_spike;
+                for v in &mut self.solution[..n] {
+                    if v.abs() < floor {
+                        *v = 0.0;
                     }
                 }
             }
 
             self.iteration += 1;
+            if let Some(iters) = self.warm_start_iters.as_mut() {
+                *iters += 1;
+            }
 
             // 5+6. Fused Loop B+C: convergence/restart accumulators + momentum extrapolation.
             // Compute tentative momentum BEFORE the loop (only depends on self.t_fista).
             // On restart (rare), correct with a single copy_from_slice afterwards.
-            let t_new = (1.0 + (1.0 + 4.0 * self.t_fista * self.t_fista).sqrt()) / 2.0;
+            let t_new = next_t(self.t_fista);
             let momentum = ((self.t_fista - 1.0) / t_new) as f32;
             let check_restart = self.iteration > 1;
 
-            let mut diff_sq = 0.0_f64;
-            let mut xk_sq = 0.0_f64;
-            let mut dot = 0.0_f64;
-
             // Constraint match hoisted outside the inner loop for SIMD auto-vectorization.
             // The `dot` accumulator is always computed (one fma per element) to avoid
             // duplicating the loop body for the check_restart branch.
-            match self.constraint {
-                Constraint::NonNegative => {
-                    for i in 0..n {
-                        let x_new = self.solution[i];
-                        let x_old = self.residual_buf[i];
-                        let x_new_f64 = x_new as f64;
-                        let x_old_f64 = x_old as f64;
-                        let d = x_new_f64 - x_old_f64;
-                        diff_sq += d * d;
-                        xk_sq += x_old_f64 * x_old_f64;
-                        dot += (self.solution_prev[i] as f64 - x_new_f64) * d;
-                        self.solution_prev[i] = (x_new + momentum * (x_new - x_old)).max(0.0);
-                    }
-                }
-                Constraint::Box01 => {
-                    for i in 0..n {
-                        let x_new = self.solution[i];
-                        let x_old = self.residual_buf[i];
-                        let x_new_f64 = x_new as f64;
-                        let x_old_f64 = x_old as f64;
-                        let d = x_new_f64 - x_old_f64;
-                        diff_sq += d * d;
-                        xk_sq += x_old_f64 * x_old_f64;
-                        dot += (self.solution_prev[i] as f64 - x_new_f64) * d;
-                        self.solution_prev[i] =
-                            (x_new + momentum * (x_new - x_old)).clamp(0.0, 1.0);
-                    }
-                }
-            }
+            let (x, x_old) = (&self.solution[..n], &self.residual_buf[..n]);
+            let y = &mut self.solution_prev[..n];
+            let (diff_sq, xk_sq, dot) = match self.constraint {
+                Constraint::NonNegative => extrapolate(x, x_old, y, momentum, |v| v.max(0.0)),
+                Constraint::Box01 => extrapolate(x, x_old, y, momentum, |v| v.clamp(0.0, 1.0)),
+                Constraint::Signed => extrapolate(x, x_old, y, momentum, |v| v),
+            };
 
             // Adaptive restart: if momentum hurt progress, reset.
             // Undo the speculative momentum by setting solution_prev = solution.
@@ -154,46 +310,442 @@ impl Solver {
             if check_restart && dot > 0.0 {
                 self.t_fista = 1.0;
                 self.solution_prev[..n].copy_from_slice(&self.solution[..n]);
+                self.batch_restarted = true;
+                self.restart_count += 1;
             } else {
                 self.t_fista = t_new;
             }
 
-            // 7. Convergence check using primal residual (squared comparison)
-            if self.iteration > 5 && diff_sq < tol_sq * (xk_sq + 1e-20) {
-                self.converged = true;
+            // 7. Convergence check: primal residual (squared comparison), or the
+            //    relative objective change at x_{k+1}, which needs K*x_{k+1}.
+            //    reconvolution is overwritten at the top of the next iteration anyway.
+            //    Objective sampling for plots reuses the same K*x_{k+1}.
+            self.rel_change = (diff_sq / (xk_sq + 1e-20)).sqrt();
+            // Optional early divergence check: ||x_k|| jumped by more than the
+            // factor since the previous iteration (or went non-finite)
+            if growth_sq > 0.0
+                && (!xk_sq.is_finite() || (prev_xk_sq > 0.0 && xk_sq > growth_sq * prev_xk_sq))
+            {
+                self.batch_diverged = true;
+                self.reconvolution_stale = true;
+                break;
+            }
+            prev_xk_sq = xk_sq;
+            if xk_sq > 0.0 && self.progress_ref_rel_change.is_infinite() {
+                self.progress_ref_rel_change = self.rel_change;
+            }
+            let sample_objective = self.objective_sample_every > 0
+                && self.iteration % self.objective_sample_every == 0;
+            match self.convergence_mode {
+                ConvergenceMode::PrimalResidual => {
+                    if self.warmup_done() && primal_converged(diff_sq, xk_sq, tol_sq, signal_sq) {
+                        self.converged = true;
+                    }
+                    if sample_objective {
+                        self.convolve_solution();
+                        let objective = self.compute_objective();
+                        self.objective_history.push(objective);
+                    }
+                }
+                ConvergenceMode::ObjectiveChange => {
+                    self.convolve_solution();
+                    let objective = self.compute_objective();
+                    if sample_objective {
+                        self.objective_history.push(objective);
+                    }
+                    let change = (self.prev_objective - objective).abs();
+                    if self.warmup_done() && change < self.tolerance * objective.abs().max(1e-20) {
+                        self.converged = true;
+                    }
+                    self.prev_objective = objective;
+                }
+            }
+
+            // Plain L1 phase converged: engage the amplitude floor and/or the gain
+            // estimate and keep going
+            let engage_floor = self.min_spike > 0.0 && !self.min_spike_engaged;
+            let engage_gain = self.estimate_gain && !self.gain_engaged;
+            if self.converged && (engage_floor || engage_gain) {
+                self.min_spike_engaged |= engage_floor;
+                self.gain_engaged |= engage_gain;
+                self.converged = false;
+                self.prev_objective = f64::INFINITY;
+                self.reset_momentum();
             }
 
             // Mark reconvolution as stale (it currently holds K*y_k, not K*x_{k+1})
             self.reconvolution_stale = true;
         }
 
+        self.update_status_tracking(self.iteration - start_iteration);
+        self.last_batch_micros = crate::timing::now_micros() - start_micros;
         self.converged
     }
-}
 
-#[cfg(test)]
-mod tests {
-    use crate::kernel::build_kernel;
-    use crate::Solver;
+    /// Classify the iterations run so far: converged, still progressing
+    /// (`MaxIterations` — the caller's budget ran out), stalled, or diverging.
+    /// Stall and divergence are judged per `step_batch` call, so use batches of
+    /// at least a few dozen iterations.
+    pub fn get_status(&self) -> ConvergenceStatus {
+        if self.active_len == 0 {
+            ConvergenceStatus::EmptyTrace
+        } else if self.converged {
+            ConvergenceStatus::Converged
+        } else if self.growth_batches >= DIVERGE_BATCHES {
+            ConvergenceStatus::Diverging
+        } else if self.stall_iters >= STALL_ITERS {
+            ConvergenceStatus::Stalled
+        } else {
+            ConvergenceStatus::MaxIterations
+        }
+    }
 
-    /// Helper: create a solver with given params and run to convergence
-    fn solve_to_convergence(
-        solver: &mut Solver,
-        trace: &[f32],
-        max_batches: u32,
-        batch_size: u32,
-    ) -> u32 {
-        solver.set_trace(trace);
-        let mut total_batches = 0;
+    /// Flag divergence within a batch: when the solution norm grows by more than
+    /// `factor` from one iteration to the next (or turns non-finite), `step_batch`
+    /// stops right away and `get_status` reports `Diverging`, instead of waiting
+    /// for `DIVERGE_BATCHES` runaway batches. Costs nothing extra per iteration.
+    /// Early iterates from a zero start legitimately grow fast, so keep the factor
+    /// generous (e.g. 10). Returns false for factors that are not finite and > 1;
+    /// 0 turns it off (the default).
+    pub fn set_divergence_factor(&mut self, factor: f64) -> bool {
+        if factor != 0.0 && !(factor.is_finite() && factor > 1.0) {
+            return false;
+        }
+        self.divergence_factor = factor;
+        true
+    }
+
+    /// Whether the last `step_batch` stopped early on a norm jump (see
+    /// `set_divergence_factor`).
+    pub fn was_diverged(&self) -> bool {
+        self.batch_diverged
+    }
+
+    /// Convergence progress in [0, 1] for a progress bar: how far `rel_change` has
+    /// come from its first measured value r0 toward the tolerance, on a log scale,
+    /// `clamp(ln(r0 / rel_change) / ln(r0 / tolerance), 0, 1)`. 1 once converged
+    /// (or for an empty trace), 0 before the first informative iteration. Only
+    /// roughly monotonic: rel_change fluctuates, especially around restarts. Under
+    /// `ObjectiveChange` the tolerance applies to the objective, so this is an
+    /// estimate. Restarts from 0 after `reset_solution`, `set_solution`, or
+    /// `load_state`.
+    pub fn get_progress(&self) -> f32 {
+        if self.converged || self.active_len == 0 {
+            return 1.0;
+        }
+        let r0 = self.progress_ref_rel_change;
+        if !r0.is_finite() {
+            return 0.0;
+        }
+        if r0 <= self.tolerance {
+            return if self.rel_change <= self.tolerance {
+                1.0
+            } else {
+                0.0
+            };
+        }
+        let progress = (r0 / self.rel_change).ln() / (r0 / self.tolerance).ln();
+        if progress.is_nan() {
+            0.0
+        } else {
+            progress.clamp(0.0, 1.0) as f32
+        }
+    }
+
+    /// One-shot solve: load `trace`, run up to `max_batches` batches of
+    /// `batch_size` iterations until convergence, and return the solution.
+    /// Uses the current params, mode, and constraint; no filtering or baseline
+    /// subtraction is applied.
+    pub fn solve(&mut self, trace: &[f32], max_batches: u32, batch_size: u32) -> Vec<f32> {
+        self.set_trace(trace);
         for _ in 0..max_batches {
-            total_batches += 1;
-            if solver.step_batch(batch_size) {
+            if self.step_batch(batch_size) {
                 break;
             }
         }
-        total_batches
+        self.get_solution()
+    }
+
+    /// First pass of a two-pass solve for a responsive UI: run up to
+    /// `max_batches` batches on the loaded trace until converged at `coarse_tol`,
+    /// and return that solution so it can be shown right away. Follow with
+    /// `refine`, which carries on from it with momentum intact, so the pair
+    /// gives exactly the result of a single solve at the fine tolerance. The
+    /// solver's own tolerance is restored afterwards.
+    pub fn solve_coarse(&mut self, coarse_tol: f64, batch_size: u32, max_batches: u32) -> Vec<f32> {
+        let saved_tolerance = self.tolerance;
+        self.tolerance = coarse_tol;
+        self.run_batches(batch_size, max_batches);
+        self.tolerance = saved_tolerance;
+        self.get_solution()
     }
 
+    /// Second pass after `solve_coarse`: continue the current solve, without
+    /// restarting, for up to `max_batches` more batches until converged at
+    /// `fine_tol`. The solver's own tolerance is restored afterwards. Returns
+    /// the refined solution.
+    pub fn refine(&mut self, fine_tol: f64, batch_size: u32, max_batches: u32) -> Vec<f32> {
+        let saved_tolerance = self.tolerance;
+        self.tolerance = fine_tol;
+        if self.active_len > 0 {
+            self.converged = false;
+        }
+        self.run_batches(batch_size, max_batches);
+        self.tolerance = saved_tolerance;
+        self.get_solution()
+    }
+
+    /// Run FISTA on the loaded trace until convergence or `max_total` iterations,
+    /// in one call (no per-batch boundary crossings for headless jobs). Runs in
+    /// internal batches, so stall/divergence tracking works as with `step_batch`,
+    /// and stops early on `request_cancel` or a `set_divergence_factor` norm jump. Returns true if converged.
+    pub fn step_until_converged(&mut self, max_total: u32) -> bool {
+        let mut remaining = max_total;
+        while remaining > 0 && !self.converged {
+            let chunk = remaining.min(UNTIL_CONVERGED_CHUNK);
+            let start = self.iteration;
+            self.step_batch(chunk);
+            if self.batch_cancelled || self.batch_diverged || self.iteration == start {
+                break;
+            }
+            remaining -= self.iteration - start;
+        }
+        self.converged
+    }
+
+    /// Iteratively reweighted L1 (adaptive lasso) to reduce the downward amplitude
+    /// bias of plain L1: run up to `max_batches` batches on the loaded trace, then
+    /// `n_passes` more times scale each sample's lambda-map entry by
+    /// `eps / (|s_i| + eps)` from the current solution and re-solve warm.
+    ///
+    /// `eps` is `REWEIGHT_EPS_FRAC` of the largest spike, so silent samples keep
+    /// their penalty and large spikes are barely penalized. Weights multiply the
+    /// map as it was when the call started; the reweighted map stays active until
+    /// the next `set_trace` or `set_lambda_map`.
+    pub fn refine_reweighted(&mut self, n_passes: u32, max_batches: u32, batch_size: u32) {
+        let n = self.active_len;
+        let base_map = self.lambda_map[..n].to_vec();
+        for pass in 0..=n_passes {
+            if pass > 0 {
+                let peak = self.solution[..n]
+                    .iter()
+                    .fold(0.0_f32, |m, &v| m.max(v.abs()));
+                if peak == 0.0 {
+                    break; // nothing to reweight
+                }
+                let eps = REWEIGHT_EPS_FRAC * peak;
+                for i in 0..n {
+                    self.lambda_map[i] = base_map[i] * eps / (self.solution[i].abs() + eps);
+                }
+                self.converged = false;
+                self.prev_objective = f64::INFINITY;
+                self.reset_momentum();
+                self.reset_status_tracking();
+            }
+            for _ in 0..max_batches {
+                if self.step_batch(batch_size) {
+                    break;
+                }
+            }
+        }
+    }
+
+    /// Run n_steps of FISTA iterations (same as `step_batch`) and return the
+    /// iteration count, relative change, restart flag, and baseline in one call.
+    pub fn step_batch_detailed(&mut self, n_steps: u32) -> StepResult {
+        let converged = self.step_batch(n_steps);
+        StepResult {
+            iteration: self.iteration,
+            rel_change: self.rel_change,
+            restarted: self.batch_restarted,
+            baseline: self.baseline,
+            converged,
+        }
+    }
+}
+
+impl Solver {
+    /// reconvolution = K * x over the active length, with the solver's engine.
+    pub(crate) fn convolve_solution(&mut self) {
+        let n = self.active_len;
+        match self.conv_mode {
+            ConvMode::Fft => {
+                self.fft
+                    .convolve_forward(&self.solution[..n], n, &mut self.reconvolution[..n])
+            }
+            ConvMode::BandedAR2 => self
+                .banded
+                .convolve_forward(&self.solution[..n], &mut self.reconvolution[..n]),
+        }
+        self.apply_gain_to_reconvolution();
+    }
+
+    /// Whether the convergence warm-up has passed: `convergence_warmup` iterations
+    /// since the start, or `warm_start_warmup` since a `load_state` warm start.
+    fn warmup_done(&self) -> bool {
+        match self.warm_start_iters {
+            Some(iters) => iters > self.warm_start_warmup,
+            None => self.iteration > self.convergence_warmup,
+        }
+    }
+
+    /// Up to `max_batches` `step_batch` calls, stopping at the one that converges.
+    fn run_batches(&mut self, batch_size: u32, max_batches: u32) -> bool {
+        for _ in 0..max_batches {
+            if self.step_batch(batch_size) {
+                return true;
+            }
+        }
+        false
+    }
+
+    /// Forget stall/divergence history (new trace or restored state).
+    pub(crate) fn reset_status_tracking(&mut self) {
+        self.stall_rel_change = f64::INFINITY;
+        self.stall_iters = 0;
+        self.growth_batches = 0;
+        self.prev_batch_norm_sq = 0.0;
+        self.prev_batch_rel_change = f64::INFINITY;
+        self.progress_ref_rel_change = f64::INFINITY;
+    }
+
+    /// End-of-batch bookkeeping for `get_status`: progress is a halving of
+    /// rel_change. Divergence is a batch that ends with rel_change > 1 (each step
+    /// moves the solution by more than its own size) or with the solution norm and
+    /// rel_change both growing, repeated `DIVERGE_BATCHES` times — or either
+    /// going non-finite, or a per-iteration norm jump (`set_divergence_factor`).
+    fn update_status_tracking(&mut self, iters_run: u32) {
+        if iters_run == 0 || self.converged {
+            return;
+        }
+        let n = self.active_len;
+        let norm_sq: f64 = self.solution[..n]
+            .iter()
+            .map(|&v| v as f64 * v as f64)
+            .sum();
+
+        if self.batch_diverged || !norm_sq.is_finite() || !self.rel_change.is_finite() {
+            self.growth_batches = DIVERGE_BATCHES;
+        } else if self.rel_change > 1.0
+            || (norm_sq > self.prev_batch_norm_sq && self.rel_change > self.prev_batch_rel_change)
+        {
+            self.growth_batches += 1;
+        } else {
+            self.growth_batches = 0;
+        }
+
+        if self.rel_change < 0.5 * self.stall_rel_change {
+            self.stall_rel_change = self.rel_change;
+            self.stall_iters = 0;
+        } else {
+            self.stall_iters = self.stall_iters.saturating_add(iters_run);
+        }
+
+        self.prev_batch_norm_sq = norm_sq;
+        self.prev_batch_rel_change = self.rel_change;
+    }
+}
+
+/// Float type the shared FISTA step kernels run in: f32 for `Solver`, f64 for
+/// `SolverF64`. Convergence and restart accumulators are always f64.
+pub(crate) trait StepFloat:
+    Copy + Add<Output = Self> + Sub<Output = Self> + Mul<Output = Self>
+{
+    fn to_f64(self) -> f64;
+}
+
+impl StepFloat for f32 {
+    #[inline]
+    fn to_f64(self) -> f64 {
+        self as f64
+    }
+}
+
+impl StepFloat for f64 {
+    #[inline]
+    fn to_f64(self) -> f64 {
+        self
+    }
+}
+
+/// FISTA momentum sequence: t_{k+1} = (1 + sqrt(1 + 4·t_k²)) / 2.
+#[inline]
+pub(crate) fn next_t(t: f64) -> f64 {
+    (1.0 + (1.0 + 4.0 * t * t).sqrt()) / 2.0
+}
+
+/// Proximal gradient step, saving x_k in `x_old`: x_{k+1} = prox(y_k - step·∇,
+/// thresh·map). `prox(z, t)` is the constraint's shrinkage; passing it as a
+/// closure keeps the loop monomorphized per constraint, so it still vectorizes.
+#[inline]
+pub(crate) fn prox_step<T: StepFloat>(
+    x: &mut [T],
+    x_old: &mut [T],
+    y: &[T],
+    grad: &[T],
+    map: &[T],
+    step: T,
+    thresh: T,
+    prox: impl Fn(T, T) -> T,
+) {
+    for i in 0..x.len() {
+        x_old[i] = x[i];
+        let z = y[i] - step * grad[i];
+        x[i] = prox(z, thresh * map[i]);
+    }
+}
+
+/// Momentum extrapolation y_{k+1} = project(x_{k+1} + m·(x_{k+1} - x_k)), fused
+/// with the convergence and restart accumulators. Returns (‖x_{k+1} - x_k‖²,
+/// ‖x_k‖², ⟨y_k - x_{k+1}, x_{k+1} - x_k⟩); a positive dot product means the
+/// momentum hurt progress (O'Donoghue & Candes restart).
+#[inline]
+pub(crate) fn extrapolate<T: StepFloat>(
+    x: &[T],
+    x_old: &[T],
+    y: &mut [T],
+    momentum: T,
+    project: impl Fn(T) -> T,
+) -> (f64, f64, f64) {
+    let (mut diff_sq, mut xk_sq, mut dot) = (0.0_f64, 0.0_f64, 0.0_f64);
+    for i in 0..x.len() {
+        let x_new = x[i];
+        let x_prev = x_old[i];
+        let x_new_f64 = x_new.to_f64();
+        let x_prev_f64 = x_prev.to_f64();
+        let d = x_new_f64 - x_prev_f64;
+        diff_sq += d * d;
+        xk_sq += x_prev_f64 * x_prev_f64;
+        dot += (y[i].to_f64() - x_new_f64) * d;
+        y[i] = project(x_new + momentum * (x_new - x_prev));
+    }
+    (diff_sq, xk_sq, dot)
+}
+
+/// Absolute fallback scale for the primal-residual test: the centered signal
+/// energy ||trace - mean(trace)||² in solution units (a lone spike s
+/// contributes ||h||²·s² to it), so a large raw-fluorescence offset cannot loosen it.
+pub(crate) fn signal_scale_sq<T: StepFloat>(trace: &[T], kernel: &[T]) -> f64 {
+    let n = trace.len().max(1) as f64;
+    let mean = trace.iter().map(|&y| y.to_f64()).sum::<f64>() / n;
+    let centered_sq: f64 = trace.iter().map(|&y| (y.to_f64() - mean).powi(2)).sum();
+    let kernel_sq: f64 = kernel.iter().map(|&k| k.to_f64() * k.to_f64()).sum();
+    centered_sq / kernel_sq.max(f64::MIN_POSITIVE)
+}
+
+/// Primal-residual convergence: the step is small relative to ||x_k||, or, for a
+/// near-zero solution (high lambda) where f32 jitter alone can keep the relative
+/// test failing, relative to the signal scale.
+#[inline]
+pub(crate) fn primal_converged(diff_sq: f64, xk_sq: f64, tol_sq: f64, signal_sq: f64) -> bool {
+    diff_sq < tol_sq * (xk_sq + 1e-20) || diff_sq < tol_sq * signal_sq
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::kernel::build_kernel;
+    use crate::Solver;
+
     /// Helper: build an f32 trace from kernel convolved with spikes
     fn build_trace(kernel: &[f32], n: usize, spikes: &[usize]) -> Vec<f32> {
         let mut trace = vec![0.0_f32; n];
@@ -219,7 +771,7 @@ mod tests {
         let trace = build_kernel(0.02, 0.4, 30.0);
         let n = trace.len();
 
-        solve_to_convergence(&mut solver, &trace, 200, 10);
+        solver.solve(&trace, 200, 10);
 
         let solution = solver.get_solution();
         assert_eq!(solution.len(), n);
@@ -269,7 +821,7 @@ mod tests {
         solver.set_params(0.02, 0.4, 0.01, 30.0);
 
         let trace = vec![0.0_f32; 100];
-        solve_to_convergence(&mut solver, &trace, 100, 10);
+        solver.solve(&trace, 100, 10);
 
         let solution = solver.get_solution();
         let max_val = solution.iter().cloned().fold(0.0_f32, f32::max);
@@ -289,7 +841,7 @@ mod tests {
         let kernel = build_kernel(0.02, 0.4, 30.0);
         let trace = build_trace(&kernel, 200, &[10, 50, 100, 150]);
 
-        solve_to_convergence(&mut solver, &trace, 100, 10);
+        solver.solve(&trace, 100, 10);
 
         assert!(
             solver.converged(),
@@ -321,7 +873,7 @@ mod tests {
             trace[i] += 0.01 * ((i as f32 * 0.7).sin());
         }
 
-        solve_to_convergence(&mut solver, &trace, 200, 10);
+        solver.solve(&trace, 200, 10);
 
         let solution = solver.get_solution();
         for (i, &v) in solution.iter().enumerate() {
@@ -338,13 +890,13 @@ mod tests {
         // Run 1
         let mut solver1 = Solver::new();
         solver1.set_params(0.02, 0.4, 0.01, 30.0);
-        solve_to_convergence(&mut solver1, &trace, 200, 10);
+        solver1.solve(&trace, 200, 10);
         let sol1 = solver1.get_solution();
 
         // Run 2
         let mut solver2 = Solver::new();
         solver2.set_params(0.02, 0.4, 0.01, 30.0);
-        solve_to_convergence(&mut solver2, &trace, 200, 10);
+        solver2.solve(&trace, 200, 10);
         let sol2 = solver2.get_solution();
 
         assert_eq!(sol1.len(), sol2.len());
@@ -369,7 +921,7 @@ mod tests {
         let n = 200;
         let trace = build_trace(&kernel, n, &[10, 50, 100, 150]);
 
-        solve_to_convergence(&mut solver, &trace, 200, 10);
+        solver.solve(&trace, 200, 10);
 
         let reconvolution = solver.get_reconvolution();
 
@@ -399,7 +951,7 @@ mod tests {
         // Cold start solve with original lambda
         let mut solver = Solver::new();
         solver.set_params(0.02, 0.4, 0.01, 30.0);
-        solve_to_convergence(&mut solver, &trace, 200, 10);
+        solver.solve(&trace, 200, 10);
 
         // Export state from converged solution
         let state = solver.export_state();
@@ -411,7 +963,7 @@ mod tests {
         warm_solver.load_state(&state);
         // load_state restores the solution; need to also copy into solution_prev
         // (the extrapolated point y_0 = x_0 for warm-start)
-        let active = warm_solver.active_len;
+        let active = warm_solver.active_length();
         warm_solver.solution_prev[..active].copy_from_slice(&warm_solver.solution[..active]);
         warm_solver.converged = false;
         warm_solver.prev_objective = f64::INFINITY;
@@ -428,7 +980,7 @@ mod tests {
         // Cold start with new lambda
         let mut cold_solver = Solver::new();
         cold_solver.set_params(0.02, 0.4, 0.012, 30.0);
-        solve_to_convergence(&mut cold_solver, &trace, 200, 10);
+        cold_solver.solve(&trace, 200, 10);
         let cold_iters = cold_solver.iteration_count();
 
         assert!(
@@ -496,7 +1048,7 @@ mod tests {
             trace[i] += dc_offset;
         }
 
-        solve_to_convergence(&mut solver, &trace, 200, 10);
+        solver.solve(&trace, 200, 10);
 
         // Baseline should be close to the DC offset
         let baseline = solver.get_baseline();
@@ -534,14 +1086,14 @@ mod tests {
         // Solve with low lambda
         let mut solver_low = Solver::new();
         solver_low.set_params(0.02, 0.4, 0.01, 30.0);
-        solve_to_convergence(&mut solver_low, &trace, 200, 10);
+        solver_low.solve(&trace, 200, 10);
         let sol_low = solver_low.get_solution();
         let nnz_low = sol_low.iter().filter(|&&v| v > 1e-6).count();
 
         // Solve with high lambda
         let mut solver_high = Solver::new();
         solver_high.set_params(0.02, 0.4, 1.0, 30.0);
-        solve_to_convergence(&mut solver_high, &trace, 200, 10);
+        solver_high.solve(&trace, 200, 10);
         let sol_high = solver_high.get_solution();
         let nnz_high = sol_high.iter().filter(|&&v| v > 1e-6).count();
 
@@ -565,9 +1117,7 @@ mod tests {
         solver.set_trace(&trace);
 
         // Set up a known signal in solution_prev
-        for i in 0..n {
-            solver.solution_prev[i] = trace[i];
-        }
+        solver.solution_prev[..n].copy_from_slice(&trace[..n]);
 
         // FFT-based forward convolution
         let mut fft_result = vec![0.0_f32; n];
@@ -615,7 +1165,7 @@ mod tests {
         let mut solver = Solver::new();
         solver.set_params(0.02, 0.4, 0.01, 30.0);
         solver.set_conv_mode(ConvMode::BandedAR2);
-        solve_to_convergence(&mut solver, &trace, 200, 10);
+        solver.solve(&trace, 200, 10);
 
         assert!(
             solver.converged(),
@@ -654,16 +1204,946 @@ mod tests {
         solver.set_params(0.02, 0.4, 0.001, 30.0); // low lambda for large values
         solver.set_conv_mode(ConvMode::BandedAR2);
         solver.set_constraint(Constraint::Box01);
-        solve_to_convergence(&mut solver, &scaled_trace, 200, 10);
+        solver.solve(&scaled_trace, 200, 10);
 
         let solution = solver.get_solution();
         for (i, &v) in solution.iter().enumerate() {
             assert!(
-                v >= 0.0 && v <= 1.0,
+                (0.0..=1.0).contains(&v),
                 "Box01 solution at index {} should be in [0,1], got {}",
                 i,
                 v
             );
         }
     }
+
+    // Test 14: Onset delay moves the recovered spike from the fluorescence onset
+    // back to the true spike time
+    #[test]
+    fn onset_delay_shifts_recovered_spike_earlier() {
+        use crate::kernel::apply_onset_delay;
+
+        let fs = 30.0;
+        let delay_s = 0.2; // 6 samples
+        let delay = 6;
+        let spike = 40;
+        let delayed_kernel = apply_onset_delay(build_kernel(0.02, 0.4, fs), delay_s, fs);
+        let trace = build_trace(&delayed_kernel, 200, &[spike]);
+
+        let argmax = |v: &[f32]| {
+            v.iter()
+                .enumerate()
+                .max_by(|a, b| a.1.partial_cmp(b.1).unwrap())
+                .unwrap()
+                .0
+        };
+
+        let mut plain = Solver::new();
+        plain.set_params(0.02, 0.4, 0.001, fs);
+        plain.solve(&trace, 200, 10);
+        let plain_idx = argmax(&plain.get_solution());
+
+        let mut delayed = Solver::new();
+        delayed.set_params(0.02, 0.4, 0.001, fs);
+        delayed.set_onset_delay(delay_s);
+        delayed.solve(&trace, 200, 10);
+        let delayed_idx = argmax(&delayed.get_solution());
+
+        assert_eq!(
+            plain_idx as i64 - delayed_idx as i64,
+            delay as i64,
+            "Delay-aware spike ({}) should be {} samples before the plain one ({})",
+            delayed_idx,
+            delay,
+            plain_idx
+        );
+        assert!(
+            delayed_idx.abs_diff(spike) <= 1,
+            "Delay-aware spike at {} should match the true spike at {}",
+            delayed_idx,
+            spike
+        );
+    }
+
+    // Test 15: step_batch_detailed reports the same state as the individual getters
+    #[test]
+    fn step_batch_detailed_matches_getters() {
+        let kernel = build_kernel(0.02, 0.4, 30.0);
+        let trace: Vec<f32> = build_trace(&kernel, 200, &[20, 90, 150])
+            .iter()
+            .map(|&v| v + 0.5)
+            .collect();
+
+        let mut solver = Solver::new();
+        solver.set_params(0.02, 0.4, 0.01, 30.0);
+        solver.set_trace(&trace);
+
+        let first = solver.step_batch_detailed(10);
+        assert_eq!(first.iteration(), 10);
+        assert_eq!(first.iteration(), solver.iteration_count());
+        assert!(first.rel_change().is_finite() && first.rel_change() >= 0.0);
+        assert_eq!(first.baseline(), solver.baseline);
+
+        let mut last = first;
+        for _ in 0..200 {
+            last = solver.step_batch_detailed(10);
+            if last.converged() {
+                break;
+            }
+        }
+        assert!(last.converged());
+        assert_eq!(last.converged(), solver.converged());
+        assert!(
+            last.rel_change() < solver.tolerance,
+            "Converged rel_change {} should be below tolerance",
+            last.rel_change()
+        );
+    }
+
+    // Test 16: restart_count accumulates across batches and clears on set_trace
+    #[test]
+    fn restart_count_tracks_restarts() {
+        let kernel = build_kernel(0.02, 0.4, 30.0);
+        let trace = build_trace(&kernel, 300, &[20, 25, 30, 120, 200, 205]);
+
+        let mut solver = Solver::new();
+        solver.set_params(0.02, 0.4, 0.001, 30.0);
+        solver.set_trace(&trace);
+        assert_eq!(solver.get_restart_count(), 0);
+
+        let mut batch_restarts = 0;
+        for _ in 0..100 {
+            let result = solver.step_batch_detailed(10);
+            if result.restarted() {
+                batch_restarts += 1;
+            }
+            if result.converged() {
+                break;
+            }
+        }
+        let count = solver.get_restart_count();
+        assert!(count > 0, "Overlapping transients should trigger restarts");
+        assert!(count >= batch_restarts, "{} < {}", count, batch_restarts);
+        assert!(count <= solver.iteration_count());
+
+        solver.set_trace(&trace);
+        assert_eq!(solver.get_restart_count(), 0);
+    }
+
+    // Test 17: baseline_nonneg clamps a negative baseline estimate at zero
+    #[test]
+    fn baseline_nonneg_clamps_spurious_dip() {
+        let kernel = build_kernel(0.02, 0.4, 30.0);
+        let mut trace = build_trace(&kernel, 300, &[30, 120, 220]);
+        // Spurious dip (e.g. motion artifact) pulls mean(trace - K*s) below zero
+        for v in &mut trace[150..210] {
+            *v -= 3.0;
+        }
+
+        let mut free = Solver::new();
+        free.set_params(0.02, 0.4, 0.01, 30.0);
+        free.solve(&trace, 200, 10);
+        assert!(free.baseline < 0.0, "Unclamped baseline {}", free.baseline);
+
+        let mut clamped = Solver::new();
+        clamped.set_params(0.02, 0.4, 0.01, 30.0);
+        clamped.set_baseline_nonneg(true);
+        clamped.solve(&trace, 200, 10);
+        assert_eq!(clamped.baseline, 0.0);
+        assert_eq!(clamped.get_baseline(), 0.0);
+
+        // A genuine positive offset is unaffected by the clamp
+        let offset: Vec<f32> = build_trace(&kernel, 300, &[30, 120, 220])
+            .iter()
+            .map(|&v| v + 0.8)
+            .collect();
+        clamped.set_trace(&offset);
+        for _ in 0..200 {
+            if clamped.step_batch(10) {
+                break;
+            }
+        }
+        assert!(
+            (clamped.baseline - 0.8).abs() < 0.1,
+            "Positive offset baseline {}",
+            clamped.baseline
+        );
+    }
+
+    // Test 18: ObjectiveChange criterion converges to the same solution
+    #[test]
+    fn objective_change_mode_converges() {
+        use crate::ConvergenceMode;
+
+        let kernel = build_kernel(0.02, 0.4, 30.0);
+        let trace = build_trace(&kernel, 300, &[20, 80, 160, 240]);
+
+        let mut primal = Solver::new();
+        primal.set_params(0.02, 0.4, 0.01, 30.0);
+        primal.solve(&trace, 500, 10);
+        assert!(primal.converged());
+
+        let mut objective = Solver::new();
+        objective.set_params(0.02, 0.4, 0.01, 30.0);
+        objective.set_convergence_mode(ConvergenceMode::ObjectiveChange);
+        objective.solve(&trace, 500, 10);
+        assert!(objective.converged());
+        assert!(objective.prev_objective.is_finite());
+
+        let a = primal.get_solution();
+        let b = objective.get_solution();
+        let peak = a.iter().cloned().fold(0.0_f32, f32::max);
+        for i in 0..a.len() {
+            assert!(
+                (a[i] - b[i]).abs() < 0.1 * peak,
+                "Solutions differ at {}: {} vs {}",
+                i,
+                a[i],
+                b[i]
+            );
+        }
+    }
+
+    // Test 19: step_batch records a non-negative wall-clock duration
+    #[test]
+    fn last_batch_micros_recorded() {
+        let kernel = build_kernel(0.02, 0.4, 30.0);
+        let trace = build_trace(&kernel, 2000, &[100, 700, 1500]);
+
+        let mut solver = Solver::new();
+        assert_eq!(solver.get_last_batch_micros(), 0.0);
+        solver.set_trace(&trace);
+        solver.step_batch(20);
+        let micros = solver.get_last_batch_micros();
+        assert!(micros > 0.0 && micros.is_finite(), "got {}", micros);
+    }
+
+    // Test 20: A lambda map suppresses spikes where it is large
+    #[test]
+    fn lambda_map_suppresses_spikes_in_window() {
+        let kernel = build_kernel(0.02, 0.4, 30.0);
+        let n = 300;
+        let trace = build_trace(&kernel, n, &[50, 200]);
+
+        let mut solver = Solver::new();
+        solver.set_params(0.02, 0.4, 0.01, 30.0);
+        solver.set_trace(&trace);
+        assert!(!solver.set_lambda_map(&vec![1.0; n - 1]), "length mismatch");
+        assert!(
+            !solver.set_lambda_map(&vec![-1.0; n]),
+            "negative multiplier"
+        );
+
+        let mut map = vec![1.0_f32; n];
+        map[150..].fill(1e4);
+        assert!(solver.set_lambda_map(&map));
+        for _ in 0..200 {
+            if solver.step_batch(10) {
+                break;
+            }
+        }
+        let solution = solver.get_solution();
+        let early: f32 = solution[..150].iter().sum();
+        let late: f32 = solution[150..].iter().sum();
+        assert!(
+            early > 0.5,
+            "Unpenalized spike should remain, got {}",
+            early
+        );
+        assert_eq!(late, 0.0, "Heavily penalized window should be empty");
+
+        // set_trace restores the all-ones map
+        solver.solve(&trace, 200, 10);
+        let late: f32 = solver.get_solution()[150..].iter().sum();
+        assert!(
+            late > 0.5,
+            "Spike should return with the default map, got {}",
+            late
+        );
+    }
+
+    // Test 21: Fixed spike support recovers amplitudes only at the given times
+    #[test]
+    fn spike_support_recovers_amplitudes() {
+        let kernel = build_kernel(0.02, 0.4, 30.0);
+        let n = 300;
+        let mut trace = vec![0.0_f32; n];
+        for (&t, &amp) in [40_usize, 130, 220].iter().zip(&[1.0_f32, 2.5, 0.7]) {
+            for (k, &kv) in kernel.iter().enumerate() {
+                if t + k < n {
+                    trace[t + k] += amp * kv;
+                }
+            }
+        }
+
+        let mut solver = Solver::new();
+        solver.set_params(0.02, 0.4, 0.05, 30.0);
+        solver.set_trace(&trace);
+        assert!(!solver.set_spike_support(&[40, n]), "out-of-range index");
+        assert!(solver.set_spike_support(&[40, 130, 220]));
+        for _ in 0..500 {
+            if solver.step_batch(10) {
+                break;
+            }
+        }
+
+        let solution = solver.get_solution();
+        for (i, &v) in solution.iter().enumerate() {
+            if ![40, 130, 220].contains(&i) {
+                assert_eq!(v, 0.0, "Nonzero off-support at {}", i);
+            }
+        }
+        // No L1 shrinkage: amplitudes match the ground truth
+        for (&t, &amp) in [40_usize, 130, 220].iter().zip(&[1.0_f32, 2.5, 0.7]) {
+            assert!(
+                (solution[t] - amp).abs() < 0.05 * amp,
+                "Amplitude at {}: {} vs {}",
+                t,
+                solution[t],
+                amp
+            );
+        }
+    }
+
+    // Test 22: solve() matches the manual set_trace + step_batch loop
+    #[test]
+    fn solve_matches_manual_loop() {
+        let kernel = build_kernel(0.02, 0.4, 30.0);
+        let trace = build_trace(&kernel, 200, &[30, 120]);
+
+        let mut manual = Solver::new();
+        manual.set_trace(&trace);
+        for _ in 0..200 {
+            if manual.step_batch(10) {
+                break;
+            }
+        }
+
+        let mut one_shot = Solver::new();
+        let solution = one_shot.solve(&trace, 200, 10);
+        assert_eq!(solution, manual.get_solution());
+        assert_eq!(one_shot.iteration_count(), manual.iteration_count());
+        assert!(one_shot.converged());
+    }
+
+    // Test 23: get_status distinguishes converged, budget-limited, stalled, diverging, empty
+    #[test]
+    fn convergence_status_reasons() {
+        use crate::ConvergenceStatus;
+
+        let kernel = build_kernel(0.02, 0.4, 30.0);
+        let trace = build_trace(&kernel, 300, &[20, 120, 220]);
+
+        let mut solver = Solver::new();
+        solver.set_params(0.02, 0.4, 0.01, 30.0);
+        assert_eq!(solver.get_status(), ConvergenceStatus::EmptyTrace);
+
+        solver.solve(&trace, 300, 20);
+        assert_eq!(solver.get_status(), ConvergenceStatus::Converged);
+
+        solver.set_trace(&trace);
+        solver.step_batch(3);
+        assert_eq!(solver.get_status(), ConvergenceStatus::MaxIterations);
+
+        // A tolerance below f32 roundoff can never be met on a noisy, barely
+        // regularized fit: rel_change plateaus around 1e-8
+        let mut noisy = trace.clone();
+        let mut state: u32 = 7;
+        for v in noisy.iter_mut() {
+            state = state.wrapping_mul(1_664_525).wrapping_add(1_013_904_223);
+            *v += 0.3 * ((state >> 8) as f32 / (1u32 << 24) as f32 - 0.5);
+        }
+        solver.set_params(0.02, 0.4, 1e-6, 30.0);
+        solver.set_trace(&noisy);
+        solver.tolerance = 1e-9;
+        for _ in 0..60 {
+            solver.step_batch(50);
+        }
+        assert_eq!(solver.get_status(), ConvergenceStatus::Stalled);
+        solver.tolerance = 1e-4;
+        solver.set_params(0.02, 0.4, 0.01, 30.0);
+
+        // A step size far beyond 1/L makes the iterates blow up
+        solver.set_trace(&trace);
+        solver.lipschitz_constant *= 0.01;
+        for _ in 0..10 {
+            solver.step_batch(20);
+        }
+        assert_eq!(solver.get_status(), ConvergenceStatus::Diverging);
+    }
+
+    // Test 24: A cancel request stops step_batch between iterations and is consumed
+    #[test]
+    fn cancel_stops_batch_between_iterations() {
+        let kernel = build_kernel(0.02, 0.4, 30.0);
+        let trace = build_trace(&kernel, 300, &[20, 120, 220]);
+        let mut solver = Solver::new();
+        solver.set_params(0.02, 0.4, 0.01, 30.0);
+        solver.set_trace(&trace);
+        solver.step_batch(5);
+        let solution = solver.get_solution();
+
+        solver.request_cancel();
+        assert!(!solver.step_batch(1000));
+        assert!(solver.was_cancelled());
+        assert_eq!(solver.iteration_count(), 5);
+        assert_eq!(solver.get_solution(), solution);
+
+        // From another thread through the shared handle
+        let handle = solver.cancel_handle();
+        std::thread::spawn(move || handle.store(true, std::sync::atomic::Ordering::Relaxed))
+            .join()
+            .unwrap();
+        solver.step_batch(1000);
+        assert!(solver.was_cancelled());
+        assert_eq!(solver.iteration_count(), 5);
+
+        // The request was consumed: the next batch resumes and converges
+        assert!(solver.step_batch(1000));
+        assert!(!solver.was_cancelled());
+        assert!(solver.iteration_count() > 5);
+    }
+
+    // Test 25: Reweighted L1 recovers amplitudes closer to ground truth
+    #[test]
+    fn refine_reweighted_reduces_amplitude_bias() {
+        let kernel = build_kernel(0.02, 0.4, 30.0);
+        let spikes = [40, 140, 240];
+        let trace = build_trace(&kernel, 360, &spikes);
+        // Ground truth amplitudes are 1.0; error in each spike's summed mass
+        let amplitude_error = |solution: &[f32]| -> f32 {
+            spikes
+                .iter()
+                .map(|&t| (solution[t - 2..t + 3].iter().sum::<f32>() - 1.0).abs())
+                .sum()
+        };
+
+        let mut solver = Solver::new();
+        solver.set_params(0.02, 0.4, 0.1, 30.0);
+        let plain = solver.solve(&trace, 500, 20);
+
+        solver.set_trace(&trace);
+        solver.refine_reweighted(3, 500, 20);
+        let refined = solver.get_solution();
+
+        let (plain_err, refined_err) = (amplitude_error(&plain), amplitude_error(&refined));
+        assert!(
+            refined_err < 0.5 * plain_err,
+            "reweighted error {} vs plain {}",
+            refined_err,
+            plain_err
+        );
+        // Silent samples keep the full penalty
+        assert_eq!(solver.lambda_map[100], 1.0);
+        assert!(solver.lambda_map[40] < 0.2);
+    }
+
+    // Test 26: The minimum spike amplitude floor removes small spurious values
+    #[test]
+    fn min_spike_amplitude_floors_solution() {
+        let kernel = build_kernel(0.02, 0.4, 30.0);
+        let mut trace = build_trace(&kernel, 300, &[50, 200]);
+        for (i, v) in trace.iter_mut().enumerate() {
+            *v += 0.02 * ((i * 7919 % 13) as f32 / 6.0 - 1.0);
+        }
+
+        let mut solver = Solver::new();
+        solver.set_params(0.02, 0.4, 0.001, 30.0);
+        let plain = solver.solve(&trace, 200, 10);
+        let small = |s: &[f32]| s.iter().filter(|&&v| v > 0.0 && v < 0.2).count();
+        assert!(small(&plain) > 0, "noise should leave small spikes");
+
+        solver.set_min_spike_amplitude(0.2);
+        let floored = solver.solve(&trace, 200, 10);
+        assert_eq!(small(&floored), 0);
+        let mass: f32 = floored.iter().sum();
+        assert!(mass > 1.5, "real spikes kept, mass {}", mass);
+
+        assert!(solver.converged(), "floored phase converges too");
+
+        solver.set_min_spike_amplitude(f32::NAN);
+        assert_eq!(solver.min_spike, 0.0);
+    }
+
+    // Test 27: step_until_converged matches a step_batch loop and honors cancel
+    #[test]
+    fn step_until_converged_matches_batches() {
+        let kernel = build_kernel(0.02, 0.4, 30.0);
+        let trace = build_trace(&kernel, 300, &[30, 150, 260]);
+
+        let mut looped = Solver::new();
+        looped.set_trace(&trace);
+        while !looped.step_batch(super::UNTIL_CONVERGED_CHUNK) {}
+
+        let mut single = Solver::new();
+        single.set_trace(&trace);
+        assert!(single.step_until_converged(100_000));
+        assert_eq!(single.iteration_count(), looped.iteration_count());
+        assert_eq!(single.get_solution(), looped.get_solution());
+
+        // Budget limit
+        single.set_trace(&trace);
+        assert!(!single.step_until_converged(7));
+        assert_eq!(single.iteration_count(), 7);
+
+        // Cancel
+        single.set_trace(&trace);
+        single.request_cancel();
+        assert!(!single.step_until_converged(1000));
+        assert_eq!(single.iteration_count(), 0);
+    }
+
+    // Test 28: An asymmetric data term discounts a downward artifact
+    #[test]
+    fn asymmetry_discounts_downward_artifact() {
+        let kernel = build_kernel(0.02, 0.4, 30.0);
+        let spikes = [60, 250, 420];
+        let mut trace = build_trace(&kernel, 600, &spikes);
+        for v in trace.iter_mut() {
+            *v += 2.0;
+        }
+        for v in &mut trace[320..350] {
+            *v -= 1.5; // motion dip
+        }
+
+        let fit = |asymmetry: f64| {
+            let mut solver = Solver::new();
+            solver.set_params(0.02, 0.4, 0.01, 30.0);
+            assert!(solver.set_asymmetry(asymmetry));
+            solver.solve(&trace, 1000, 20);
+            let solution = solver.get_solution();
+            let spurious: f32 = solution
+                .iter()
+                .enumerate()
+                .filter(|(i, _)| spikes.iter().all(|&s| i.abs_diff(s) > 3))
+                .map(|(_, v)| v)
+                .sum();
+            ((solver.get_baseline() - 2.0).abs(), spurious)
+        };
+        let (sym_baseline_err, sym_spurious) = fit(0.0);
+        let (asym_baseline_err, asym_spurious) = fit(0.8);
+        assert!(asym_baseline_err < 0.5 * sym_baseline_err);
+        assert!(asym_spurious < 0.5 * sym_spurious);
+
+        let mut solver = Solver::new();
+        assert!(!solver.set_asymmetry(1.0));
+        assert!(!solver.set_asymmetry(f64::NAN));
+        assert_eq!(solver.asymmetry, 0.0);
+    }
+
+    // Test 29: Objective sampling records a decreasing history without changing the iterates
+    #[test]
+    fn objective_sampling_records_history() {
+        use crate::ConvergenceMode;
+
+        let kernel = build_kernel(0.02, 0.4, 30.0);
+        let trace = build_trace(&kernel, 300, &[30, 150, 260]);
+
+        let mut plain = Solver::new();
+        plain.set_trace(&trace);
+        plain.step_batch(60);
+        assert!(plain.get_objective_history().is_empty());
+
+        let mut sampled = Solver::new();
+        sampled.set_objective_sampling(10);
+        sampled.set_trace(&trace);
+        sampled.step_batch(25);
+        sampled.step_batch(35);
+        assert_eq!(sampled.get_solution(), plain.get_solution());
+        let history = sampled.get_objective_history();
+        assert_eq!(history.len(), 6);
+        assert!(history.iter().all(|v| v.is_finite()));
+        assert!(history.last().unwrap() < history.first().unwrap());
+
+        // Matches what ObjectiveChange mode computes at the same iterations
+        let mut objective = Solver::new();
+        objective.set_convergence_mode(ConvergenceMode::ObjectiveChange);
+        objective.set_objective_sampling(10);
+        objective.set_trace(&trace);
+        objective.step_batch(60);
+        assert_eq!(objective.get_objective_history(), history);
+
+        // Cleared by a new trace
+        sampled.set_trace(&trace);
+        assert!(sampled.get_objective_history().is_empty());
+    }
+
+    // Test 30: With a zero warm-up, an already-converged warm start stops after one iteration
+    #[test]
+    fn zero_warmup_detects_converged_warm_start() {
+        let kernel = build_kernel(0.02, 0.4, 30.0);
+        let trace = build_trace(&kernel, 300, &[30, 150, 260]);
+
+        let mut cold = Solver::new();
+        cold.set_trace(&trace);
+        assert!(cold.step_until_converged(10_000));
+        let converged = cold.get_solution();
+        let state = cold.export_state();
+
+        // Default warm-up forces six iterations even from the converged solution
+        let mut seeded = Solver::new();
+        seeded.set_trace(&trace);
+        seeded.set_solution(&converged);
+        assert!(!seeded.step_batch(1));
+
+        let mut seeded = Solver::new();
+        seeded.set_convergence_warmup(0);
+        seeded.set_trace(&trace);
+        seeded.set_solution(&converged);
+        assert!(seeded.step_batch(1));
+        assert_eq!(seeded.iteration_count(), 1);
+
+        // Loaded state: converged on the first iteration after load
+        let mut loaded = Solver::new();
+        loaded.set_convergence_warmup(0);
+        loaded.set_trace(&trace);
+        assert!(loaded.load_state(&state));
+        let before = loaded.iteration_count();
+        assert!(loaded.step_batch(1));
+        assert_eq!(loaded.iteration_count(), before + 1);
+    }
+
+    // Test 31: Gain estimation recovers g for a scaled trace of unit spikes
+    #[test]
+    fn gain_estimation_recovers_scale() {
+        let kernel = build_kernel(0.02, 0.4, 30.0);
+        let spikes = [40, 140, 240];
+        let trace: Vec<f32> = build_trace(&kernel, 320, &spikes)
+            .iter()
+            .map(|&v| 2.5 * v)
+            .collect();
+
+        let mut solver = Solver::new();
+        solver.set_params(0.02, 0.4, 1e-4, 30.0);
+        solver.set_trace(&trace);
+        assert_eq!(solver.get_gain(), 1.0);
+        solver.set_gain_estimation(true);
+        solver.step_until_converged(20_000);
+
+        let g = solver.get_gain();
+        assert!((g - 2.5).abs() < 0.1, "gain {g}");
+        let solution = solver.get_solution();
+        for &i in &spikes {
+            assert!(
+                (solution[i] - 1.0).abs() < 0.05,
+                "spike {i}: {}",
+                solution[i]
+            );
+        }
+        // The displayed fit includes the gain
+        let fit = solver.get_reconvolution_with_baseline();
+        let err: f32 = fit
+            .iter()
+            .zip(&trace)
+            .map(|(a, b)| (a - b).abs())
+            .sum::<f32>()
+            / 320.0;
+        assert!(err < 0.02, "mean abs error {err}");
+
+        // The uncropped fit and the spike counts see the gain too
+        let full = solver.get_reconvolution_full();
+        let cropped = solver.get_reconvolution();
+        for i in 0..320 {
+            assert!((full[i] - cropped[i]).abs() < 1e-4, "full fit at {i}");
+        }
+        assert!(solver.set_single_spike_amplitude(2.5));
+        let counts = solver.get_solution_in_spikes();
+        for &i in &spikes {
+            assert!(
+                (counts[i] - 1.0).abs() < 0.05,
+                "count at {i}: {}",
+                counts[i]
+            );
+        }
+
+        // Default: gain fixed at 1, reset when disabled
+        solver.set_gain_estimation(false);
+        assert_eq!(solver.get_gain(), 1.0);
+    }
+
+    // Test 32: Progress rises from 0 to 1 over a solve
+    #[test]
+    fn progress_rises_to_one() {
+        let kernel = build_kernel(0.02, 0.4, 30.0);
+        let trace = build_trace(&kernel, 300, &[30, 150, 260]);
+
+        let mut solver = Solver::new();
+        solver.set_trace(&trace);
+        assert_eq!(solver.get_progress(), 0.0);
+        solver.step_batch(1);
+        assert_eq!(solver.get_progress(), 0.0); // x_0 = 0: no reference yet
+
+        let mut samples = Vec::new();
+        while !solver.step_batch(10) {
+            let p = solver.get_progress();
+            assert!((0.0..=1.0).contains(&p));
+            samples.push(p);
+        }
+        assert_eq!(solver.get_progress(), 1.0);
+        assert!(samples.len() > 2);
+        // Broadly increasing: the last quarter beats the first quarter
+        let q = samples.len().div_ceil(4);
+        let early = samples[..q].iter().sum::<f32>() / q as f32;
+        let late = samples[samples.len() - q..].iter().sum::<f32>() / q as f32;
+        assert!(late > early, "early {early}, late {late}");
+
+        solver.reset_solution();
+        assert_eq!(solver.get_progress(), 0.0);
+    }
+
+    // Test 33: A near-zero solution at very high lambda converges instead of
+    // running to the cap (f32 jitter on a large offset defeats the relative test)
+    #[test]
+    fn high_lambda_converges_on_absolute_change() {
+        let kernel = build_kernel(0.02, 0.4, 30.0);
+        let mut trace = build_trace(&kernel, 400, &[40, 150, 300]);
+        let mut state: u32 = 7;
+        for v in trace.iter_mut() {
+            state = state.wrapping_mul(1_664_525).wrapping_add(1_013_904_223);
+            *v += 1000.0 + 0.1 * ((state >> 8) as f32 / (1u32 << 24) as f32 - 0.5);
+        }
+
+        let mut solver = Solver::new();
+        solver.set_params(0.02, 0.4, 0.4615, 30.0);
+        solver.set_trace(&trace);
+        assert!(solver.step_until_converged(5_000));
+        assert!(
+            solver.iteration_count() < 100,
+            "{} iterations",
+            solver.iteration_count()
+        );
+        let peak = solver
+            .get_solution()
+            .iter()
+            .cloned()
+            .fold(0.0_f32, f32::max);
+        assert!(peak < 1e-3, "peak {peak}");
+    }
+
+    // Test 34: solve_coarse returns the coarse result before refine turns it
+    // into exactly what a single fine-tolerance solve gives
+    #[test]
+    fn coarse_then_refine_matches_fine_solve() {
+        let kernel = build_kernel(0.02, 0.4, 30.0);
+        let trace = build_trace(&kernel, 300, &[20, 120, 220]);
+
+        let mut direct = Solver::new();
+        direct.set_params(0.02, 0.4, 0.01, 30.0);
+        direct.tolerance = 1e-6;
+        let expected = direct.solve(&trace, 500, 10);
+
+        let mut coarse_only = Solver::new();
+        coarse_only.set_params(0.02, 0.4, 0.01, 30.0);
+        coarse_only.tolerance = 1e-2;
+        let coarse_expected = coarse_only.solve(&trace, 500, 10);
+
+        let mut solver = Solver::new();
+        solver.set_params(0.02, 0.4, 0.01, 30.0);
+        solver.set_trace(&trace);
+        let coarse = solver.solve_coarse(1e-2, 10, 500);
+        assert_eq!(coarse, coarse_expected);
+        assert_eq!(solver.iteration_count(), coarse_only.iteration_count());
+        assert_eq!(solver.tolerance, 1e-4);
+
+        let fine = solver.refine(1e-6, 10, 500);
+        assert!(solver.converged());
+        assert_eq!(fine, expected);
+        assert_ne!(coarse, fine);
+        assert_eq!(solver.iteration_count(), direct.iteration_count());
+        assert_eq!(solver.tolerance, 1e-4);
+
+        // The batch budget counts the batch that converges or runs out
+        solver.set_trace(&trace);
+        solver.solve_coarse(1e-2, 10, 1);
+        assert_eq!(solver.iteration_count(), 10);
+        solver.refine(1e-6, 10, 2);
+        assert_eq!(solver.iteration_count(), 30);
+    }
+
+    // Test 35: At low photon counts the Poisson data term recovers spike
+    // amplitudes better than least squares (fixed support, so no L1 shrinkage)
+    #[test]
+    fn poisson_data_term_improves_low_count_amplitudes() {
+        use crate::DataTerm;
+
+        let kernel = build_kernel(0.02, 0.4, 30.0);
+        let (n, b, a) = (900, 0.2_f32, 1.5_f32);
+        let spikes: Vec<usize> = (0..10).map(|i| 40 + i * 85).collect();
+        let mut rate = vec![b; n];
+        for &s in &spikes {
+            for (j, &k) in kernel.iter().enumerate().take(n - s) {
+                rate[s + j] += a * k;
+            }
+        }
+
+        let (mut ls_err, mut poisson_err) = (0.0_f32, 0.0_f32);
+        for seed in 1..11_u32 {
+            // Knuth's Poisson sampler on a fixed LCG
+            let mut state = seed.wrapping_mul(2_654_435_761);
+            let mut uniform = || {
+                state = state.wrapping_mul(1_664_525).wrapping_add(1_013_904_223);
+                (state >> 8) as f32 / (1u32 << 24) as f32
+            };
+            let counts: Vec<f32> = rate
+                .iter()
+                .map(|&mu| {
+                    let (limit, mut p, mut k) = ((-mu).exp(), 1.0_f32, 0);
+                    loop {
+                        p *= uniform();
+                        if p <= limit {
+                            break k as f32;
+                        }
+                        k += 1;
+                    }
+                })
+                .collect();
+
+            let rms_error = |term: DataTerm| {
+                let mut solver = Solver::new();
+                solver.set_params(0.02, 0.4, 0.1, 30.0);
+                solver.set_data_term(term);
+                solver.set_trace(&counts);
+                assert!(solver.set_spike_support(&spikes));
+                assert!(solver.step_until_converged(20_000));
+                let solution = solver.get_solution();
+                let sq: f32 = spikes.iter().map(|&s| (solution[s] - a).powi(2)).sum();
+                (sq / spikes.len() as f32).sqrt()
+            };
+            ls_err += rms_error(DataTerm::LeastSquares);
+            poisson_err += rms_error(DataTerm::Poisson);
+        }
+        assert!(
+            poisson_err < 0.95 * ls_err,
+            "poisson {poisson_err} vs ls {ls_err}"
+        );
+    }
+
+    // Test 36: The signed constraint recovers negative events that the
+    // non-negative prox clips to zero
+    #[test]
+    fn signed_constraint_recovers_bipolar_events() {
+        use crate::Constraint;
+
+        let kernel = build_kernel(0.02, 0.4, 30.0);
+        let n = 300;
+        let mut trace = vec![0.0_f32; n];
+        for &(s, a) in &[(40, 1.0_f32), (140, -0.8), (230, 0.6)] {
+            for (j, &k) in kernel.iter().enumerate().take(n - s) {
+                trace[s + j] += a * k;
+            }
+        }
+
+        let mut solver = Solver::new();
+        solver.set_params(0.02, 0.4, 0.001, 30.0);
+        solver.set_constraint(Constraint::Signed);
+        solver.set_trace(&trace);
+        assert!(solver.step_until_converged(5000));
+        let solution = solver.get_solution();
+        for &(s, a) in &[(40, 1.0_f32), (140, -0.8), (230, 0.6)] {
+            let amp: f32 = solution[s - 1..=s + 1].iter().sum();
+            assert!((amp - a).abs() < 0.1, "event at {s}: {amp} vs {a}");
+        }
+
+        // The default non-negative prox cannot represent the negative event
+        let mut nonneg = Solver::new();
+        nonneg.set_params(0.02, 0.4, 0.001, 30.0);
+        nonneg.set_trace(&trace);
+        nonneg.step_until_converged(5000);
+        assert!(nonneg.get_solution().iter().all(|&v| v >= 0.0));
+        assert!(solution.iter().any(|&v| v < -0.5));
+
+        // Warm starts keep the negative events under Signed and are projected
+        // onto the box under Box01
+        let mut warm = Solver::new();
+        warm.set_params(0.02, 0.4, 0.001, 30.0);
+        warm.set_constraint(Constraint::Signed);
+        warm.set_trace(&trace);
+        warm.warm_start_from(&solution);
+        assert_eq!(warm.get_solution(), solution);
+        warm.set_constraint(Constraint::Box01);
+        warm.set_solution(&[-0.5, 0.5, 2.0]);
+        assert_eq!(&warm.get_solution()[..4], &[0.0, 0.5, 1.0, 0.0]);
+    }
+
+    // Test 37: A divergence factor stops a blowing-up batch at once and reports it
+    #[test]
+    fn divergence_factor_flags_norm_jump() {
+        use crate::ConvergenceStatus;
+
+        let kernel = build_kernel(0.02, 0.4, 30.0);
+        let trace = build_trace(&kernel, 300, &[20, 120, 220]);
+
+        let mut solver = Solver::new();
+        solver.set_params(0.02, 0.4, 0.01, 30.0);
+        solver.set_trace(&trace);
+        solver.lipschitz_constant *= 0.01;
+        solver.step_batch(200);
+        assert_ne!(solver.get_status(), ConvergenceStatus::Diverging);
+        assert!(!solver.was_diverged());
+
+        assert!(!solver.set_divergence_factor(1.0));
+        assert!(!solver.set_divergence_factor(f64::NAN));
+        assert!(solver.set_divergence_factor(10.0));
+        solver.set_trace(&trace);
+        solver.lipschitz_constant *= 0.01;
+        solver.step_batch(200);
+        assert!(solver.was_diverged());
+        assert!(solver.iteration < 200, "stopped at {}", solver.iteration);
+        assert_eq!(solver.get_status(), ConvergenceStatus::Diverging);
+        assert!(solver.get_solution_norm() > 0.0);
+
+        // A well-conditioned solve never trips it
+        let mut solver = Solver::new();
+        solver.set_params(0.02, 0.4, 0.01, 30.0);
+        assert!(solver.set_divergence_factor(10.0));
+        solver.set_trace(&trace);
+        assert!(solver.step_until_converged(2000));
+        assert!(!solver.was_diverged());
+        let norm = solver
+            .get_solution()
+            .iter()
+            .map(|&v| v * v)
+            .sum::<f32>()
+            .sqrt();
+        assert!((solver.get_solution_norm() - norm as f64).abs() < 1e-5);
+    }
+
+    // Test 38: A loaded converged state re-converges on the first iteration by
+    // default, under either criterion; the warm-start warm-up is configurable
+    #[test]
+    fn loaded_state_reconverges_immediately() {
+        use crate::ConvergenceMode;
+
+        let kernel = build_kernel(0.02, 0.4, 30.0);
+        let trace = build_trace(&kernel, 300, &[30, 150, 260]);
+
+        for mode in [
+            ConvergenceMode::PrimalResidual,
+            ConvergenceMode::ObjectiveChange,
+        ] {
+            let mut cold = Solver::new();
+            cold.set_convergence_mode(mode);
+            cold.set_trace(&trace);
+            assert!(cold.step_until_converged(10_000));
+            let state = cold.export_state();
+
+            let mut loaded = Solver::new();
+            loaded.set_convergence_mode(mode);
+            loaded.set_trace(&trace);
+            assert!(loaded.load_state(&state));
+            assert!(loaded.prev_objective.is_finite());
+            let before = loaded.iteration_count();
+            assert!(loaded.step_batch(5), "{:?}", mode);
+            assert_eq!(loaded.iteration_count(), before + 1);
+
+            let mut delayed = Solver::new();
+            delayed.set_convergence_mode(mode);
+            delayed.set_warm_start_warmup(3);
+            delayed.set_trace(&trace);
+            assert!(delayed.load_state(&state));
+            let before = delayed.iteration_count();
+            assert!(!delayed.step_batch(3));
+            assert_eq!(delayed.iteration_count(), before + 3);
+        }
+    }
 }
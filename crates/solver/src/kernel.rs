@@ -1,3 +1,37 @@
+#[cfg(feature = "jsbindings")]
+use wasm_bindgen::prelude::*;
+
+/// Starting-point kinetics for common indicators: (name, tau_rise, tau_decay) in seconds.
+/// Values shared with `simulate::presets` are kept identical so simulated and
+/// deconvolved kernels agree out of the box.
+const INDICATOR_PRESETS: &[(&str, f64, f64)] = &[
+    ("GCaMP6f", 0.1, 0.6),
+    ("GCaMP6m", 0.15, 0.9),
+    ("GCaMP6s", 0.4, 1.8),
+    ("GCaMP7f", 0.08, 0.5),
+    ("jGCaMP8f", 0.05, 0.3),
+    ("OGB-1", 0.05, 1.5),
+];
+
+/// Look up `[tau_rise, tau_decay]` (seconds) for a named indicator.
+///
+/// Matching is case-insensitive. Returns `None` for unknown names.
+#[cfg_attr(feature = "jsbindings", wasm_bindgen)]
+pub fn indicator_preset(name: &str) -> Option<Vec<f64>> {
+    INDICATOR_PRESETS
+        .iter()
+        .find(|(preset, _, _)| preset.eq_ignore_ascii_case(name))
+        .map(|&(_, tau_rise, tau_decay)| vec![tau_rise, tau_decay])
+}
+
+/// Kernel shape for `(tau_rise, tau_decay, fs)` without touching any `Solver`,
+/// e.g. to preview a slider position before committing with `set_params`.
+/// Same as `build_kernel`.
+#[cfg_attr(feature = "jsbindings", wasm_bindgen)]
+pub fn preview_kernel(tau_rise: f64, tau_decay: f64, fs: f64) -> Vec<f32> {
+    build_kernel(tau_rise, tau_decay, fs)
+}
+
 /// Clamp tau_rise away from tau_decay to prevent degenerate zero kernels.
 /// When tau_rise ≈ tau_decay, the biexponential exp(-t/τ_d) - exp(-t/τ_r) collapses to zero.
 pub(crate) fn clamp_tau_rise(tau_rise: f64, tau_decay: f64) -> f64 {
@@ -14,6 +48,14 @@ pub(crate) fn clamp_tau_rise(tau_rise: f64, tau_decay: f64) -> f64 {
 /// Kernel length extends until the decay envelope drops below 1e-6 of peak.
 /// Computed in f64 for precision, returned as Vec<f32>.
 pub fn build_kernel(tau_rise: f64, tau_decay: f64, fs: f64) -> Vec<f32> {
+    build_kernel_f64(tau_rise, tau_decay, fs)
+        .iter()
+        .map(|&v| v as f32)
+        .collect()
+}
+
+/// `build_kernel` without the final cast to f32, for the f64 solver path.
+pub(crate) fn build_kernel_f64(tau_rise: f64, tau_decay: f64, fs: f64) -> Vec<f64> {
     let tau_rise = clamp_tau_rise(tau_rise, tau_decay);
 
     let dt = 1.0 / fs;
@@ -42,9 +84,138 @@ pub fn build_kernel(tau_rise: f64, tau_decay: f64, fs: f64) -> Vec<f32> {
         }
     }
 
+    kernel_f64
+}
+
+/// Build a kernel with two decay components, normalized to peak = 1.0.
+///
+/// h(t) = A*exp(-t/tau_fast) + (1-A)*exp(-t/tau_slow) - exp(-t/tau_rise), with
+/// A = `weight` clamped to [0, 1]. Length follows the slowest decay that carries
+/// weight, so `weight = 1` reproduces `build_kernel(tau_rise, tau_fast, fs)` exactly.
+pub fn build_biexp_kernel(
+    tau_rise: f64,
+    tau_fast: f64,
+    tau_slow: f64,
+    weight: f64,
+    fs: f64,
+) -> Vec<f32> {
+    let weight = weight.clamp(0.0, 1.0);
+    let tau_envelope = biexp_envelope_tau(tau_fast, tau_slow, weight);
+    let tau_rise = clamp_tau_rise(tau_rise, tau_envelope);
+
+    let dt = 1.0 / fs;
+    let kernel_len = ((-1e-6_f64.ln()) * tau_envelope / dt).ceil() as usize;
+    let kernel_len = kernel_len.max(2);
+
+    let mut kernel_f64 = Vec::with_capacity(kernel_len);
+    let mut peak = 0.0_f64;
+
+    for i in 0..kernel_len {
+        let t = (i as f64) * dt;
+        let val = weight * (-t / tau_fast).exp() + (1.0 - weight) * (-t / tau_slow).exp()
+            - (-t / tau_rise).exp();
+        kernel_f64.push(val);
+        if val > peak {
+            peak = val;
+        }
+    }
+
+    if peak > 0.0 {
+        for v in kernel_f64.iter_mut() {
+            *v /= peak;
+        }
+    }
+
     kernel_f64.iter().map(|&v| v as f32).collect()
 }
 
+/// Shift a kernel right by `round(onset_delay_s * fs)` samples of leading zeros,
+/// modeling the latency between a spike and the start of the fluorescence rise.
+/// Peak normalization is unaffected; non-positive delays return the kernel unchanged.
+pub fn apply_onset_delay(kernel: Vec<f32>, onset_delay_s: f64, fs: f64) -> Vec<f32> {
+    let delay = onset_delay_samples(onset_delay_s, fs);
+    if delay == 0 {
+        return kernel;
+    }
+    let mut delayed = vec![0.0_f32; delay + kernel.len()];
+    delayed[delay..].copy_from_slice(&kernel);
+    delayed
+}
+
+/// Multiply the last `round(fraction * len)` samples by a half-cosine falling
+/// from 1 toward 0, so the kernel ends smoothly instead of at the hard
+/// truncation point. Earlier samples (and so the peak) are unchanged;
+/// non-positive fractions return the kernel unchanged.
+pub fn apply_tail_taper(mut kernel: Vec<f32>, fraction: f64) -> Vec<f32> {
+    let len = kernel.len();
+    let m = if fraction > 0.0 {
+        ((fraction * len as f64).round() as usize).min(len)
+    } else {
+        0
+    };
+    for j in 0..m {
+        let x = std::f64::consts::PI * (j + 1) as f64 / (m + 1) as f64;
+        kernel[len - m + j] *= (0.5 * (1.0 + x.cos())) as f32;
+    }
+    kernel
+}
+
+/// Number of leading zero samples for an onset delay in seconds.
+pub(crate) fn onset_delay_samples(onset_delay_s: f64, fs: f64) -> usize {
+    if onset_delay_s > 0.0 && fs > 0.0 {
+        (onset_delay_s * fs).round() as usize
+    } else {
+        0
+    }
+}
+
+/// Convolve a kernel with a unit-area Gaussian of `sigma_samples`, truncated at
+/// ±4σ, and renormalize to peak = 1.0. Models a trace that was smoothed with a
+/// symmetric (zero-phase) filter upstream: the effective response then starts
+/// before the spike.
+///
+/// Returns the smoothed kernel and its offset — the number of leading samples
+/// that lie before t = 0. Non-positive sigmas return the kernel unchanged, offset 0.
+pub fn smooth_kernel_symmetric(kernel: &[f32], sigma_samples: f64) -> (Vec<f32>, usize) {
+    if sigma_samples <= 0.0 || !sigma_samples.is_finite() || kernel.is_empty() {
+        return (kernel.to_vec(), 0);
+    }
+    let half = (4.0 * sigma_samples).ceil() as usize;
+    let weights: Vec<f64> = (0..=2 * half)
+        .map(|j| {
+            let x = j as f64 - half as f64;
+            (-0.5 * x * x / (sigma_samples * sigma_samples)).exp()
+        })
+        .collect();
+    let area: f64 = weights.iter().sum();
+
+    let mut smoothed = vec![0.0_f64; kernel.len() + 2 * half];
+    for (k, &hk) in kernel.iter().enumerate() {
+        for (j, &w) in weights.iter().enumerate() {
+            smoothed[k + j] += hk as f64 * w / area;
+        }
+    }
+    let peak = smoothed.iter().cloned().fold(0.0_f64, f64::max);
+    if peak > 0.0 {
+        for v in smoothed.iter_mut() {
+            *v /= peak;
+        }
+    }
+    (smoothed.iter().map(|&v| v as f32).collect(), half)
+}
+
+/// Slowest decay constant that carries weight in a bi-exponential kernel.
+/// Sets the kernel length and stands in for tau_decay wherever a single decay is needed.
+pub(crate) fn biexp_envelope_tau(tau_fast: f64, tau_slow: f64, weight: f64) -> f64 {
+    if weight >= 1.0 {
+        tau_fast
+    } else if weight <= 0.0 {
+        tau_slow
+    } else {
+        tau_fast.max(tau_slow)
+    }
+}
+
 /// Derive AR(2) coefficients (g1, g2) from tau parameters.
 ///
 /// The AR(2) process c[t] = g1*c[t-1] + g2*c[t-2] + s[t] has characteristic
@@ -66,6 +237,127 @@ pub fn tau_to_ar2(tau_rise: f64, tau_decay: f64, fs: f64) -> (f64, f64) {
     (g1, g2)
 }
 
+/// Invert `tau_to_ar2`: recover (tau_rise, tau_decay) from AR(2) coefficients.
+///
+/// The roots of z^2 - g1*z - g2 are d and r (the larger is the decay root).
+/// Returns None when the roots are complex (g1^2 + 4*g2 < 0, an oscillatory
+/// model) or either root lies outside (0, 1), where no decaying kernel exists.
+pub fn ar2_to_tau(g1: f64, g2: f64, fs: f64) -> Option<(f64, f64)> {
+    let discriminant = g1 * g1 + 4.0 * g2;
+    if discriminant.is_nan() || discriminant < 0.0 || fs <= 0.0 {
+        return None;
+    }
+    let d = (g1 + discriminant.sqrt()) / 2.0;
+    let r = (g1 - discriminant.sqrt()) / 2.0;
+    if r <= 0.0 || d >= 1.0 {
+        return None;
+    }
+    let dt = 1.0 / fs;
+    Some((-dt / r.ln(), -dt / d.ln()))
+}
+
+/// Estimate (tau_rise, tau_decay) in seconds from kernel samples at `fs`.
+///
+/// Time is measured from the onset (the last zero sample before the response),
+/// so leading zeros from an onset delay are skipped. tau_decay is a log-linear
+/// least-squares fit to the tail after the peak, between 50% and 5% of peak,
+/// where the rise term has died out. tau_rise is a log-linear fit to the rise
+/// residual A·exp(-t/tau_decay) - h(t), using the tail fit's intercept A.
+/// Returns None when either phase has fewer than two usable samples or does
+/// not decay.
+pub fn fit_kernel_taus(kernel: &[f32], fs: f64) -> Option<(f64, f64)> {
+    if fs <= 0.0 {
+        return None;
+    }
+    let first = kernel.iter().position(|&v| v > 0.0)?;
+    let onset = first.saturating_sub(1);
+    let h: Vec<f64> = kernel[onset..].iter().map(|&v| v as f64).collect();
+    let dt = 1.0 / fs;
+    let (peak_idx, peak) =
+        h.iter()
+            .enumerate()
+            .fold((0, f64::NEG_INFINITY), |(bi, bv), (i, &v)| {
+                if v > bv {
+                    (i, v)
+                } else {
+                    (bi, bv)
+                }
+            });
+
+    let tail: Vec<(f64, f64)> = h
+        .iter()
+        .enumerate()
+        .skip(peak_idx + 1)
+        .take_while(|&(_, &v)| v >= 0.05 * peak)
+        .filter(|&(_, &v)| v <= 0.5 * peak)
+        .map(|(i, &v)| (i as f64 * dt, v.ln()))
+        .collect();
+    let (decay_slope, intercept) = fit_line(&tail)?;
+    if decay_slope >= 0.0 {
+        return None;
+    }
+    let tau_decay = -1.0 / decay_slope;
+    let amplitude = intercept.exp();
+
+    let rise: Vec<(f64, f64)> = h
+        .iter()
+        .enumerate()
+        .map(|(i, &v)| {
+            let t = i as f64 * dt;
+            (t, amplitude * (-t / tau_decay).exp() - v)
+        })
+        .take_while(|&(_, r)| r > 1e-3 * amplitude)
+        .map(|(t, r)| (t, r.ln()))
+        .collect();
+    let (rise_slope, _) = fit_line(&rise)?;
+    if rise_slope >= 0.0 {
+        return None;
+    }
+    Some((-1.0 / rise_slope, tau_decay))
+}
+
+/// Ordinary least-squares (slope, intercept) through `(x, y)` points.
+fn fit_line(points: &[(f64, f64)]) -> Option<(f64, f64)> {
+    if points.len() < 2 {
+        return None;
+    }
+    let n = points.len() as f64;
+    let mx = points.iter().map(|p| p.0).sum::<f64>() / n;
+    let my = points.iter().map(|p| p.1).sum::<f64>() / n;
+    let sxx: f64 = points.iter().map(|p| (p.0 - mx) * (p.0 - mx)).sum();
+    let sxy: f64 = points.iter().map(|p| (p.0 - mx) * (p.1 - my)).sum();
+    if sxx <= 0.0 {
+        return None;
+    }
+    let slope = sxy / sxx;
+    Some((slope, my - slope * mx))
+}
+
+/// Yule-Walker AR(2) estimate from the sample autocovariance at lags 0, 1, 2:
+///
+///   g1 = γ1(γ0 - γ2) / (γ0² - γ1²),  g2 = (γ0·γ2 - γ1²) / (γ0² - γ1²)
+///
+/// Returns None for traces too short or too flat to estimate.
+pub(crate) fn estimate_ar2_yule_walker(trace: &[f32]) -> Option<(f64, f64)> {
+    let n = trace.len();
+    if n < 3 {
+        return None;
+    }
+    let mean = trace.iter().map(|&v| v as f64).sum::<f64>() / n as f64;
+    let autocov = |lag: usize| -> f64 {
+        (lag..n)
+            .map(|t| (trace[t] as f64 - mean) * (trace[t - lag] as f64 - mean))
+            .sum::<f64>()
+            / n as f64
+    };
+    let (c0, c1, c2) = (autocov(0), autocov(1), autocov(2));
+    let det = c0 * c0 - c1 * c1;
+    if !det.is_finite() || det.abs() <= 1e-12 * c0 * c0 {
+        return None;
+    }
+    Some((c1 * (c0 - c2) / det, (c0 * c2 - c1 * c1) / det))
+}
+
 /// Compute the Lipschitz constant of the gradient of (1/2)||y - K*s||^2.
 ///
 /// L = max_w |H(w)|^2, where H(w) is the DFT of the kernel. This equals the
@@ -75,7 +367,14 @@ pub fn tau_to_ar2(tau_rise: f64, tau_decay: f64, fs: f64) -> (f64, f64) {
 /// Computed via direct DFT in f64 — O(n²) but zero-allocation and fast for
 /// typical kernel sizes (~200–600 samples). Only runs on parameter changes.
 pub fn compute_lipschitz(kernel: &[f32]) -> f64 {
-    let n = kernel.len();
+    // Pre-cast kernel to f64 once instead of per-frequency
+    let kernel_f64: Vec<f64> = kernel.iter().map(|&k| k as f64).collect();
+    compute_lipschitz_f64(&kernel_f64)
+}
+
+/// `compute_lipschitz` for an f64 kernel.
+pub(crate) fn compute_lipschitz_f64(kernel_f64: &[f64]) -> f64 {
+    let n = kernel_f64.len();
     if n == 0 {
         return 1e-10;
     }
@@ -84,9 +383,6 @@ pub fn compute_lipschitz(kernel: &[f32]) -> f64 {
     let inv = 2.0 * std::f64::consts::PI / (fft_len as f64);
     let mut max_power = 0.0_f64;
 
-    // Pre-cast kernel to f64 once instead of per-frequency
-    let kernel_f64: Vec<f64> = kernel.iter().map(|&k| k as f64).collect();
-
     // Real kernel has symmetric spectrum: only need 0..=fft_len/2
     for w in 0..=fft_len / 2 {
         let freq = inv * (w as f64);
@@ -254,4 +550,153 @@ mod tests {
             l1_norm * l1_norm
         );
     }
+
+    // Test 9: Indicator presets resolve case-insensitively; unknown names are None
+    #[test]
+    fn indicator_preset_lookup() {
+        let taus = indicator_preset("GCaMP6f").expect("GCaMP6f preset should exist");
+        assert_eq!(taus, vec![0.1, 0.6]);
+        assert_eq!(indicator_preset("jgcamp8f"), Some(vec![0.05, 0.3]));
+        for name in ["GCaMP6s", "GCaMP7f"] {
+            let taus = indicator_preset(name).unwrap();
+            assert!(
+                taus[0] > 0.0 && taus[0] < taus[1],
+                "{} should have 0 < tau_rise < tau_decay, got {:?}",
+                name,
+                taus
+            );
+        }
+        assert_eq!(indicator_preset("not-an-indicator"), None);
+    }
+
+    // Test 10: Bi-exponential kernel with weight=1 is the double-exponential kernel
+    #[test]
+    fn biexp_weight_one_matches_double_exponential() {
+        let single = build_kernel(0.02, 0.4, 30.0);
+        let biexp = build_biexp_kernel(0.02, 0.4, 2.0, 1.0, 30.0);
+        assert_eq!(single, biexp);
+    }
+
+    // Test 11: A slow component lengthens the tail while keeping peak = 1.0
+    #[test]
+    fn biexp_slow_component_extends_tail() {
+        let single = build_kernel(0.02, 0.4, 30.0);
+        let biexp = build_biexp_kernel(0.02, 0.4, 2.0, 0.7, 30.0);
+        let peak = biexp.iter().cloned().fold(f32::NEG_INFINITY, f32::max);
+        assert!(
+            (peak - 1.0).abs() < 1e-6,
+            "Peak should be 1.0, got {}",
+            peak
+        );
+        assert!(biexp[0].abs() < 1e-7, "First sample should be 0.0");
+        assert!(
+            biexp.len() > single.len(),
+            "Slow decay should lengthen the kernel: {} vs {}",
+            biexp.len(),
+            single.len()
+        );
+        // One second in, the slow component dominates
+        assert!(biexp[30] > single[30]);
+    }
+
+    // Test 12: Onset delay prepends round(delay * fs) zeros and keeps the peak at 1.0
+    #[test]
+    fn onset_delay_prepends_zeros() {
+        let kernel = build_kernel(0.02, 0.4, 30.0);
+        let delayed = apply_onset_delay(kernel.clone(), 0.1, 30.0);
+        assert_eq!(delayed.len(), kernel.len() + 3);
+        assert!(delayed[..3].iter().all(|&v| v == 0.0));
+        assert_eq!(&delayed[3..], &kernel[..]);
+        assert_eq!(apply_onset_delay(kernel.clone(), 0.0, 30.0), kernel);
+
+        // Lipschitz depends only on |H(w)|, which a pure delay leaves unchanged
+        let l0 = compute_lipschitz(&kernel);
+        let l1 = compute_lipschitz(&delayed);
+        assert!((l0 - l1).abs() / l0 < 1e-3, "{} vs {}", l0, l1);
+    }
+
+    // Test 13: ar2_to_tau inverts tau_to_ar2 and rejects complex roots
+    #[test]
+    fn ar2_to_tau_roundtrip() {
+        let (g1, g2) = tau_to_ar2(0.05, 0.8, 30.0);
+        let (tau_rise, tau_decay) = ar2_to_tau(g1, g2, 30.0).unwrap();
+        assert!((tau_rise - 0.05).abs() < 1e-9, "tau_rise {}", tau_rise);
+        assert!((tau_decay - 0.8).abs() < 1e-9, "tau_decay {}", tau_decay);
+
+        // g1^2 + 4*g2 < 0: damped oscillation, no real roots
+        assert!(ar2_to_tau(1.0, -0.5, 30.0).is_none());
+        // Root above 1: growing, not decaying
+        assert!(ar2_to_tau(2.1, -1.1, 30.0).is_none());
+    }
+
+    // Test 14: Symmetric smoothing keeps peak = 1.0 and places the t = 0 sample at the offset
+    #[test]
+    fn smooth_kernel_symmetric_offset() {
+        let kernel = build_kernel(0.02, 0.4, 30.0);
+        let (smoothed, offset) = smooth_kernel_symmetric(&kernel, 1.5);
+        assert_eq!(offset, 6);
+        assert_eq!(smoothed.len(), kernel.len() + 2 * offset);
+        let peak = smoothed.iter().cloned().fold(f32::NEG_INFINITY, f32::max);
+        assert!(
+            (peak - 1.0).abs() < 1e-6,
+            "Peak should be 1.0, got {}",
+            peak
+        );
+        // Smoothing leaks energy before t = 0
+        assert!(smoothed[offset - 1] > 0.0);
+
+        let (unchanged, offset) = smooth_kernel_symmetric(&kernel, 0.0);
+        assert_eq!((unchanged, offset), (kernel, 0));
+    }
+
+    // Test 15: preview_kernel matches build_kernel
+    #[test]
+    fn preview_kernel_matches_build_kernel() {
+        assert_eq!(preview_kernel(0.1, 0.6, 30.0), build_kernel(0.1, 0.6, 30.0));
+    }
+
+    // Test 16: Tail taper keeps the head, ramps the tail down, and tightens the bound
+    #[test]
+    fn tail_taper_smooths_truncation() {
+        // Short kernel truncated well above the 1e-6 floor
+        let kernel: Vec<f32> = build_kernel(0.02, 0.4, 30.0)[..40].to_vec();
+        assert_eq!(apply_tail_taper(kernel.clone(), 0.0), kernel);
+
+        let tapered = apply_tail_taper(kernel.clone(), 0.1);
+        assert_eq!(tapered.len(), kernel.len());
+        assert_eq!(tapered[..36], kernel[..36]);
+        for i in 36..40 {
+            assert!(tapered[i] < kernel[i]);
+        }
+        assert!(tapered[39] < 0.25 * kernel[39]);
+        assert!(compute_lipschitz(&tapered) < compute_lipschitz(&kernel));
+    }
+
+    // Test 17: fit_kernel_taus recovers the kinetics a kernel was built from
+    #[test]
+    fn fit_kernel_taus_recovers_build_params() {
+        for &(tau_r, tau_d, fs) in &[(0.05, 0.5, 100.0), (0.02, 0.4, 30.0), (0.1, 1.2, 20.0)] {
+            let kernel = build_kernel(tau_r, tau_d, fs);
+            let (fit_r, fit_d) = fit_kernel_taus(&kernel, fs).expect("fit");
+            assert!(
+                (fit_d - tau_d).abs() < 0.02 * tau_d,
+                "decay {} vs {}",
+                fit_d,
+                tau_d
+            );
+            assert!(
+                (fit_r - tau_r).abs() < 0.1 * tau_r,
+                "rise {} vs {}",
+                fit_r,
+                tau_r
+            );
+
+            // Leading zeros from an onset delay do not shift the estimate
+            let delayed = apply_onset_delay(kernel, 0.1, fs);
+            let (dr, dd) = fit_kernel_taus(&delayed, fs).expect("fit delayed");
+            assert!((dr - fit_r).abs() < 1e-9 && (dd - fit_d).abs() < 1e-9);
+        }
+        assert_eq!(fit_kernel_taus(&[0.0; 8], 30.0), None);
+        assert_eq!(fit_kernel_taus(&[0.0, 1.0], 30.0), None);
+    }
 }
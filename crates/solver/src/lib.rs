@@ -1,5 +1,6 @@
 mod banded;
 pub(crate) mod baseline;
+mod batch;
 #[allow(dead_code)]
 pub(crate) mod biexp_fit;
 mod fft;
@@ -10,10 +11,14 @@ pub(crate) mod indeca;
 mod kernel;
 #[allow(dead_code)]
 pub(crate) mod kernel_est;
+mod metrics;
 pub(crate) mod peak_seed;
+mod resample;
 pub(crate) mod simulate;
+mod solver_f64;
 #[allow(dead_code)]
 pub(crate) mod threshold;
+mod timing;
 #[allow(dead_code)]
 pub(crate) mod upsample;
 
@@ -26,15 +31,25 @@ mod js_indeca;
 mod js_simulate;
 
 use banded::BandedAR2;
+pub use batch::{solve_batch, BatchConfig, BatchResult};
 use filter::BandpassFilter;
-use kernel::{build_kernel, compute_lipschitz};
+pub use fista::StepResult;
+use kernel::{
+    apply_onset_delay, apply_tail_taper, ar2_to_tau, biexp_envelope_tau, build_biexp_kernel,
+    build_kernel, compute_lipschitz, estimate_ar2_yule_walker, fit_kernel_taus,
+    smooth_kernel_symmetric,
+};
+pub use kernel::{indicator_preset, preview_kernel};
+pub use solver_f64::SolverF64;
 use std::io::{Cursor, Read};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
 
 #[cfg(feature = "jsbindings")]
 use wasm_bindgen::prelude::*;
 
 /// Convolution mode for forward/adjoint operations in FISTA.
-#[derive(Clone, Copy, PartialEq, Eq)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
 #[cfg_attr(feature = "jsbindings", wasm_bindgen)]
 pub enum ConvMode {
     /// FFT-based O(T log T) per call — the original implementation.
@@ -43,14 +58,181 @@ pub enum ConvMode {
     BandedAR2 = 1,
 }
 
+/// How the FFT length is padded above `n + k_len - 1`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "jsbindings", wasm_bindgen)]
+pub enum FftPadding {
+    /// Next power of two — the original behavior. Nearly doubles the FFT for
+    /// lengths just past a power-of-two boundary.
+    PowerOfTwo = 0,
+    /// Next 5-smooth size (2^a·3^b·5^c), at most ~25% padding; rustfft's
+    /// mixed-radix paths keep these fast.
+    FastSize = 1,
+}
+
+/// Window applied to the trace before the FFT of the displayed power spectrum.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "jsbindings", wasm_bindgen)]
+pub enum SpectrumWindow {
+    /// No window (implicit boxcar) — the original behavior; most leakage.
+    Rectangular = 0,
+    /// Hann: good general-purpose leakage suppression.
+    Hann = 1,
+    /// Hamming: narrower main lobe than Hann, higher far sidelobes.
+    Hamming = 2,
+    /// Blackman: lowest sidelobes, widest main lobe.
+    Blackman = 3,
+}
+
 /// Constraint type for the proximal step.
-#[derive(Clone, Copy, PartialEq, Eq)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
 #[cfg_attr(feature = "jsbindings", wasm_bindgen)]
 pub enum Constraint {
     /// Current: max(0, z - threshold) — L1 + non-negativity.
     NonNegative = 0,
     /// InDeCa Eq. 3: clamp(z, 0, 1) — box constraint, no L1 penalty.
     Box01 = 1,
+    /// sign(z)·max(0, |z| - threshold) — plain L1 soft-threshold allowing
+    /// negative events, for signals that genuinely go both ways (voltage
+    /// indicators, differenced traces).
+    Signed = 2,
+}
+
+impl Constraint {
+    /// Project a value onto the feasible set: >= 0, [0, 1], or unchanged.
+    pub(crate) fn project(self, v: f32) -> f32 {
+        match self {
+            Constraint::NonNegative => v.max(0.0),
+            Constraint::Box01 => v.clamp(0.0, 1.0),
+            Constraint::Signed => v,
+        }
+    }
+}
+
+/// Kernel norm that lambda is multiplied by (`get_lambda_scale`).
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "jsbindings", wasm_bindgen)]
+pub enum LambdaScale {
+    /// G_dc = Σh, the kernel's integral — used for every kernel with a clearly
+    /// positive area, including all built-in kernels.
+    DcGain = 0,
+    /// Σ|h|, the fallback when G_dc is below `MIN_DC_GAIN_FRACTION` of it (e.g. a
+    /// custom kernel whose negative lobe cancels its area), so the L1 penalty
+    /// cannot silently vanish.
+    L1Norm = 1,
+}
+
+/// Magnitude below which a solution sample counts as zero for sparse exports.
+pub(crate) const SPIKE_EPS: f32 = 1e-6;
+
+/// Floor on the Poisson rate K*s + b, keeping y/μ and ln μ finite.
+pub(crate) const MIN_POISSON_RATE: f64 = 1e-3;
+
+/// Smallest G_dc / Σ|h| for which lambda is scaled by the DC gain.
+const MIN_DC_GAIN_FRACTION: f64 = 0.1;
+
+/// Convergence criterion checked after each FISTA iteration.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "jsbindings", wasm_bindgen)]
+pub enum ConvergenceMode {
+    /// ||x_{k+1} - x_k|| < tol * ||x_k|| — free, reuses the momentum loop's sums.
+    /// Also accepts ||x_{k+1} - x_k|| < tol * ||y - mean(y)|| / ||h||, so a
+    /// near-zero solution (very high lambda) cannot stall on f32 jitter.
+    PrimalResidual = 0,
+    /// |F_prev - F| / |F| < tol on the objective F(x_{k+1}). Costs one extra
+    /// forward convolution plus an O(n) reduction per iteration (~50% more work
+    /// per FISTA step in FFT mode).
+    ObjectiveChange = 1,
+}
+
+/// Data-fidelity term of the objective.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "jsbindings", wasm_bindgen)]
+pub enum DataTerm {
+    /// (1/2)||K*s + b - y||² — Gaussian noise, the default.
+    LeastSquares = 0,
+    /// c·Σ (μ_i - y_i·ln μ_i) with rate μ = K*s + b — Poisson noise, for raw photon
+    /// counts. Gradient c·K^T(1 - y/μ); μ is floored at `MIN_POISSON_RATE`. The
+    /// scale c = mean(y) keeps lambda comparable with least squares.
+    Poisson = 1,
+}
+
+/// Outcome of the iterations run so far, from `get_status`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "jsbindings", wasm_bindgen)]
+pub enum ConvergenceStatus {
+    /// The convergence criterion was met.
+    Converged = 0,
+    /// Not converged within the iterations run so far, with no stall or divergence
+    /// detected — more iterations should help.
+    MaxIterations = 1,
+    /// rel_change stopped shrinking while still above tolerance (e.g. lambda too
+    /// small for the noise level, or f32 roundoff below a very tight tolerance).
+    Stalled = 2,
+    /// The iterates run away: rel_change stays above 1 or grows with the solution
+    /// norm, or went non-finite (e.g. a kernel mismatch or a NaN in the trace).
+    Diverging = 3,
+    /// No trace is loaded (or it is empty).
+    EmptyTrace = 4,
+}
+
+/// How the scalar baseline b is estimated from the residual `trace - K*y_k`.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum BaselineMode {
+    /// Mean of the residual — the least-squares optimum, but pulled up by any
+    /// unmodeled positive excursions (inflating b and clipping small spikes).
+    Mean,
+    /// Low quantile of the residual, `q` in [0, 1] (e.g. 0.2). Robust to unmodeled
+    /// transients but slightly biased low, since noise puts roughly a fraction `q`
+    /// of baseline samples below the true level.
+    Percentile(f64),
+}
+
+/// Shape of the bandpass filter's transition bands.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum TaperShape {
+    /// Raised cosine spanning ±50% of each cutoff (the default; widths are set with
+    /// `set_filter_taper_fraction`).
+    Cosine,
+    /// Gaussian roll-off with standard deviation `sigma_hz`, placed so the gain is
+    /// 0.5 at each cutoff. Smoother stopband decay than the cosine's hard edges.
+    Gaussian { sigma_hz: f32 },
+}
+
+/// An in-place preprocessing step applied to the active trace, recorded so it can
+/// be listed (`preprocessing_log`) and replayed (`pipeline_impulse_response`).
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum PreprocessStep {
+    /// `apply_filter`, with the cutoffs of the enabled bands.
+    Filter {
+        hp: Option<f32>,
+        lp: Option<f32>,
+    },
+    SubtractBaseline {
+        window: usize,
+    },
+    MedianFilter {
+        window: usize,
+    },
+}
+
+impl PreprocessStep {
+    fn label(&self) -> String {
+        match *self {
+            PreprocessStep::Filter {
+                hp: Some(hp),
+                lp: Some(lp),
+            } => format!("bandpass(hp={hp:.4} Hz, lp={lp:.4} Hz)"),
+            PreprocessStep::Filter { hp: Some(hp), .. } => format!("highpass(hp={hp:.4} Hz)"),
+            PreprocessStep::Filter { lp, .. } => {
+                format!("lowpass(lp={:.4} Hz)", lp.unwrap_or(0.0))
+            }
+            PreprocessStep::SubtractBaseline { window } => {
+                format!("subtract_baseline(window={window}, quantile=0.2)")
+            }
+            PreprocessStep::MedianFilter { window } => format!("median_filter(window={window})"),
+        }
+    }
 }
 
 /// FISTA solver for calcium deconvolution.
@@ -68,14 +250,30 @@ pub struct Solver {
     tau_decay: f64,
     lambda: f64,
     fs: f64,
+    slow_decay: Option<(f64, f64)>, // (tau_slow, fast weight) when a bi-exponential kernel is active
+    custom_kernel: Option<Vec<f32>>, // user kernel from set_kernel, replacing the tau model
+    onset_delay_s: f64,             // spike-to-rise latency, prepended to the kernel as zeros
+    kernel_taper: f64,              // fraction of the kernel tail cosine-tapered, 0 = off
+    smoothing_sigma_s: f64,         // upstream zero-phase smoothing folded into the kernel
+    kernel_offset: usize,           // kernel samples before t = 0 (acausal kernels)
 
     // Pre-allocated working buffers (f32 to halve memory per worker)
-    pub(crate) trace: Vec<f32>,
+    pub(crate) trace: Vec<f32>, // working copy: filtered / baseline-subtracted in place
+    raw_trace: Vec<f32>,        // samples exactly as passed to set_trace (full length)
+    raw_len: usize,             // length of the last set_trace
+    window_start: usize,        // active region is raw_trace[window_start..][..active_len]
+    use_filtered_trace: bool,   // solve against `trace` (true) or `raw_trace`
     pub(crate) solution: Vec<f32>,
     pub(crate) solution_prev: Vec<f32>,
     pub(crate) gradient: Vec<f32>,
     pub(crate) reconvolution: Vec<f32>,
     pub(crate) residual_buf: Vec<f32>,
+    pub(crate) lambda_map: Vec<f32>, // per-sample lambda multiplier, all-ones by default
+    pub(crate) support_mask: Vec<f32>, // 1.0 at allowed spike times when support_active
+    pub(crate) support_active: bool,
+    pub(crate) min_spike: f32, // post-prox amplitude floor, 0 = off (heuristic)
+    single_spike_amplitude: f32, // calibrated peak ΔF/F of one action potential
+    pub(crate) min_spike_engaged: bool, // floor active: plain L1 phase has converged
     pub(crate) kernel: Vec<f32>,
 
     // FISTA state
@@ -87,13 +285,49 @@ pub struct Solver {
     // Convergence tracking
     pub(crate) prev_objective: f64,
     pub(crate) tolerance: f64,
+    pub(crate) convergence_warmup: u32, // iterations before convergence may be declared
+    pub(crate) warm_start_warmup: u32,  // the same, counted from a load_state warm start
+    pub(crate) warm_start_iters: Option<u32>, // iterations since a load_state warm start
+    pub(crate) convergence_mode: ConvergenceMode,
+    pub(crate) objective_sample_every: u32, // 0 = off; else record the objective every N iterations
+    pub(crate) objective_history: Vec<f64>,
+    pub(crate) rel_change: f64, // ||x_{k+1} - x_k|| / ||x_k|| from the last iteration
+    pub(crate) batch_restarted: bool, // an adaptive restart fired during the last step_batch
+    pub(crate) restart_count: u32, // adaptive restarts since set_trace
+    pub(crate) last_batch_micros: f64, // wall-clock time of the last step_batch
+    pub(crate) cancel_flag: Arc<AtomicBool>, // set by request_cancel, consumed by step_batch
+    pub(crate) batch_cancelled: bool, // the last step_batch stopped on a cancel request
+    pub(crate) divergence_factor: f64, // per-iteration norm growth that flags divergence, 0 = off
+    pub(crate) batch_diverged: bool, // the last step_batch stopped on a norm jump
+    pub(crate) stall_rel_change: f64, // rel_change at the last halving (stall detection)
+    pub(crate) stall_iters: u32, // iterations since rel_change last halved
+    pub(crate) growth_batches: u32, // consecutive batches with growing norm and rel_change
+    pub(crate) prev_batch_norm_sq: f64,
+    pub(crate) prev_batch_rel_change: f64,
+    pub(crate) progress_ref_rel_change: f64, // first rel_change with x_k != 0 (get_progress)
     pub(crate) lipschitz_constant: f64,
 
     // Baseline and kernel scaling
     pub(crate) baseline: f64,
+    pub(crate) baseline_nonneg: bool, // clamp the estimated baseline at 0 (ΔF/F traces)
+    pub(crate) baseline_mode: BaselineMode,
+    pub(crate) asymmetry: f64, // data-term tilt in (-1, 1), 0 = symmetric least squares
+    pub(crate) data_term: DataTerm,
+    baseline_scratch: Vec<f32>, // selection buffer for BaselineMode::Percentile / TV residual
+    baseline_nonfinite: u32,    // non-finite residual terms skipped by the last mean baseline
+    pub(crate) baseline_tv_lambda: f64, // > 0: per-sample TV-regularized baseline
+    pub(crate) baseline_series: Vec<f32>, // b[t] when the TV baseline is active
+    preprocessing: Vec<PreprocessStep>, // in-place trace steps applied since set_trace, in order
+    pub(crate) fixed_baseline: Option<f64>, // user-pinned b; skips estimation entirely
+    pub(crate) estimate_gain: bool, // fit y ≈ g*(K*s) + b with a jointly estimated g
+    pub(crate) gain: f64,       // multiplicative gain g (1.0 unless estimated)
+    pub(crate) gain_engaged: bool, // gain updates active: the g = 1 phase has converged
     baseline_ema: f64,
     baseline_ema_init: bool,
     kernel_dc_gain: f64,
+    rescale_on_kernel_change: bool, // keep s·G_dc fixed when the kernel is rebuilt
+    lambda_scale: LambdaScale,      // which kernel norm multiplies lambda
+    lambda_norm: f64,               // value of that norm for the current kernel
 
     // Convolution engines
     pub(crate) fft: fft::FftConvolver,
@@ -104,6 +338,7 @@ pub struct Solver {
 
     // Bandpass filter
     bandpass: BandpassFilter,
+    eager_spectrum: bool, // compute the raw-trace power spectrum in set_trace
     pub(crate) filtered: bool, // true after apply_filter() succeeded on current trace
 }
 
@@ -121,12 +356,28 @@ impl Solver {
             tau_decay: 0.4,
             lambda: 0.01,
             fs: 30.0,
+            slow_decay: None,
+            custom_kernel: None,
+            onset_delay_s: 0.0,
+            kernel_taper: 0.0,
+            smoothing_sigma_s: 0.0,
+            kernel_offset: 0,
             trace: Vec::new(),
+            raw_trace: Vec::new(),
+            raw_len: 0,
+            window_start: 0,
+            use_filtered_trace: true,
             solution: Vec::new(),
             solution_prev: Vec::new(),
             gradient: Vec::new(),
             reconvolution: Vec::new(),
             residual_buf: Vec::new(),
+            lambda_map: Vec::new(),
+            support_mask: Vec::new(),
+            support_active: false,
+            min_spike: 0.0,
+            single_spike_amplitude: 1.0,
+            min_spike_engaged: false,
             kernel: Vec::new(),
             iteration: 0,
             t_fista: 1.0,
@@ -134,97 +385,317 @@ impl Solver {
             active_len: 0,
             prev_objective: f64::INFINITY,
             tolerance: 1e-4,
+            convergence_warmup: 5,
+            warm_start_warmup: 0,
+            warm_start_iters: None,
+            convergence_mode: ConvergenceMode::PrimalResidual,
+            objective_sample_every: 0,
+            objective_history: Vec::new(),
+            rel_change: f64::INFINITY,
+            batch_restarted: false,
+            restart_count: 0,
+            last_batch_micros: 0.0,
+            cancel_flag: Arc::new(AtomicBool::new(false)),
+            batch_cancelled: false,
+            divergence_factor: 0.0,
+            batch_diverged: false,
+            stall_rel_change: f64::INFINITY,
+            progress_ref_rel_change: f64::INFINITY,
+            stall_iters: 0,
+            growth_batches: 0,
+            prev_batch_norm_sq: 0.0,
+            prev_batch_rel_change: f64::INFINITY,
             lipschitz_constant: 1.0,
             baseline: 0.0,
+            baseline_nonneg: false,
+            fixed_baseline: None,
+            estimate_gain: false,
+            gain: 1.0,
+            gain_engaged: false,
+            preprocessing: Vec::new(),
+            baseline_mode: BaselineMode::Mean,
+            asymmetry: 0.0,
+            data_term: DataTerm::LeastSquares,
+            baseline_scratch: Vec::new(),
+            baseline_nonfinite: 0,
+            baseline_tv_lambda: 0.0,
+            baseline_series: Vec::new(),
             baseline_ema: 0.0,
             baseline_ema_init: false,
             kernel_dc_gain: 1.0,
+            rescale_on_kernel_change: false,
+            lambda_scale: LambdaScale::DcGain,
+            lambda_norm: 1.0,
             fft: fft::FftConvolver::new(),
             banded: BandedAR2::new(0.02, 0.4, 30.0),
             conv_mode: ConvMode::Fft,
             constraint: Constraint::NonNegative,
             reconvolution_stale: true,
             bandpass: BandpassFilter::new(),
+            eager_spectrum: false,
             filtered: false,
         };
 
-        // Build kernel with default params
-        solver.kernel = build_kernel(solver.tau_rise, solver.tau_decay, solver.fs);
-        solver.lipschitz_constant = compute_lipschitz(&solver.kernel);
-        solver.kernel_dc_gain = solver.kernel.iter().map(|&k| k as f64).sum();
+        // Build kernel and everything derived from it (including filter cutoffs)
+        // with default params, so the set_params lambda-only fast path is valid
+        // from the start.
+        solver.rebuild_kernel();
 
         solver
     }
 
     /// Update solver parameters and rebuild kernel.
+    ///
+    /// When only lambda changes (same taus and fs, double-exponential kernel), the
+    /// kernel, DC gain, Lipschitz constant, and kernel FFT are all reused — lambda
+    /// enters only through the soft threshold. Keeps slider drags cheap.
     pub fn set_params(&mut self, tau_rise: f64, tau_decay: f64, lambda: f64, fs: f64) {
+        if self.slow_decay.is_none()
+            && self.custom_kernel.is_none()
+            && tau_rise == self.tau_rise
+            && tau_decay == self.tau_decay
+            && fs == self.fs
+        {
+            self.lambda = lambda;
+            return;
+        }
         self.tau_rise = tau_rise;
         self.tau_decay = tau_decay;
         self.lambda = lambda;
         self.fs = fs;
-        self.kernel = build_kernel(tau_rise, tau_decay, fs);
-        self.kernel_dc_gain = self.kernel.iter().map(|&k| k as f64).sum();
-        self.bandpass.update_cutoffs(tau_rise, tau_decay, fs);
+        self.slow_decay = None;
+        self.custom_kernel = None;
+        self.rebuild_kernel();
+    }
 
-        // Update convolution engines (only the active one + compute Lipschitz)
-        match self.conv_mode {
-            ConvMode::BandedAR2 => {
-                self.banded.update(tau_rise, tau_decay, fs);
-            }
-            ConvMode::Fft => {
-                // banded will be updated lazily if conv_mode switches
+    /// Update parameters with a bi-exponential kernel:
+    /// h(t) = w*exp(-t/tau_fast) + (1-w)*exp(-t/tau_slow) - exp(-t/tau_rise), peak = 1.0.
+    ///
+    /// Stays active until the next `set_params`. BandedAR2 mode can only model a single
+    /// decay and uses (tau_rise, tau_fast); use FFT mode for the exact kernel.
+    pub fn set_params_biexp(
+        &mut self,
+        tau_rise: f64,
+        tau_fast: f64,
+        tau_slow: f64,
+        weight: f64,
+        lambda: f64,
+        fs: f64,
+    ) {
+        self.tau_rise = tau_rise;
+        self.tau_decay = tau_fast;
+        self.lambda = lambda;
+        self.fs = fs;
+        self.slow_decay = Some((tau_slow, weight));
+        self.custom_kernel = None;
+        self.rebuild_kernel();
+    }
+
+    /// Replace the tau-model kernel with `kernel`, sampled at the current `fs`
+    /// (e.g. an empirically measured spike response). Onset delay, tail taper and
+    /// smoothing still apply on top. Stays active until the next `set_params` or
+    /// `set_params_biexp`; the taus keep driving the filter cutoffs and, since
+    /// BandedAR2 can only model the tau kernel, use FFT mode. Lambda is scaled
+    /// by Σ|h| instead of the DC gain when the kernel's area nearly cancels (see
+    /// `get_lambda_scale`). Returns false and keeps the current kernel if
+    /// `kernel` is empty, all zero, or has a non-finite sample.
+    pub fn set_kernel(&mut self, kernel: &[f32]) -> bool {
+        if kernel.iter().any(|k| !k.is_finite()) || kernel.iter().all(|&k| k == 0.0) {
+            return false;
+        }
+        self.custom_kernel = Some(kernel.to_vec());
+        self.rebuild_kernel();
+        true
+    }
+
+    /// Set the onset delay (seconds) between a spike and the start of the fluorescence
+    /// rise. The kernel is shifted right by `round(delay * fs)` zero samples, so recovered
+    /// spikes land at the spike time rather than the fluorescence onset. Persists across
+    /// `set_params`. BandedAR2 mode has no delay term and ignores it.
+    pub fn set_onset_delay(&mut self, onset_delay_s: f64) {
+        self.onset_delay_s = onset_delay_s.max(0.0);
+        self.rebuild_kernel();
+    }
+
+    /// Cosine-taper the last `fraction` of the kernel (e.g. 0.05) so it ends
+    /// smoothly instead of at the hard truncation point. This trades a tiny change
+    /// in the model's tail for less truncation ripple in the kernel spectrum and a
+    /// slightly tighter Lipschitz bound (a larger step, potentially faster
+    /// convergence). 0 (default) keeps the truncated kernel, so existing results
+    /// are unchanged. Persists across `set_params`; BandedAR2 mode ignores it.
+    /// Returns false and changes nothing unless `fraction` is in [0, 0.5].
+    pub fn set_kernel_taper(&mut self, fraction: f64) -> bool {
+        if !(0.0..=0.5).contains(&fraction) {
+            return false;
+        }
+        self.kernel_taper = fraction;
+        self.rebuild_kernel();
+        true
+    }
+
+    /// Account for symmetric (zero-phase) Gaussian smoothing of `sigma_s` seconds
+    /// applied to the trace upstream. The kernel is convolved with the same Gaussian,
+    /// which makes it two-sided: `get_kernel_offset` samples lie before t = 0, and the
+    /// convolution output is re-sliced so recovered spikes land at the true onset
+    /// rather than where the smoothed rise starts. 0 (default) keeps the causal kernel.
+    /// Persists across `set_params`. BandedAR2 mode is strictly causal and ignores it.
+    pub fn set_acausal_smoothing(&mut self, sigma_s: f64) {
+        self.smoothing_sigma_s = if sigma_s.is_finite() {
+            sigma_s.max(0.0)
+        } else {
+            0.0
+        };
+        self.rebuild_kernel();
+    }
+
+    /// Number of leading `get_kernel` samples that lie before t = 0 (0 for a causal kernel).
+    pub fn get_kernel_offset(&self) -> usize {
+        self.kernel_offset
+    }
+
+    /// Apply a named indicator's kinetics (see `indicator_preset`), keeping the
+    /// current lambda. Returns false and leaves parameters untouched for unknown names.
+    pub fn apply_indicator_preset(&mut self, name: &str, fs: f64) -> bool {
+        match indicator_preset(name) {
+            Some(taus) => {
+                self.set_params(taus[0], taus[1], self.lambda, fs);
+                true
             }
+            None => false,
         }
-        self.lipschitz_constant = self.current_lipschitz();
+    }
 
-        // Update kernel FFT if buffers are already set up and large enough.
-        // On re-enqueue quanta with unchanged trace length, this avoids a full
-        // FFT plan + buffer rebuild in ensure_buffers.
-        if self.conv_mode == ConvMode::Fft && self.fft.fft_len() > 0 && self.active_len > 0 {
-            let min_len = self.active_len + self.kernel.len() - 1;
-            if min_len <= self.fft.fft_len() {
-                self.fft.prepare_kernel(&self.kernel);
-            } else {
-                self.fft.invalidate();
+    /// Estimate (tau_rise, tau_decay) from the raw trace: Yule-Walker AR(2)
+    /// coefficients from the autocovariance at lags 0-2, converted to taus via the
+    /// roots of `tau_to_ar2`. On success applies them with `set_params` (keeping
+    /// lambda and fs) and returns `[tau_rise, tau_decay]`.
+    ///
+    /// Returns an empty vector and leaves parameters untouched when the fit has
+    /// complex or non-decaying roots, or the trace is too short or flat.
+    pub fn fit_kernel_from_autocorrelation(&mut self) -> Vec<f64> {
+        let n = self.active_len;
+        let fitted =
+            estimate_ar2_yule_walker(&self.raw_trace[self.window_start..self.window_start + n])
+                .and_then(|(g1, g2)| ar2_to_tau(g1, g2, self.fs));
+        match fitted {
+            Some((tau_rise, tau_decay)) => {
+                self.set_params(tau_rise, tau_decay, self.lambda, self.fs);
+                vec![tau_rise, tau_decay]
             }
+            None => Vec::new(),
+        }
+    }
+
+    /// Resample the loaded (raw) trace from the current `fs` to `target_fs`, update
+    /// `fs`, rebuild the kernel, and reload the result with `set_trace` (resetting
+    /// iteration state; re-apply the filter if it was in use).
+    ///
+    /// Band-limited FFT resampling: downsampling truncates the spectrum, which is
+    /// the anti-aliasing step. The output has `round(n * target_fs / fs)` samples
+    /// (at least 1) covering the same duration, with output sample `j` at time
+    /// `j / target_fs`. A linear trend between the first and last samples is removed
+    /// beforehand and restored after, so the edges do not ring. Non-positive or
+    /// non-finite rates leave everything untouched.
+    pub fn resample(&mut self, target_fs: f64) {
+        if !(target_fs.is_finite() && target_fs > 0.0 && self.fs > 0.0) {
+            return;
         }
+        let n = self.active_len;
+        let out_len = if n == 0 {
+            0
+        } else {
+            ((n as f64 * target_fs / self.fs).round() as usize).max(1)
+        };
+        let resampled = resample::resample_fft(
+            &self.raw_trace[self.window_start..self.window_start + n],
+            out_len,
+        );
+        self.fs = target_fs;
+        self.rebuild_kernel();
+        self.set_trace(&resampled);
     }
 
     /// Load a trace for deconvolution. Grows buffers if needed (never shrinks).
     /// Resets iteration state for a fresh solve.
+    ///
+    /// Traces shorter than the kernel are valid: K is the n×n causal Toeplitz
+    /// matrix of the first n kernel taps (FFT padding covers n + k_len - 1), so
+    /// each spike's response is simply cropped at the window edge, the baseline is
+    /// the mean residual over the n samples, and the Lipschitz bound of the full
+    /// kernel still holds.
     pub fn set_trace(&mut self, trace: &[f32]) {
-        self.active_len = trace.len();
-
         // Grow buffers if needed (never shrink to prevent WASM memory fragmentation)
         if self.trace.len() < trace.len() {
             let n = trace.len();
             self.trace.resize(n, 0.0);
+            self.raw_trace.resize(n, 0.0);
             self.solution.resize(n, 0.0);
             self.solution_prev.resize(n, 0.0);
             self.gradient.resize(n, 0.0);
             self.reconvolution.resize(n, 0.0);
             self.residual_buf.resize(n, 0.0);
+            self.lambda_map.resize(n, 1.0);
+            self.support_mask.resize(n, 0.0);
+            self.baseline_series.resize(n, 0.0);
         }
 
-        // Copy trace data and zero out solution buffers for active region
         let n = trace.len();
-        self.trace[..n].copy_from_slice(trace);
-        self.solution[..n].fill(0.0);
-        self.solution_prev[..n].fill(0.0);
-        self.gradient[..n].fill(0.0);
-        self.reconvolution[..n].fill(0.0);
-        self.residual_buf[..n].fill(0.0);
+        self.raw_trace[..n].copy_from_slice(trace);
+        self.raw_len = n;
+        self.activate_window(0, n);
+    }
 
-        // Reset iteration state
-        self.iteration = 0;
-        self.t_fista = 1.0;
-        self.converged = false;
-        self.prev_objective = f64::INFINITY;
-        self.baseline = 0.0;
-        self.baseline_ema = 0.0;
-        self.baseline_ema_init = false;
+    /// Restrict the solve to `trace[start..start + len]` of the trace passed to
+    /// `set_trace`, which stays resident: zooming or panning over a long recording
+    /// needs no new trace transfer. Like `set_trace` for the sub-slice otherwise —
+    /// iteration state, filtering, lambda map, and spike support are reset, and
+    /// FFT plans are reused when the padded length is unchanged. All getters and
+    /// indices then refer to the window. `set_window(0, n)` restores the full trace.
+    ///
+    /// Returns false and changes nothing if the window extends past the trace.
+    pub fn set_window(&mut self, start: usize, len: usize) -> bool {
+        match start.checked_add(len) {
+            Some(end) if end <= self.raw_len => {
+                self.activate_window(start, len);
+                true
+            }
+            _ => false,
+        }
+    }
+
+    /// Start of the active window within the `set_trace` trace (0 unless
+    /// `set_window` moved it).
+    pub fn get_window_start(&self) -> usize {
+        self.window_start
+    }
+
+    /// Length of the active region: the `set_trace` length, or the `set_window`
+    /// length. Every per-sample getter (solution, reconvolution, ...) returns
+    /// this many values.
+    pub fn active_length(&self) -> usize {
+        self.active_len
+    }
+
+    /// Make `raw_trace[start..start + len]` the active region: fresh working copy,
+    /// per-trace settings, cold solution, spectrum cache, and FFT plans.
+    fn activate_window(&mut self, start: usize, len: usize) {
+        self.window_start = start;
+        self.active_len = len;
+
+        // Copy trace data, reset per-trace settings, and cold-start the solution
+        let n = len;
+        self.trace[..n].copy_from_slice(&self.raw_trace[start..start + n]);
+        self.lambda_map[..n].fill(1.0);
+        self.support_active = false;
         self.filtered = false;
-        self.reconvolution_stale = true;
+        self.preprocessing.clear();
+        self.reset_solution();
+
+        // The cached spectrum belongs to the previous trace
+        self.bandpass.invalidate_power_spectrum();
+        if self.eager_spectrum {
+            self.bandpass.compute_spectrum_only(&self.trace[..n]);
+        }
 
         // Prepare FFT infrastructure for this trace length (skip if using banded mode)
         if self.conv_mode == ConvMode::Fft {
@@ -242,6 +713,18 @@ impl Solver {
         self.kernel.clone()
     }
 
+    /// The kernel evaluated at `factor`× the sample rate, for a smooth UI curve at
+    /// low fs. Same analytic shape, peak normalization, onset delay, and smoothing
+    /// as the solver's kernel, which it never replaces; `factor = 1` reproduces
+    /// `get_kernel`. Sample i lies at time `i / (factor * fs)` on the kernel's
+    /// axis. Empty for `factor = 0`.
+    pub fn get_kernel_oversampled(&self, factor: u32) -> Vec<f32> {
+        if factor == 0 {
+            return Vec::new();
+        }
+        self.kernel_at_rate(self.fs * factor as f64).0
+    }
+
     /// Returns the current solution (spike train) for the active region.
     ///
     /// See `get_kernel` for why this returns an owned copy rather than a memory view.
@@ -249,6 +732,129 @@ impl Solver {
         self.solution[..self.active_len].to_vec()
     }
 
+    /// Returns FISTA's extrapolated point y_k (the momentum-shifted iterate where the
+    /// next gradient is evaluated) for the active region, for inspecting how the
+    /// momentum overshoots and how adaptive restarts pull it back. Equals
+    /// `get_solution` right after a restart or `reset_momentum`.
+    pub fn get_solution_prev(&self) -> Vec<f32> {
+        self.solution_prev[..self.active_len].to_vec()
+    }
+
+    /// L2 norm of the current solution over the active region, ||x_k||. Tracked
+    /// per iteration by `set_divergence_factor`; NaN or infinite once the
+    /// iterates have blown up.
+    pub fn get_solution_norm(&self) -> f64 {
+        self.solution[..self.active_len]
+            .iter()
+            .map(|&v| v as f64 * v as f64)
+            .sum::<f64>()
+            .sqrt()
+    }
+
+    /// Returns only the nonzero solution samples (|v| > 1e-6) as interleaved
+    /// `[index, value, index, value, ...]`. Indices are stored as f32, exact for
+    /// traces up to 2^24 samples. For a sparse spike train this is orders of
+    /// magnitude smaller than `get_solution` to copy and postMessage.
+    pub fn get_solution_sparse(&self) -> Vec<f32> {
+        let mut sparse = Vec::new();
+        for (i, &v) in self.solution[..self.active_len].iter().enumerate() {
+            if v.abs() > SPIKE_EPS {
+                sparse.push(i as f32);
+                sparse.push(v);
+            }
+        }
+        sparse
+    }
+
+    /// Number of active-region solution samples strictly above `threshold`,
+    /// counted in one pass without copying the solution to JS.
+    pub fn count_spikes(&self, threshold: f32) -> u32 {
+        self.solution[..self.active_len]
+            .iter()
+            .filter(|&&v| v > threshold)
+            .count() as u32
+    }
+
+    /// Active-region sample indices whose solution value is strictly above
+    /// `threshold`, in ascending order (same rule as `count_spikes`). Returned as
+    /// `u32` so JS receives a `Uint32Array` for raster plots.
+    pub fn get_spike_indices(&self, threshold: f32) -> Vec<u32> {
+        self.solution[..self.active_len]
+            .iter()
+            .enumerate()
+            .filter(|&(_, &v)| v > threshold)
+            .map(|(i, _)| i as u32)
+            .collect()
+    }
+
+    /// Per-event fluorescence amplitude in trace (ΔF) units: `solution[i] * G_dc`
+    /// (times `get_gain`) for each sample above `threshold`, aligned with
+    /// `get_spike_indices`. G_dc is the kernel's integral (`get_kernel_dc_gain`),
+    /// so each value is the total fluorescence the event adds to the fit.
+    pub fn get_event_df_amplitudes(&self, threshold: f32) -> Vec<f32> {
+        let gain = (self.kernel_dc_gain * self.gain) as f32;
+        self.solution[..self.active_len]
+            .iter()
+            .filter(|&&v| v > threshold)
+            .map(|&v| v * gain)
+            .collect()
+    }
+
+    /// Counts of the nonzero active-region solution values in `n_bins` equal bins
+    /// over `[0, max_amp]` (the last bin includes `max_amp`; larger values are
+    /// not counted), for picking a `count_spikes` threshold from the data.
+    /// Empty if `n_bins` is 0 or `max_amp` is not finite and positive.
+    pub fn amplitude_histogram(&self, n_bins: usize, max_amp: f32) -> Vec<u32> {
+        self.histogram_scaled(n_bins, max_amp, 1.0)
+    }
+
+    /// `amplitude_histogram` of the amplitudes in trace (ΔF) units, as
+    /// `get_event_df_amplitudes`, so `max_amp` and the bin edges are in the
+    /// units of the plotted trace.
+    pub fn amplitude_histogram_df(&self, n_bins: usize, max_amp: f32) -> Vec<u32> {
+        self.histogram_scaled(n_bins, max_amp, (self.kernel_dc_gain * self.gain) as f32)
+    }
+
+    /// Total deconvolved activity: the sum of the active-region solution, for
+    /// ranking ROIs without copying their spike trains to JS. Signed solutions
+    /// (`Constraint::Signed`) sum with their sign.
+    pub fn get_total_activity(&self) -> f64 {
+        self.solution[..self.active_len]
+            .iter()
+            .map(|&v| v as f64)
+            .sum()
+    }
+
+    /// `get_total_activity` in trace (ΔF) units: scaled by the kernel DC gain and
+    /// the gain, i.e. the area under the fit each spike contributes (ΔF·samples),
+    /// which matches the summed reconvolution when no response is cut off at the end.
+    pub fn get_total_activity_df(&self) -> f64 {
+        self.get_total_activity() * self.kernel_dc_gain * self.gain
+    }
+
+    /// Calibrate the peak trace amplitude (e.g. ΔF/F) of a single action potential,
+    /// for `get_solution_in_spikes`. Default 1.0 (uncalibrated). Returns false and
+    /// keeps the previous value unless `amplitude` is finite and positive.
+    pub fn set_single_spike_amplitude(&mut self, amplitude: f32) -> bool {
+        if !(amplitude.is_finite() && amplitude > 0.0) {
+            return false;
+        }
+        self.single_spike_amplitude = amplitude;
+        true
+    }
+
+    /// The solution as estimated spike counts per bin: `gain * solution[i] / a`
+    /// with `a` from `set_single_spike_amplitude`. The kernel is peak-normalized,
+    /// so `gain * solution[i]` is the peak amplitude of its transient in trace
+    /// units; dividing by the per-spike peak converts it to a count.
+    pub fn get_solution_in_spikes(&self) -> Vec<f32> {
+        let scale = self.gain as f32 / self.single_spike_amplitude;
+        self.solution[..self.active_len]
+            .iter()
+            .map(|&v| v * scale)
+            .collect()
+    }
+
     /// Returns the reconvolution (K * solution) for the active region.
     /// Computes the reconvolution lazily if it is stale (not computed during iteration).
     ///
@@ -260,6 +866,80 @@ impl Solver {
         self.reconvolution[..self.active_len].to_vec()
     }
 
+    /// Returns the full linear convolution K * solution, `n + k_len - 1` samples,
+    /// including the decay tail of late spikes that extends past the window edge.
+    /// The first `n` samples match `get_reconvolution` (the estimated gain
+    /// included). Baseline is not added.
+    /// With an acausal kernel the `get_kernel_offset` samples before t = 0 are
+    /// dropped, so the output is that much shorter.
+    pub fn get_reconvolution_full(&mut self) -> Vec<f32> {
+        let n = self.active_len;
+        if n == 0 {
+            return Vec::new();
+        }
+        let full_len = n + self.kernel.len() - 1 - self.kernel_offset;
+        let mut full = vec![0.0_f32; full_len];
+        match self.conv_mode {
+            ConvMode::Fft => {
+                self.fft
+                    .convolve_forward_full(&self.solution[..n], n, &mut full);
+            }
+            ConvMode::BandedAR2 => {
+                // The recursion continues the tail when fed trailing zeros
+                let mut padded = vec![0.0_f32; full_len];
+                padded[..n].copy_from_slice(&self.solution[..n]);
+                self.banded.convolve_forward(&padded, &mut full);
+            }
+        }
+        if self.gain != 1.0 {
+            let g = self.gain as f32;
+            for v in &mut full {
+                *v *= g;
+            }
+        }
+        full
+    }
+
+    /// Forward-convolve an external spike train with the current kernel, e.g. to
+    /// overlay the trace predicted by ephys spike times. Returns `K * spikes` over
+    /// the active length; `spikes` is truncated or zero-padded to it. Uses the same
+    /// engine as the fit and leaves solver state untouched. Baseline is not added.
+    pub fn forward_convolve(&mut self, spikes: &[f32]) -> Vec<f32> {
+        let n = self.active_len;
+        let mut source = vec![0.0_f32; n];
+        let m = spikes.len().min(n);
+        source[..m].copy_from_slice(&spikes[..m]);
+        let mut out = vec![0.0_f32; n];
+        if n == 0 {
+            return out;
+        }
+        match self.conv_mode {
+            ConvMode::Fft => self.fft.convolve_forward(&source, n, &mut out),
+            ConvMode::BandedAR2 => self.banded.convolve_forward(&source, &mut out),
+        }
+        out
+    }
+
+    /// Deterministic example trace for demos: `n_spikes` unit spikes at distinct
+    /// random samples (`generate_demo_spikes` returns them), convolved with the
+    /// current kernel, plus Gaussian noise of std `noise`. Same output for the
+    /// same arguments and kernel; solver state is untouched.
+    pub fn generate_demo_trace(
+        &self,
+        n: usize,
+        n_spikes: usize,
+        noise: f32,
+        seed: u64,
+    ) -> Vec<f32> {
+        simulate::demo_trace(n, n_spikes, noise, seed, &self.kernel, self.kernel_offset)
+    }
+
+    /// Ground-truth spike train (0/1 per sample) of `generate_demo_trace` for the
+    /// same `n`, `n_spikes`, and `seed`.
+    pub fn generate_demo_spikes(&self, n: usize, n_spikes: usize, seed: u64) -> Vec<f32> {
+        simulate::demo_spikes(n, n_spikes, seed)
+    }
+
     /// Returns reconvolution with baseline added: K*s + b for the active region.
     /// Computes the reconvolution lazily if it is stale.
     ///
@@ -268,15 +948,32 @@ impl Solver {
         if self.reconvolution_stale {
             self.compute_reconvolution();
         }
+        let n = self.active_len;
+        if self.tv_baseline_active() {
+            return self.reconvolution[..n]
+                .iter()
+                .zip(&self.baseline_series[..n])
+                .map(|(&v, &b)| v + b)
+                .collect();
+        }
         let b = self.baseline_ema as f32;
-        self.reconvolution[..self.active_len]
-            .iter()
-            .map(|&v| v + b)
-            .collect()
+        self.reconvolution[..n].iter().map(|&v| v + b).collect()
+    }
+
+    /// Solution and reconvolution-with-baseline in one allocation for the redraw
+    /// path: `[s[0..n], (K*s + b)[0..n]]`, `2n` values from the same solution state.
+    /// Split at `n` (half the length) on the JS side.
+    pub fn get_results(&mut self) -> Vec<f32> {
+        let n = self.active_len;
+        let mut out = Vec::with_capacity(2 * n);
+        out.extend_from_slice(&self.solution[..n]);
+        out.extend(self.get_reconvolution_with_baseline());
+        out
     }
 
     /// Returns the estimated scalar baseline (EMA-smoothed for stable display).
     /// Lazily computes reconvolution if stale, to ensure the EMA is up to date.
+    /// With a TV baseline (`set_baseline_tv_lambda`) this is the mean of b[t].
     pub fn get_baseline(&mut self) -> f64 {
         if self.reconvolution_stale {
             self.compute_reconvolution();
@@ -284,14 +981,60 @@ impl Solver {
         self.baseline_ema
     }
 
-    /// Returns the current trace for the active region.
-    /// After apply_filter(), this contains the filtered trace.
+    /// Non-finite residual terms (NaN or infinite trace or reconvolution samples)
+    /// skipped by the latest mean baseline estimate; 0 for the other baseline modes.
+    pub fn get_baseline_nonfinite_count(&mut self) -> u32 {
+        if self.reconvolution_stale {
+            self.compute_reconvolution();
+        }
+        self.baseline_nonfinite
+    }
+
+    /// Fitted baseline at each active sample, for plotting under the trace: the
+    /// TV series b[t] when `set_baseline_tv_lambda` is active, otherwise
+    /// `get_baseline` repeated. Lazily computes reconvolution if stale, so it
+    /// matches `get_reconvolution_with_baseline` minus `get_reconvolution`.
+    pub fn get_baseline_series(&mut self) -> Vec<f32> {
+        if self.reconvolution_stale {
+            self.compute_reconvolution();
+        }
+        let n = self.active_len;
+        if self.tv_baseline_active() {
+            self.baseline_series[..n].to_vec()
+        } else {
+            vec![self.baseline_ema as f32; n]
+        }
+    }
+
+    /// Returns the trace the solver fits for the active region: the filtered copy
+    /// (after apply_filter/subtract_baseline) unless `set_use_filtered_trace(false)`.
     ///
     /// See `get_kernel` for why this returns an owned copy rather than a memory view.
     pub fn get_trace(&self) -> Vec<f32> {
+        self.active_trace().to_vec()
+    }
+
+    /// Returns the trace exactly as passed to `set_trace`, unaffected by filtering.
+    pub fn get_trace_raw(&self) -> Vec<f32> {
+        self.raw_trace[self.window_start..self.window_start + self.active_len].to_vec()
+    }
+
+    /// Returns the filtered / baseline-subtracted copy of the trace. Equals the raw
+    /// trace until `apply_filter` or `subtract_baseline` runs.
+    pub fn get_trace_filtered(&self) -> Vec<f32> {
         self.trace[..self.active_len].to_vec()
     }
 
+    /// Choose whether the solver fits the filtered copy (default) or the raw trace.
+    /// Filtering is non-destructive, so this can be toggled without `set_trace`
+    /// for before/after comparisons; iteration state is kept as a warm start.
+    pub fn set_use_filtered_trace(&mut self, enabled: bool) {
+        self.use_filtered_trace = enabled;
+        self.converged = false;
+        self.prev_objective = f64::INFINITY;
+        self.reconvolution_stale = true;
+    }
+
     /// Returns whether the solver has converged.
     pub fn converged(&self) -> bool {
         self.converged
@@ -302,6 +1045,37 @@ impl Solver {
         self.iteration
     }
 
+    /// Returns how many adaptive restarts have fired since the last `set_trace`.
+    /// A high count relative to `iteration_count` signals an ill-conditioned problem
+    /// (e.g. a nearly rank-deficient kernel).
+    pub fn get_restart_count(&self) -> u32 {
+        self.restart_count
+    }
+
+    /// Returns the wall-clock duration of the last `step_batch` call in microseconds,
+    /// measured inside WASM (`performance.now()`) so JS boundary overhead is excluded.
+    /// Divide the batch size by this for an iterations/second readout.
+    pub fn get_last_batch_micros(&self) -> f64 {
+        self.last_batch_micros
+    }
+
+    /// Ask the running (or next) `step_batch` to stop before its next iteration.
+    /// Iterations are never interrupted midway, so the solution, momentum, and
+    /// iteration count stay consistent and the following batch resumes cleanly.
+    /// The request is consumed by the batch that honors it.
+    ///
+    /// In a single-threaded wasm worker this takes effect from the next batch;
+    /// native callers can cancel a batch in flight from another thread via
+    /// `cancel_handle`.
+    pub fn request_cancel(&self) {
+        self.cancel_flag.store(true, Ordering::Relaxed);
+    }
+
+    /// Whether the last `step_batch` stopped early on a cancel request.
+    pub fn was_cancelled(&self) -> bool {
+        self.batch_cancelled
+    }
+
     /// Reset FISTA momentum. Used for warm-start after kernel change.
     /// Sets t_fista = 1.0 and copies solution into solution_prev.
     pub fn reset_momentum(&mut self) {
@@ -310,6 +1084,85 @@ impl Solver {
         self.solution_prev[..n].copy_from_slice(&self.solution[..n]);
     }
 
+    /// Rescale the current solution by `old_dc_gain / new_dc_gain` whenever the
+    /// kernel changes (`set_params`, onset delay, smoothing, ...), so each event
+    /// keeps its fluorescence integral. This helps when the fit is set by area,
+    /// as with dense, overlapping transients. Isolated spikes already keep their
+    /// peak under the peak-normalized kernel, so leave it off for sparse
+    /// activity. Clears convergence so the next `step_batch` refits; pair with
+    /// `reset_momentum`. Skipped when either DC gain is not positive. Default
+    /// off (the solution is kept as is).
+    pub fn set_rescale_on_kernel_change(&mut self, enabled: bool) {
+        self.rescale_on_kernel_change = enabled;
+    }
+
+    /// Cold-restart the solve on the loaded trace: zero the solution, momentum,
+    /// and baseline, and reset iteration and convergence state. The trace (and
+    /// any filtering), kernel, FFT plans, lambda map, and spike support are kept,
+    /// so this is much cheaper than `set_trace` for A/B comparisons.
+    pub fn reset_solution(&mut self) {
+        let n = self.active_len;
+        self.solution[..n].fill(0.0);
+        self.solution_prev[..n].fill(0.0);
+        self.gradient[..n].fill(0.0);
+        self.reconvolution[..n].fill(0.0);
+        self.residual_buf[..n].fill(0.0);
+        self.baseline_series[..n].fill(0.0);
+        self.min_spike_engaged = false;
+
+        // Reset iteration state
+        self.iteration = 0;
+        self.warm_start_iters = None;
+        self.t_fista = 1.0;
+        self.converged = false;
+        self.prev_objective = f64::INFINITY;
+        self.rel_change = f64::INFINITY;
+        self.batch_restarted = false;
+        self.restart_count = 0;
+        self.reset_status_tracking();
+        self.objective_history.clear();
+        self.gain = 1.0;
+        self.gain_engaged = false;
+        self.baseline = self.fixed_baseline.unwrap_or(0.0);
+        self.baseline_ema = self.baseline;
+        self.baseline_ema_init = self.fixed_baseline.is_some();
+        self.reconvolution_stale = true;
+    }
+
+    /// Seed the solution for a warm start from a plain array (e.g. a previous
+    /// `get_solution`), without the binary `load_state` format. Values are
+    /// projected onto the current constraint (>= 0, [0, 1] under `Box01`, kept
+    /// as-is under `Signed`) and non-finite ones set to 0; entries past the
+    /// active length are ignored and missing ones are 0.
+    /// Momentum is reset (t_fista = 1, solution_prev = solution).
+    pub fn set_solution(&mut self, solution: &[f32]) {
+        let n = self.active_len;
+        let m = solution.len().min(n);
+        for (dst, &v) in self.solution[..m].iter_mut().zip(solution) {
+            *dst = if v.is_finite() {
+                self.constraint.project(v)
+            } else {
+                0.0
+            };
+        }
+        self.solution[m..n].fill(0.0);
+        self.reset_momentum();
+        self.warm_start_iters = None;
+        self.converged = false;
+        self.prev_objective = f64::INFINITY;
+        self.reconvolution_stale = true;
+        self.reset_status_tracking();
+    }
+
+    /// Seed the solve of a new ROI with the solution of a neighboring one:
+    /// adjacent cells in a field of view fire together, so theirs is a better
+    /// start than zeros. Call after `set_trace` (which resets the solution).
+    /// Same seeding as `set_solution`: projected onto the current constraint,
+    /// truncated or zero-padded to the active length, momentum reset.
+    pub fn warm_start_from(&mut self, other_solution: &[f32]) {
+        self.set_solution(other_solution);
+    }
+
     /// Set the convolution mode (FFT or BandedAR2).
     /// Recomputes the Lipschitz constant for the selected mode.
     /// Does NOT reset solution/iteration state — warm-start is preserved.
@@ -330,34 +1183,517 @@ impl Solver {
         self.lipschitz_constant = self.current_lipschitz();
     }
 
-    /// Set the constraint type (NonNegative or Box01).
-    pub fn set_constraint(&mut self, c: Constraint) {
-        self.constraint = c;
+    /// Set the FFT padding strategy. Rebuilds FFT plans for the active trace if
+    /// the padded length changes; results are identical up to f32 roundoff.
+    pub fn set_fft_padding(&mut self, padding: FftPadding) {
+        self.fft.set_padding(padding);
+        if self.conv_mode == ConvMode::Fft && self.active_len > 0 {
+            self.fft.ensure_buffers(self.active_len, &self.kernel);
+        }
     }
 
-    /// Lipschitz constant for the current convolution mode.
-    fn current_lipschitz(&self) -> f64 {
-        match self.conv_mode {
-            ConvMode::Fft => compute_lipschitz(&self.kernel),
-            ConvMode::BandedAR2 => self.banded.lipschitz(),
-        }
+    /// Padded FFT length used for the current trace and kernel (see
+    /// `set_fft_padding`), or 0 before the FFT path has been set up (e.g. a
+    /// solver that has only run in BandedAR2 mode).
+    pub fn get_fft_len(&self) -> usize {
+        self.fft.fft_len()
     }
 
-    /// Effective lambda scaled by kernel DC gain: lambda * G_dc.
-    pub(crate) fn effective_lambda(&self) -> f64 {
-        self.lambda * self.kernel_dc_gain
+    /// Forward plus inverse FFTs run by the convolution engine and the bandpass
+    /// filter since construction or `reset_fft_op_count`, for checking that
+    /// caching actually saves work (e.g. a lambda-only `set_params` runs none).
+    pub fn get_fft_op_count(&self) -> u64 {
+        self.fft.op_count() + self.bandpass.op_count()
     }
 
-    /// Serialize solver state for warm-start cache.
-    /// Format: [active_len (u32)] [t_fista (f64)] [iteration (u32)] [baseline (f64)] [solution f32...] [solution_prev f32...]
-    pub fn export_state(&self) -> Vec<u8> {
-        let n = self.active_len;
-        let mut buf = Vec::with_capacity(state_byte_len(n));
+    pub fn reset_fft_op_count(&mut self) {
+        self.fft.reset_op_count();
+        self.bandpass.reset_op_count();
+    }
+
+    /// True when the kernel has more taps than the active trace has samples
+    /// (e.g. a long tau_decay at a low sampling rate). The deconvolution is then
+    /// ill-posed and the FFT padding is dominated by the kernel, so the UI should
+    /// warn rather than present a degenerate, slow solve. Re-evaluated against the
+    /// current kernel and trace, so it tracks `set_trace`, `set_window`, and
+    /// `set_params`. False when no trace is loaded.
+    pub fn kernel_too_long_for_trace(&self) -> bool {
+        self.active_len > 0 && self.kernel.len() > self.active_len
+    }
+
+    /// Set the constraint type (NonNegative, Box01, or Signed to allow negative
+    /// events). Baseline estimation is the same for all three.
+    pub fn set_constraint(&mut self, c: Constraint) {
+        self.constraint = c;
+    }
+
+    /// Zero any solution value below `min_spike` (in magnitude under
+    /// `Constraint::Signed`) after each proximal step, to keep tiny spurious
+    /// spikes out of the solution and event lists. This is a
+    /// heuristic: the hard floor is not a proximal operator, so the iteration no
+    /// longer minimizes the stated objective exactly. Default 0 (off); negative
+    /// or non-finite values are treated as 0.
+    ///
+    /// Spikes grow from zero in small steps, so a floor from the first iteration
+    /// would keep real ones at zero. It engages once the plain L1 iteration has
+    /// converged (after each `set_trace` or call here), and iteration continues
+    /// with the floor until it converges again.
+    pub fn set_min_spike_amplitude(&mut self, min_spike: f32) {
+        self.min_spike = if min_spike.is_finite() {
+            min_spike.max(0.0)
+        } else {
+            0.0
+        };
+        self.min_spike_engaged = false;
+        self.converged = false;
+        self.prev_objective = f64::INFINITY;
+    }
+
+    /// Tilt the data term to `½·Σ w_i·r_i²` with `w = 1 + asymmetry` where the fit
+    /// is below the data and `1 - asymmetry` where it is above. A positive value
+    /// discounts downward artifacts (e.g. motion dips) that non-negative spikes
+    /// cannot explain, so they bias the baseline and spikes less; a negative value
+    /// does the opposite. The scalar `Mean` baseline becomes the matching
+    /// expectile; percentile and TV baselines are unchanged. The step size shrinks
+    /// by `1 + |asymmetry|`.
+    ///
+    /// Default 0 (symmetric least squares). Returns false and leaves the setting
+    /// unchanged unless `asymmetry` is finite and in (-1, 1).
+    pub fn set_asymmetry(&mut self, asymmetry: f64) -> bool {
+        if !asymmetry.is_finite() || asymmetry.abs() >= 1.0 {
+            return false;
+        }
+        self.asymmetry = asymmetry;
+        self.converged = false;
+        self.prev_objective = f64::INFINITY;
+        self.reconvolution_stale = true;
+        true
+    }
+
+    /// Select the data term: least squares (default) or the Poisson negative
+    /// log-likelihood for low photon counts. The trace must then be raw counts
+    /// (unfiltered, non-negative) with a positive baseline b, which is still
+    /// estimated as the mean residual (unbiased, since E[y] = K*s + b). The
+    /// Poisson gradient's Lipschitz bound `c·L·max(y)/min(b)²` holds because
+    /// μ >= b for a non-negative kernel and spikes, so the step size is
+    /// recomputed from it every iteration. `set_asymmetry` only tilts least squares.
+    pub fn set_data_term(&mut self, term: DataTerm) {
+        self.data_term = term;
+        self.converged = false;
+        self.prev_objective = f64::INFINITY;
+        self.reconvolution_stale = true;
+    }
+
+    /// Clamp the estimated baseline at 0 during iteration. For ΔF/F traces the
+    /// baseline cannot be negative, and a negative estimate combined with s >= 0
+    /// biases the fit. Default false.
+    pub fn set_baseline_nonneg(&mut self, enabled: bool) {
+        self.baseline_nonneg = enabled;
+        self.reconvolution_stale = true;
+    }
+
+    /// Pin the baseline to a known value `b` (e.g. measured from a blank region)
+    /// instead of estimating it jointly. `step_batch` then skips the per-iteration
+    /// baseline reduction and results are reproducible for a given `b`. Overrides
+    /// the baseline mode, the TV baseline, `set_baseline_nonneg`, and the skip for
+    /// filtered traces. Non-finite `b` is rejected (returns false, no change).
+    pub fn set_fixed_baseline(&mut self, b: f64) -> bool {
+        if !b.is_finite() {
+            return false;
+        }
+        self.fixed_baseline = Some(b);
+        self.baseline = b;
+        self.baseline_ema = b;
+        self.baseline_ema_init = true;
+        self.converged = false;
+        self.prev_objective = f64::INFINITY;
+        self.reconvolution_stale = true;
+        true
+    }
+
+    /// Return to estimating the baseline from the residual (the default).
+    pub fn clear_fixed_baseline(&mut self) {
+        self.fixed_baseline = None;
+        self.converged = false;
+        self.prev_objective = f64::INFINITY;
+        self.reconvolution_stale = true;
+    }
+
+    /// Whether the baseline is pinned by `set_fixed_baseline` (true) or estimated.
+    pub fn baseline_is_fixed(&self) -> bool {
+        self.fixed_baseline.is_some()
+    }
+
+    /// Fit `y ≈ g*(K*s) + b` with a scalar gain `g` absorbing unknown indicator
+    /// sensitivity: once the g = 1 fit has converged, each iteration updates g by
+    /// closed-form least squares given the current spikes and baseline, like the
+    /// baseline itself. The L1 penalty applies to the trace-unit amplitudes g*s, so
+    /// the sparsity slider keeps its meaning; g and s are then only determined up
+    /// to a common scale, which is pinned by normalizing the spikes so the largest
+    /// is 1. g is thus the largest event's amplitude in trace units, and a train
+    /// of equal events comes out as unit spikes. Default off (g = 1). Disabling
+    /// resets g to 1.
+    pub fn set_gain_estimation(&mut self, enabled: bool) {
+        self.estimate_gain = enabled;
+        if !enabled {
+            self.gain = 1.0;
+            self.gain_engaged = false;
+        }
+        self.converged = false;
+        self.prev_objective = f64::INFINITY;
+        self.reconvolution_stale = true;
+    }
+
+    /// Current multiplicative gain g (1.0 unless `set_gain_estimation` is on).
+    pub fn get_gain(&self) -> f64 {
+        self.gain
+    }
+
+    /// Model the baseline as a per-sample series b[t] with a total-variation penalty
+    /// `tv_lambda·Σ|b[t+1] - b[t]|`, for slow drift. Each iteration solves for b
+    /// exactly given the current spikes (1-D TV denoising of `trace - K*y_k`),
+    /// so it stays piecewise smooth and only jumps where the data demand it.
+    /// Larger values give a flatter baseline; 0 (default) keeps the scalar baseline.
+    /// Applies to filtered traces too, and overrides `set_baseline_percentile`.
+    pub fn set_baseline_tv_lambda(&mut self, tv_lambda: f64) {
+        self.baseline_tv_lambda = if tv_lambda.is_finite() {
+            tv_lambda.max(0.0)
+        } else {
+            0.0
+        };
+        self.converged = false;
+        self.prev_objective = f64::INFINITY;
+        self.reconvolution_stale = true;
+    }
+
+    /// Estimate the baseline as the `quantile` (in [0, 1]) of the residual instead of
+    /// its mean. See `BaselineMode::Percentile` for the robustness/bias tradeoff.
+    pub fn set_baseline_percentile(&mut self, quantile: f64) {
+        self.set_baseline_mode(BaselineMode::Percentile(quantile));
+    }
+
+    /// Restore the default mean-residual baseline estimate.
+    pub fn set_baseline_mean(&mut self) {
+        self.set_baseline_mode(BaselineMode::Mean);
+    }
+
+    /// Select the convergence criterion (default `PrimalResidual`).
+    pub fn set_convergence_mode(&mut self, mode: ConvergenceMode) {
+        self.convergence_mode = mode;
+        self.prev_objective = f64::INFINITY;
+    }
+
+    /// Iterations (counted since `set_trace` / `reset_solution`) that must
+    /// complete before `step_batch` may declare convergence; it can stop after
+    /// iteration `warmup + 1` at the earliest. Default 5, which guards against a
+    /// spurious stop during the first momentum steps of a cold start. Set 0 for
+    /// `set_solution` warm starts so an already-converged solution is detected on
+    /// the first iteration; `load_state` uses `set_warm_start_warmup` instead.
+    pub fn set_convergence_warmup(&mut self, warmup: u32) {
+        self.convergence_warmup = warmup;
+    }
+
+    /// Convergence warm-up after a successful `load_state`, counted from the load
+    /// instead of from the restored iteration count. Default 0: a restored,
+    /// nearly converged solution may stop on the first iteration, with the
+    /// restored objective as the reference for `ObjectiveChange`. Raise it if the
+    /// first steps after a load (momentum settling) should not count.
+    pub fn set_warm_start_warmup(&mut self, warmup: u32) {
+        self.warm_start_warmup = warmup;
+    }
+
+    /// Record the objective every `every` iterations during `step_batch`, for
+    /// convergence plots, independently of the convergence criterion. Each sample
+    /// costs one extra forward convolution under `PrimalResidual` (none under
+    /// `ObjectiveChange`, which computes it anyway). 0 (default) disables
+    /// sampling. Clears the recorded history.
+    pub fn set_objective_sampling(&mut self, every: u32) {
+        self.objective_sample_every = every;
+        self.objective_history.clear();
+    }
+
+    /// Objective values recorded by `set_objective_sampling`, oldest first: entry
+    /// j was taken at the `(j + 1) * every`-th iteration since `set_trace` (or
+    /// `reset_solution`, or the last `set_objective_sampling`).
+    pub fn get_objective_history(&self) -> Vec<f64> {
+        self.objective_history.clone()
+    }
+
+    /// Diagnostic: relative error of the adjoint identity <Kx, y> = <x, K^T y> for
+    /// fixed deterministic x, y over the active length, using the solver's own
+    /// convolution path (FFT or BandedAR2) and current kernel. The FISTA gradient
+    /// is only correct when this is ~0 (f32 roundoff, well below 1e-4).
+    ///
+    /// Not called during solving; working buffers are left untouched.
+    /// Returns NaN when no trace is loaded.
+    pub fn check_adjoint(&mut self) -> f64 {
+        let n = self.active_len;
+        if n == 0 {
+            return f64::NAN;
+        }
+        let x: Vec<f32> = (0..n).map(|i| (i as f32 * 0.3).sin()).collect();
+        let y: Vec<f32> = (0..n).map(|i| (i as f32 * 0.7 + 1.0).cos()).collect();
+        let mut kx = vec![0.0_f32; n];
+        let mut kty = vec![0.0_f32; n];
+        match self.conv_mode {
+            ConvMode::Fft => {
+                self.fft.convolve_forward(&x, n, &mut kx);
+                self.fft.convolve_adjoint(&y, n, &mut kty);
+            }
+            ConvMode::BandedAR2 => {
+                self.banded.convolve_forward(&x, &mut kx);
+                self.banded.convolve_adjoint(&y, &mut kty);
+            }
+        }
+        let dot = |a: &[f32], b: &[f32]| -> f64 {
+            a.iter().zip(b).map(|(&p, &q)| p as f64 * q as f64).sum()
+        };
+        let lhs = dot(&kx, &y);
+        let rhs = dot(&x, &kty);
+        (lhs - rhs).abs() / lhs.abs().max(rhs.abs()).max(1e-12)
+    }
+
+    /// Lipschitz constant for the current convolution mode.
+    fn current_lipschitz(&self) -> f64 {
+        match self.conv_mode {
+            ConvMode::Fft => compute_lipschitz(&self.kernel),
+            ConvMode::BandedAR2 => self.banded.lipschitz(),
+        }
+    }
+
+    /// Slowest decay constant of the active kernel — tau_decay, or the weighted
+    /// bi-exponential envelope. Drives filter cutoffs and baseline windows.
+    fn decay_envelope(&self) -> f64 {
+        match self.slow_decay {
+            Some((tau_slow, weight)) => biexp_envelope_tau(self.tau_decay, tau_slow, weight),
+            None => self.tau_decay,
+        }
+    }
+
+    /// Rebuild the kernel from the stored parameters and refresh everything derived
+    /// from it: DC gain, filter cutoffs, the active convolution engine, and Lipschitz.
+    fn rebuild_kernel(&mut self) {
+        let (kernel, offset) = self.kernel_at_rate(self.fs);
+        self.bandpass
+            .update_cutoffs(self.tau_rise, self.decay_envelope(), self.fs);
+
+        // Update convolution engines (only the active one + compute Lipschitz)
+        match self.conv_mode {
+            ConvMode::BandedAR2 => {
+                self.banded.update(self.tau_rise, self.tau_decay, self.fs);
+            }
+            ConvMode::Fft => {
+                // banded will be updated lazily if conv_mode switches
+            }
+        }
+        self.set_kernel_samples(kernel, offset);
+    }
+
+    /// Kernel samples and smoothing offset for the current kinetics, onset delay,
+    /// and smoothing, sampled at `fs` (the solver's rate, or finer for display).
+    fn kernel_at_rate(&self, fs: f64) -> (Vec<f32>, usize) {
+        let kernel = match (&self.custom_kernel, self.slow_decay) {
+            (Some(custom), _) if fs == self.fs => custom.clone(),
+            (Some(custom), _) => {
+                let len = ((custom.len() as f64 * fs / self.fs).round() as usize).max(1);
+                resample::resample_fft(custom, len)
+            }
+            (None, Some((tau_slow, weight))) => {
+                build_biexp_kernel(self.tau_rise, self.tau_decay, tau_slow, weight, fs)
+            }
+            (None, None) => build_kernel(self.tau_rise, self.tau_decay, fs),
+        };
+        let kernel = apply_tail_taper(kernel, self.kernel_taper);
+        let kernel = apply_onset_delay(kernel, self.onset_delay_s, fs);
+        smooth_kernel_symmetric(&kernel, self.smoothing_sigma_s * fs)
+    }
+
+    /// Install kernel samples and refresh what is derived from them: DC gain,
+    /// lambda scale, Lipschitz constant, and the kernel FFT. A no-op when the samples and offset
+    /// are unchanged (e.g. re-applying the same onset delay or smoothing), so the
+    /// O(k) DC sum, the spectral Lipschitz bound, and the kernel FFT are skipped.
+    fn set_kernel_samples(&mut self, kernel: Vec<f32>, offset: usize) {
+        if kernel == self.kernel && offset == self.kernel_offset {
+            return;
+        }
+        self.kernel = kernel;
+        self.kernel_offset = offset;
+        self.fft.set_kernel_offset(offset);
+        let old_dc_gain = self.kernel_dc_gain;
+        self.kernel_dc_gain = self.kernel.iter().map(|&k| k as f64).sum();
+        if self.rescale_on_kernel_change {
+            self.rescale_solution(old_dc_gain / self.kernel_dc_gain);
+        }
+        let l1 = self.get_kernel_l1();
+        (self.lambda_scale, self.lambda_norm) = if self.kernel_dc_gain >= MIN_DC_GAIN_FRACTION * l1
+        {
+            (LambdaScale::DcGain, self.kernel_dc_gain)
+        } else {
+            (LambdaScale::L1Norm, l1)
+        };
+        self.lipschitz_constant = self.current_lipschitz();
+
+        // Update kernel FFT if buffers are already set up and large enough.
+        // On re-enqueue quanta with unchanged trace length, this avoids a full
+        // FFT plan + buffer rebuild in ensure_buffers. A longer kernel that no
+        // longer fits gets new plans now, so step_batch never sees stale buffers.
+        // With an empty trace the plans are kept for the next one, so the kernel
+        // spectrum must still be refreshed (sized as for a 1-sample trace).
+        if self.conv_mode == ConvMode::Fft && self.fft.fft_len() > 0 {
+            let min_len = self.active_len.max(1) + self.kernel.len() - 1;
+            if min_len <= self.fft.fft_len() {
+                self.fft.prepare_kernel(&self.kernel);
+            } else {
+                self.fft.ensure_buffers(self.active_len, &self.kernel);
+            }
+        }
+    }
+
+    /// Effective lambda scaled by kernel DC gain: lambda * G_dc. This is the L1
+    /// weight actually applied, which is why the same slider value acts more
+    /// strongly for slower (larger-area) kernels. For a kernel whose area nearly
+    /// cancels, Σ|h| stands in for G_dc (`get_lambda_scale`).
+    pub fn get_effective_lambda(&self) -> f64 {
+        self.lambda * self.lambda_norm
+    }
+
+    /// Which kernel norm `get_effective_lambda` multiplies lambda by: the DC gain,
+    /// or Σ|h| when G_dc < `MIN_DC_GAIN_FRACTION`·Σ|h| (a custom kernel with
+    /// near-zero or negative area).
+    pub fn get_lambda_scale(&self) -> LambdaScale {
+        self.lambda_scale
+    }
+
+    /// Set a per-sample multiplier on lambda for the active trace, so the soft
+    /// threshold becomes `step_size * effective_lambda * map[i]`. Values < 1
+    /// encourage spikes (e.g. stimulation epochs), > 1 suppress them.
+    ///
+    /// Call after `set_trace`, which resets the map to all-ones. Returns false and
+    /// leaves the map unchanged if `map.len()` differs from the active trace length
+    /// or contains negative / non-finite values.
+    pub fn set_lambda_map(&mut self, map: &[f32]) -> bool {
+        let n = self.active_len;
+        if map.len() != n || map.iter().any(|&m| !m.is_finite() || m < 0.0) {
+            return false;
+        }
+        self.lambda_map[..n].copy_from_slice(map);
+        self.converged = false;
+        self.prev_objective = f64::INFINITY;
+        true
+    }
+
+    /// Restrict nonzero activity to the given sample indices (e.g. spike times from
+    /// paired electrophysiology), estimating only their amplitudes and the baseline.
+    /// The problem becomes non-negative least squares over a fixed support: the L1
+    /// penalty is dropped and `step_batch` zeroes the solution off the support after
+    /// each proximal step.
+    ///
+    /// Call after `set_trace`, which clears the support. Returns false and leaves
+    /// the solver unconstrained if any index is outside the active trace.
+    pub fn set_spike_support(&mut self, indices: &[usize]) -> bool {
+        let n = self.active_len;
+        if indices.iter().any(|&i| i >= n) {
+            return false;
+        }
+        self.support_mask[..n].fill(0.0);
+        for &i in indices {
+            self.support_mask[i] = 1.0;
+        }
+        for i in 0..n {
+            self.solution[i] *= self.support_mask[i];
+            self.solution_prev[i] *= self.support_mask[i];
+        }
+        self.support_active = true;
+        self.converged = false;
+        self.prev_objective = f64::INFINITY;
+        self.reconvolution_stale = true;
+        true
+    }
+
+    /// Remove the spike-support restriction (sparse deconvolution again).
+    pub fn clear_spike_support(&mut self) {
+        self.support_active = false;
+        self.converged = false;
+        self.prev_objective = f64::INFINITY;
+    }
+
+    /// Returns the kernel DC gain G_dc = sum(kernel), the lambda scale factor.
+    pub fn get_kernel_dc_gain(&self) -> f64 {
+        self.kernel_dc_gain
+    }
+
+    /// L1 norm of the kernel, Σ|h|. Equals the DC gain for the (non-negative)
+    /// built-in kernels.
+    pub fn get_kernel_l1(&self) -> f64 {
+        self.kernel.iter().map(|&k| (k as f64).abs()).sum()
+    }
+
+    /// L2 norm of the kernel, sqrt(Σh²) — the response energy of a unit spike.
+    pub fn get_kernel_l2(&self) -> f64 {
+        self.kernel
+            .iter()
+            .map(|&k| k as f64 * k as f64)
+            .sum::<f64>()
+            .sqrt()
+    }
+
+    /// Largest kernel sample; 1.0 by construction for the built-in kernels.
+    pub fn get_kernel_peak(&self) -> f64 {
+        self.kernel.iter().fold(f32::NEG_INFINITY, |m, &k| m.max(k)) as f64
+    }
+
+    /// `[tau_rise, tau_decay]` in seconds recovered from the built kernel by
+    /// `fit_kernel_taus` in the kernel module, e.g. to check that a custom kernel from
+    /// `set_kernel` has the kinetics it is meant to. Smoothing and tail tapering
+    /// are part of the kernel, so they bias the estimate slightly. Empty when
+    /// the kernel has no fittable rise and decay.
+    pub fn fit_kernel_taus(&self) -> Vec<f64> {
+        match fit_kernel_taus(&self.kernel, self.fs) {
+            Some((tau_rise, tau_decay)) => vec![tau_rise, tau_decay],
+            None => Vec::new(),
+        }
+    }
+
+    /// Load a trace stored as base64 of little-endian f32 samples (e.g. a saved
+    /// recording from the community DB), then behave exactly like `set_trace`.
+    /// Standard alphabet, `=` padding optional, ASCII whitespace ignored. Returns
+    /// false and leaves the solver unchanged if the text is not valid base64 or
+    /// the decoded length is not a multiple of 4 bytes.
+    pub fn set_trace_base64(&mut self, b64: &str) -> bool {
+        let bytes = match decode_base64(b64) {
+            Some(bytes) if bytes.len() % 4 == 0 => bytes,
+            _ => return false,
+        };
+        let trace: Vec<f32> = bytes
+            .chunks_exact(4)
+            .map(|c| f32::from_le_bytes([c[0], c[1], c[2], c[3]]))
+            .collect();
+        self.set_trace(&trace);
+        true
+    }
+
+    /// Serialize solver state for warm-start cache.
+    /// Format: [active_len (u32)] [t_fista (f64)] [iteration (u32)] [baseline (f64)]
+    /// [tau_rise (f64)] [tau_decay (f64)] [lambda (f64)] [fs (f64)]
+    /// [kernel_len (u32)] [kernel_offset (u32)] [Σh (f64)] [Σh² (f64)]
+    /// [solution f32...] [solution_prev f32...]
+    pub fn export_state(&self) -> Vec<u8> {
+        let n = self.active_len;
+        let mut buf = Vec::with_capacity(state_byte_len(n));
 
         buf.extend_from_slice(&(n as u32).to_le_bytes());
         buf.extend_from_slice(&self.t_fista.to_le_bytes());
         buf.extend_from_slice(&self.iteration.to_le_bytes());
         buf.extend_from_slice(&self.baseline.to_le_bytes());
+        buf.extend_from_slice(&self.tau_rise.to_le_bytes());
+        buf.extend_from_slice(&self.tau_decay.to_le_bytes());
+        buf.extend_from_slice(&self.lambda.to_le_bytes());
+        buf.extend_from_slice(&self.fs.to_le_bytes());
+        let (kernel_len, kernel_offset, kernel_sum, kernel_sq) = self.kernel_fingerprint();
+        buf.extend_from_slice(&kernel_len.to_le_bytes());
+        buf.extend_from_slice(&kernel_offset.to_le_bytes());
+        buf.extend_from_slice(&kernel_sum.to_le_bytes());
+        buf.extend_from_slice(&kernel_sq.to_le_bytes());
 
         for i in 0..n {
             buf.extend_from_slice(&self.solution[i].to_le_bytes());
@@ -383,36 +1719,320 @@ impl Solver {
                 self.banded
                     .convolve_forward(&self.solution[..n], &mut self.reconvolution[..n]);
             }
-            ConvMode::Fft if self.fft.fft_len() > 0 => {
+            ConvMode::Fft => {
+                // Same engine as step_batch; set_trace/set_conv_mode/rebuild_kernel
+                // keep its plans sized for the active length and current kernel.
                 self.fft
                     .convolve_forward(&self.solution[..n], n, &mut self.reconvolution[..n]);
             }
-            _ => {
-                // Fallback to time-domain convolution for very small cases
-                let k_len = self.kernel.len();
-                for t in 0..n {
-                    let mut sum = 0.0;
-                    let k_max = k_len.min(t + 1);
-                    for k in 0..k_max {
-                        sum += self.kernel[k] * self.solution[t - k];
-                    }
-                    self.reconvolution[t] = sum;
-                }
-            }
         }
+        self.apply_gain_to_reconvolution();
 
         // Recompute baseline at current solution for display alignment.
         // In step_batch, baseline is skipped when filtered (cancels in gradient),
         // but the display path always needs it to align fit with trace.
-        let raw = compute_raw_baseline(&self.trace[..n], &self.reconvolution[..n], n);
+        let raw = if let Some(b) = self.fixed_baseline {
+            b
+        } else if self.tv_baseline_active() {
+            self.estimate_baseline_tv()
+        } else {
+            self.estimate_baseline()
+        };
         self.update_baseline_ema(raw);
 
         self.reconvolution_stale = false;
     }
 
+    /// L1 weight used by the proximal step: the effective lambda, or zero when a
+    /// fixed spike support turns the problem into NNLS.
+    pub(crate) fn l1_weight(&self) -> f64 {
+        if self.support_active {
+            0.0
+        } else {
+            self.get_effective_lambda()
+        }
+    }
+
+    /// The trace being fit over the active region (see `set_use_filtered_trace`).
+    pub(crate) fn active_trace(&self) -> &[f32] {
+        let n = self.active_len;
+        if self.use_filtered_trace {
+            &self.trace[..n]
+        } else {
+            &self.raw_trace[self.window_start..self.window_start + n]
+        }
+    }
+
+    /// Whether baseline estimation can be skipped: the fitted trace has had its
+    /// DC / floor removed by apply_filter (HP) or subtract_baseline.
+    pub(crate) fn baseline_removed(&self) -> bool {
+        self.filtered && self.use_filtered_trace
+    }
+
+    /// Objective (1/2)||K*x + b - y||^2 + lambda*G_dc*sum(map_i*|x_i|) at the current
+    /// solution, assuming `reconvolution` holds K*x. With a TV baseline, b is the
+    /// series and `tv_lambda*sum|b[t+1] - b[t]|` is added. Under `DataTerm::Poisson`
+    /// the data term is Σ(μ - y·ln μ) instead.
+    pub(crate) fn compute_objective(&self) -> f64 {
+        let n = self.active_len;
+        let trace = self.active_trace();
+        if self.data_term == DataTerm::Poisson {
+            let tv = self.tv_baseline_active();
+            let mut nll = 0.0_f64;
+            let mut l1 = 0.0_f64;
+            let mut tv_sum = 0.0_f64;
+            for i in 0..n {
+                let b = if tv {
+                    self.baseline_series[i] as f64
+                } else {
+                    self.baseline
+                };
+                let mu = (self.reconvolution[i] as f64 + b).max(MIN_POISSON_RATE);
+                nll += mu - trace[i] as f64 * mu.ln();
+                l1 += (self.lambda_map[i] * self.solution[i].abs()) as f64;
+            }
+            if tv {
+                for w in self.baseline_series[..n].windows(2) {
+                    tv_sum += (w[1] - w[0]).abs() as f64;
+                }
+            }
+            return self.poisson_scale() * nll
+                + self.l1_weight() * self.gain * l1
+                + self.baseline_tv_lambda * tv_sum;
+        }
+        let mut sse = 0.0_f64;
+        let mut l1 = 0.0_f64;
+        let mut tv = 0.0_f64;
+        if self.tv_baseline_active() {
+            let series = &self.baseline_series[..n];
+            for i in 0..n {
+                let r = (self.reconvolution[i] + series[i] - trace[i]) as f64;
+                sse += self.residual_weight(r) * r * r;
+                l1 += (self.lambda_map[i] * self.solution[i].abs()) as f64;
+            }
+            for w in series.windows(2) {
+                tv += (w[1] - w[0]).abs() as f64;
+            }
+        } else {
+            let b = self.baseline as f32;
+            for i in 0..n {
+                let r = (self.reconvolution[i] + b - trace[i]) as f64;
+                sse += self.residual_weight(r) * r * r;
+                l1 += (self.lambda_map[i] * self.solution[i].abs()) as f64;
+            }
+        }
+        0.5 * sse + self.l1_weight() * self.gain * l1 + self.baseline_tv_lambda * tv
+    }
+
+    /// Raw baseline estimate from the current `reconvolution` buffer, using the
+    /// configured `BaselineMode` and applying the non-negativity clamp when enabled.
+    pub(crate) fn estimate_baseline(&mut self) -> f64 {
+        let n = self.active_len;
+        let trace = if self.use_filtered_trace {
+            &self.trace[..n]
+        } else {
+            &self.raw_trace[self.window_start..self.window_start + n]
+        };
+        self.baseline_nonfinite = 0;
+        let raw = match self.baseline_mode {
+            BaselineMode::Mean if self.asymmetry != 0.0 => {
+                baseline::residual_expectile(trace, &self.reconvolution[..n], self.asymmetry)
+            }
+            BaselineMode::Mean => {
+                let (mean, skipped) = compute_raw_baseline(trace, &self.reconvolution[..n], n);
+                self.baseline_nonfinite = skipped as u32;
+                mean
+            }
+            BaselineMode::Percentile(q) => baseline::residual_quantile(
+                trace,
+                &self.reconvolution[..n],
+                &mut self.baseline_scratch,
+                q,
+            ),
+        };
+        if self.baseline_nonneg {
+            raw.max(0.0)
+        } else {
+            raw
+        }
+    }
+
+    /// Factor c on the Poisson NLL: the mean trace value (floored at
+    /// `MIN_POISSON_RATE`). Near μ ≈ mean(y) the scaled NLL matches ½||r||², so a
+    /// lambda means about the same under either data term.
+    pub(crate) fn poisson_scale(&self) -> f64 {
+        let n = self.active_len;
+        let sum: f64 = self.active_trace().iter().map(|&y| y as f64).sum();
+        (sum / n.max(1) as f64).max(MIN_POISSON_RATE)
+    }
+
+    /// Asymmetric data-term weight for residual `r = fit - trace`: `1 - asymmetry`
+    /// where the fit is above the data (dips), `1 + asymmetry` where below.
+    pub(crate) fn residual_weight(&self, r: f64) -> f64 {
+        if r > 0.0 {
+            1.0 - self.asymmetry
+        } else {
+            1.0 + self.asymmetry
+        }
+    }
+
+    /// Gain minimizing the objective for the unscaled `reconvolution` (K*y) and
+    /// the current baseline — least squares against the trace, less the L1 term
+    /// `lambda·G_dc·g·Σ map_i·y_i` — floored at `MIN_GAIN`. Keeps the previous gain
+    /// while K*y is zero (e.g. the first iteration of a cold start).
+    pub(crate) fn estimate_gain_ls(&self) -> f64 {
+        let n = self.active_len;
+        let trace = self.active_trace();
+        let tv = self.tv_baseline_active();
+        let mut num = 0.0_f64;
+        let mut den = 0.0_f64;
+        for i in 0..n {
+            let b = if tv {
+                self.baseline_series[i] as f64
+            } else {
+                self.baseline
+            };
+            let r = self.reconvolution[i] as f64;
+            num += r * (trace[i] as f64 - b);
+            den += r * r;
+        }
+        let l1: f64 = (0..n)
+            .map(|i| (self.lambda_map[i] * self.solution_prev[i].abs()) as f64)
+            .sum();
+        num -= self.l1_weight() * l1;
+        if den > 0.0 {
+            (num / den).max(MIN_GAIN)
+        } else {
+            self.gain
+        }
+    }
+
+    /// Move the scale of y_k into the gain: divide the iterates (and K*y_k in
+    /// `reconvolution`) by their largest value c and multiply g by c. The
+    /// objective is unchanged — the fit sees g*K*s and the L1 term g*s — but the
+    /// otherwise free amplitude/gain split is pinned at max(s) = 1.
+    pub(crate) fn normalize_gain_scale(&mut self) {
+        let n = self.active_len;
+        let c = self.solution_prev[..n]
+            .iter()
+            .fold(0.0_f32, |m, &v| m.max(v));
+        if c <= 0.0 || c == 1.0 {
+            return;
+        }
+        let inv = 1.0 / c;
+        for v in &mut self.solution[..n] {
+            *v *= inv;
+        }
+        for v in &mut self.solution_prev[..n] {
+            *v *= inv;
+        }
+        for r in &mut self.reconvolution[..n] {
+            *r *= inv;
+        }
+        self.gain *= c as f64;
+    }
+
+    /// Scale a freshly computed K*s in `reconvolution` by the gain.
+    pub(crate) fn apply_gain_to_reconvolution(&mut self) {
+        if self.gain != 1.0 {
+            let g = self.gain as f32;
+            for r in &mut self.reconvolution[..self.active_len] {
+                *r *= g;
+            }
+        }
+    }
+
+    /// Multiply the iterates by `factor` (kept within [0, 1] under `Box01`) and
+    /// restart convergence checks. Ignored unless `factor` is finite and positive.
+    pub(crate) fn rescale_solution(&mut self, factor: f64) {
+        if !(factor.is_finite() && factor > 0.0) {
+            return;
+        }
+        let n = self.active_len;
+        let f = factor as f32;
+        for v in self.solution[..n]
+            .iter_mut()
+            .chain(self.solution_prev[..n].iter_mut())
+        {
+            *v = self.constraint.project(*v * f);
+        }
+        self.converged = false;
+        self.prev_objective = f64::INFINITY;
+        self.reconvolution_stale = true;
+    }
+
+    /// (length, offset, Σh, Σh²) of the built kernel, stored by `export_state` so
+    /// `load_state` can reject a solution fit under any other kernel.
+    fn kernel_fingerprint(&self) -> (u32, u32, f64, f64) {
+        let sum = self.kernel.iter().map(|&k| k as f64).sum();
+        let sq = self.kernel.iter().map(|&k| k as f64 * k as f64).sum();
+        (self.kernel.len() as u32, self.kernel_offset as u32, sum, sq)
+    }
+
+    /// Histogram of `scale * solution[i]` over the nonzero active samples, shared
+    /// by `amplitude_histogram` and `amplitude_histogram_df`.
+    fn histogram_scaled(&self, n_bins: usize, max_amp: f32, scale: f32) -> Vec<u32> {
+        if n_bins == 0 || !(max_amp.is_finite() && max_amp > 0.0) {
+            return Vec::new();
+        }
+        let mut counts = vec![0_u32; n_bins];
+        let bins_per_unit = n_bins as f32 / max_amp;
+        for &v in &self.solution[..self.active_len] {
+            if v.abs() <= SPIKE_EPS {
+                continue;
+            }
+            let amp = v * scale;
+            if (0.0..=max_amp).contains(&amp) {
+                counts[((amp * bins_per_unit) as usize).min(n_bins - 1)] += 1;
+            }
+        }
+        counts
+    }
+
+    /// Whether the per-sample TV baseline replaces the scalar baseline (never
+    /// when the baseline is fixed).
+    pub(crate) fn tv_baseline_active(&self) -> bool {
+        self.baseline_tv_lambda > 0.0 && self.fixed_baseline.is_none()
+    }
+
+    /// TV-regularized baseline series from the current `reconvolution` buffer: the
+    /// exact minimizer over b[t] for the current spikes, written to `baseline_series`
+    /// (clamped at 0 with `baseline_nonneg`). Returns its mean as the scalar summary.
+    pub(crate) fn estimate_baseline_tv(&mut self) -> f64 {
+        let n = self.active_len;
+        if n == 0 {
+            return 0.0;
+        }
+        let trace = if self.use_filtered_trace {
+            &self.trace[..n]
+        } else {
+            &self.raw_trace[self.window_start..self.window_start + n]
+        };
+        if self.baseline_scratch.len() < n {
+            self.baseline_scratch.resize(n, 0.0);
+        }
+        for i in 0..n {
+            self.baseline_scratch[i] = trace[i] - self.reconvolution[i];
+        }
+        let series = &mut self.baseline_series[..n];
+        baseline::tv_denoise(&self.baseline_scratch[..n], self.baseline_tv_lambda, series);
+        if self.baseline_nonneg {
+            for b in series.iter_mut() {
+                *b = b.max(0.0);
+            }
+        }
+        series.iter().map(|&b| b as f64).sum::<f64>() / n as f64
+    }
+
     /// Update the baseline EMA from a raw baseline estimate.
     /// Called by both `step_batch` (per-iteration) and `compute_reconvolution` (lazy display path).
+    /// A non-finite estimate keeps the previous baseline, so the baseline stays finite.
     fn update_baseline_ema(&mut self, raw_baseline: f64) {
+        let raw_baseline = if raw_baseline.is_finite() {
+            raw_baseline
+        } else {
+            self.baseline
+        };
         self.baseline = raw_baseline;
         if !self.baseline_ema_init {
             self.baseline_ema = raw_baseline;
@@ -448,10 +2068,23 @@ impl Solver {
     /// estimation must still run.
     pub fn apply_filter(&mut self) -> bool {
         let n = self.active_len;
+        // apply() caches the spectrum of its input; if an earlier step (e.g.
+        // median_filter) changed the trace, cache the raw-trace spectrum first
+        let raw = &self.raw_trace[self.window_start..self.window_start + n];
+        if n >= 8 && self.bandpass.get_power_spectrum(n).is_empty() && self.trace[..n] != *raw {
+            self.bandpass.compute_spectrum_only(raw);
+        }
         let applied = self.bandpass.apply(&mut self.trace[..n]);
         if applied && self.bandpass.is_hp_enabled() {
             self.filtered = true;
         }
+        if applied {
+            let [f_hp, f_lp] = self.bandpass.get_cutoffs();
+            self.preprocessing.push(PreprocessStep::Filter {
+                hp: self.bandpass.is_hp_enabled().then_some(f_hp),
+                lp: self.bandpass.is_lp_enabled().then_some(f_lp),
+            });
+        }
         applied
     }
 
@@ -466,30 +2099,193 @@ impl Solver {
         if n == 0 {
             return;
         }
-        let window = baseline::baseline_window(self.tau_decay, self.fs);
-        baseline::subtract_rolling_baseline(&mut self.trace[..n], window, 0.2);
-        self.filtered = true;
+        let window = baseline::baseline_window(self.decay_envelope(), self.fs);
+        baseline::subtract_rolling_baseline(&mut self.trace[..n], window, 0.2);
+        self.filtered = true;
+        self.preprocessing
+            .push(PreprocessStep::SubtractBaseline { window });
+    }
+
+    /// Sliding median of odd `window` (>= 3) over the active trace, in place, to
+    /// remove single-sample artifacts (e.g. hot pixels) before solving. Outliers
+    /// shorter than `window / 2 + 1` samples are removed; transients lasting longer
+    /// survive with their rise and decay intact. Preserves DC, so baseline
+    /// estimation still runs. Windows are truncated at the trace edges.
+    ///
+    /// Returns false and leaves the trace unchanged if `window` is even or < 3.
+    pub fn median_filter(&mut self, window: usize) -> bool {
+        if window < 3 || window % 2 == 0 {
+            return false;
+        }
+        let n = self.active_len;
+        baseline::rolling_median(&mut self.trace[..n], window);
+        self.preprocessing
+            .push(PreprocessStep::MedianFilter { window });
+        true
+    }
+
+    /// In-place preprocessing applied to the active trace since `set_trace` (or
+    /// `set_window`), in order, with the parameters used — e.g.
+    /// `["median_filter(window=3)", "bandpass(hp=0.0531 Hz, lp=3.9789 Hz)"]`.
+    /// A provenance record for the UI and exported fixtures; steps that were
+    /// skipped (filter disabled, invalid window) are not listed.
+    pub fn preprocessing_log(&self) -> Vec<String> {
+        self.preprocessing
+            .iter()
+            .map(PreprocessStep::label)
+            .collect()
+    }
+
+    /// Solution for an isolated unit spike under the current settings, to expose
+    /// artifacts of the chosen filter and lambda (e.g. HP ringing split into
+    /// several events). Builds an `n`-sample trace holding one kernel response
+    /// with its spike at `n / 2`, loads it like `set_trace`, replays the
+    /// preprocessing applied to the previous trace (`preprocessing_log`), and
+    /// solves to convergence (at most `IMPULSE_MAX_ITERATIONS`). Replaces the
+    /// loaded trace, like `solve`. Returns the solution; ideally 1 at `n / 2`.
+    pub fn pipeline_impulse_response(&mut self, n: usize) -> Vec<f32> {
+        let steps = self.preprocessing.clone();
+        let spike = n / 2;
+        let mut trace = vec![0.0_f32; n];
+        for (j, &k) in self.kernel.iter().enumerate() {
+            if let Some(i) = (spike + j).checked_sub(self.kernel_offset) {
+                if i < n {
+                    trace[i] += k;
+                }
+            }
+        }
+        self.set_trace(&trace);
+        for step in steps {
+            match step {
+                PreprocessStep::Filter { .. } => {
+                    self.apply_filter();
+                }
+                PreprocessStep::SubtractBaseline { .. } => self.subtract_baseline(),
+                PreprocessStep::MedianFilter { window } => {
+                    self.median_filter(window);
+                }
+            }
+        }
+        self.step_until_converged(IMPULSE_MAX_ITERATIONS);
+        self.get_solution()
+    }
+
+    /// Compute the raw-trace power spectrum eagerly in `set_trace`, so it is cached
+    /// before any filtering and UI filter toggles never trigger another FFT.
+    pub fn set_eager_spectrum(&mut self, enabled: bool) {
+        self.eager_spectrum = enabled;
+    }
+
+    /// Get the power spectrum of the current trace (N/2+1 bins).
+    ///
+    /// Cached once per `set_trace` (by `apply_filter` or eager computation) and
+    /// returned without recomputation while the trace is unchanged. Computed from
+    /// the raw trace, so filtering and baseline subtraction don't affect it.
+    pub fn get_power_spectrum(&mut self) -> Vec<f32> {
+        let n = self.active_len;
+        if n < 8 {
+            return Vec::new();
+        }
+        // If power spectrum is not already cached from apply(), compute it
+        let spectrum = self.bandpass.get_power_spectrum(n);
+        if spectrum.is_empty() {
+            self.bandpass
+                .compute_spectrum_only(&self.raw_trace[self.window_start..self.window_start + n]);
+            self.bandpass.get_power_spectrum(n).to_vec()
+        } else {
+            spectrum.to_vec()
+        }
+    }
+
+    /// Get frequency axis in Hz for the spectrum bins.
+    pub fn get_spectrum_frequencies(&self) -> Vec<f32> {
+        self.bandpass.get_spectrum_frequencies(self.active_len)
+    }
+
+    /// Power spectrum with its frequency axis in one call, interleaved as
+    /// `[freq0, power0, freq1, power1, ...]` from one consistent trace length
+    /// (same values as `get_spectrum_frequencies` / `get_power_spectrum`).
+    /// Empty when no spectrum is available (fewer than 8 samples).
+    pub fn get_spectrum(&mut self) -> Vec<f32> {
+        let n = self.active_len;
+        let power = self.get_power_spectrum();
+        let freqs = self.bandpass.get_spectrum_frequencies(n);
+        let mut out = Vec::with_capacity(2 * power.len());
+        for (&f, &p) in freqs.iter().zip(&power) {
+            out.push(f);
+            out.push(p);
+        }
+        out
+    }
+
+    /// Power spectrum rebinned onto `n_bins` log-spaced frequency bins from the
+    /// first non-DC bin to Nyquist (power averaged within each bin), for plots
+    /// where the low HP and high LP cutoffs both need room. Pair with
+    /// `get_spectrum_log_frequencies(n_bins)`. Empty without a spectrum.
+    pub fn get_power_spectrum_log(&mut self, n_bins: usize) -> Vec<f32> {
+        let power = self.get_power_spectrum();
+        if power.is_empty() {
+            return power;
+        }
+        filter::log_rebin(&power, self.fs as f32 / self.active_len as f32, n_bins)
+    }
+
+    /// Geometric center frequency (Hz) of each `get_power_spectrum_log` bin.
+    pub fn get_spectrum_log_frequencies(&self, n_bins: usize) -> Vec<f32> {
+        let n = self.active_len;
+        if n < 8 {
+            return Vec::new();
+        }
+        filter::log_bin_centers(n / 2 + 1, self.fs as f32 / n as f32, n_bins)
+    }
+
+    /// Select the window applied before the power-spectrum FFT (default
+    /// `Rectangular`). Power is divided by the window's squared coherent gain so
+    /// levels stay comparable across windows. The cached spectrum is dropped and
+    /// recomputed from the raw trace on the next request. Filtering is unaffected.
+    pub fn set_spectrum_window(&mut self, window: SpectrumWindow) {
+        if self.bandpass.set_spectrum_window(window) && self.eager_spectrum {
+            let n = self.active_len;
+            self.bandpass
+                .compute_spectrum_only(&self.raw_trace[self.window_start..self.window_start + n]);
+        }
+    }
+
+    /// Equivalent noise bandwidth (Hz) of the active spectrum window over the
+    /// active length n: `fs·Σw²/(Σw)²`, which is `fs/n` for `Rectangular` and
+    /// about `1.5·fs/n` for `Hann`. Converts `get_power_spectrum` values P[k]
+    /// (|FFT|² over the squared coherent gain) into a power spectral density in
+    /// trace units²/Hz: two-sided `PSD[k] = P[k] / (n²·ENBW)`, doubled for the
+    /// one-sided density at bins other than DC and Nyquist. 0 when no spectrum is
+    /// available (n < 8).
+    pub fn get_spectrum_enbw(&self) -> f32 {
+        let n = self.active_len;
+        if n < 8 {
+            return 0.0;
+        }
+        self.bandpass.enbw(n)
     }
 
-    /// Get the power spectrum of the current trace (N/2+1 bins).
-    pub fn get_power_spectrum(&mut self) -> Vec<f32> {
+    /// Frequency (Hz) of the strongest non-DC bin of the raw-trace power spectrum,
+    /// e.g. to spot a mains line or decide whether filtering is needed. Uses the
+    /// cached spectrum when available; returns 0.0 for traces shorter than 8 samples.
+    pub fn get_dominant_frequency(&mut self) -> f32 {
         let n = self.active_len;
         if n < 8 {
-            return Vec::new();
+            return 0.0;
+        }
+        if self.bandpass.get_power_spectrum(n).is_empty() {
+            self.bandpass
+                .compute_spectrum_only(&self.raw_trace[self.window_start..self.window_start + n]);
         }
-        // If power spectrum is not already cached from apply(), compute it
         let spectrum = self.bandpass.get_power_spectrum(n);
-        if spectrum.is_empty() {
-            self.bandpass.compute_spectrum_only(&self.trace[..n]);
-            self.bandpass.get_power_spectrum(n).to_vec()
-        } else {
-            spectrum.to_vec()
+        let mut peak = 1;
+        for (i, &p) in spectrum.iter().enumerate().skip(2) {
+            if p > spectrum[peak] {
+                peak = i;
+            }
         }
-    }
-
-    /// Get frequency axis in Hz for the spectrum bins.
-    pub fn get_spectrum_frequencies(&self) -> Vec<f32> {
-        self.bandpass.get_spectrum_frequencies(self.active_len)
+        self.bandpass.get_spectrum_frequencies(n)[peak]
     }
 
     /// Get filter cutoff frequencies as [f_hp, f_lp].
@@ -497,15 +2293,55 @@ impl Solver {
         self.bandpass.get_cutoffs().to_vec()
     }
 
-    /// Load warm-start state. If state is empty or wrong size, performs cold-start (zero solution).
-    pub fn load_state(&mut self, state: &[u8]) {
+    /// Group delay of the bandpass filter in samples, for aligning spikes with
+    /// external timestamps. 0 for the FFT gain-curve filter, which is zero-phase
+    /// by construction; nonzero only for a filter mode that shifts timing.
+    pub fn get_filter_phase_delay(&self) -> f32 {
+        self.bandpass.phase_delay()
+    }
+
+    /// Use Gaussian transition bands of standard deviation `sigma_hz` (see
+    /// `TaperShape::Gaussian`). Non-positive or non-finite sigmas are ignored.
+    pub fn set_filter_taper_gaussian(&mut self, sigma_hz: f32) {
+        if sigma_hz.is_finite() && sigma_hz > 0.0 {
+            self.set_filter_taper(TaperShape::Gaussian { sigma_hz });
+        }
+    }
+
+    /// Restore the default raised-cosine transition bands.
+    pub fn set_filter_taper_cosine(&mut self) {
+        self.set_filter_taper(TaperShape::Cosine);
+    }
+
+    /// Set the raised-cosine transition half-widths as fractions of the HP and LP
+    /// cutoffs (default 0.5 each: the taper spans `f·(1 ± fraction)`). A narrower
+    /// LP taper keeps more of the signal band; a wider HP taper eases the roll-off
+    /// near DC. Both must be in (0, 1); returns false and keeps the current widths
+    /// otherwise. Takes effect on the next `apply_filter`.
+    pub fn set_filter_taper_fraction(&mut self, hp: f32, lp: f32) -> bool {
+        self.bandpass.set_taper_fraction(hp, lp)
+    }
+
+    /// Whether the band derived from the current taus is usable for the enabled
+    /// filter mode (positive cutoffs; f_hp < f_lp with both HP and LP on). When
+    /// false, `apply_filter` is a no-op — see `get_filter_cutoffs` for why.
+    pub fn filter_is_valid(&self) -> bool {
+        self.bandpass.is_valid()
+    }
+
+    /// Load warm-start state. If state is empty, wrong size, or was exported under a
+    /// different kernel (tau_rise, tau_decay, or fs, or a kernel fingerprint —
+    /// length, offset, sum, and energy — that differs, which catches onset delay,
+    /// smoothing, bi-exponential decay, tail taper, and custom kernels), performs
+    /// cold-start (zero solution) and returns false. A different lambda is accepted: the saved
+    /// solution is still consistent with the kernel, which is what a λ sweep reuses.
+    pub fn load_state(&mut self, state: &[u8]) -> bool {
         if state.is_empty() {
-            return; // cold start -- solution already zeroed by set_trace
+            return false; // cold start -- solution already zeroed by set_trace
         }
 
-        // Header: active_len (u32) + t_fista (f64) + iteration (u32) + baseline (f64) = 24 bytes
-        if state.len() < 24 {
-            return; // too small, cold start
+        if state.len() < STATE_HEADER_LEN {
+            return false; // too small, cold start
         }
 
         let mut cur = Cursor::new(state);
@@ -514,14 +2350,36 @@ impl Solver {
         let expected_size = state_byte_len(saved_len);
 
         if state.len() != expected_size || saved_len != self.active_len {
-            return; // size mismatch, cold start
+            return false; // size mismatch, cold start
+        }
+
+        let t_fista = read_f64_le(&mut cur);
+        let iteration = read_u32_le(&mut cur);
+        let baseline = read_f64_le(&mut cur);
+        let tau_rise = read_f64_le(&mut cur);
+        let tau_decay = read_f64_le(&mut cur);
+        let _lambda = read_f64_le(&mut cur);
+        let fs = read_f64_le(&mut cur);
+        let fingerprint = (
+            read_u32_le(&mut cur),
+            read_u32_le(&mut cur),
+            read_f64_le(&mut cur),
+            read_f64_le(&mut cur),
+        );
+        if tau_rise != self.tau_rise
+            || tau_decay != self.tau_decay
+            || fs != self.fs
+            || fingerprint != self.kernel_fingerprint()
+        {
+            return false; // solution was fit under another kernel, cold start
         }
 
-        self.t_fista = read_f64_le(&mut cur);
-        self.iteration = read_u32_le(&mut cur);
-        self.baseline = read_f64_le(&mut cur);
+        self.t_fista = t_fista;
+        self.iteration = iteration;
+        self.warm_start_iters = Some(0);
+        self.baseline = baseline;
         self.converged = false;
-        self.prev_objective = f64::INFINITY;
+        self.reset_status_tracking();
 
         for i in 0..saved_len {
             self.solution[i] = read_f32_le(&mut cur);
@@ -529,21 +2387,113 @@ impl Solver {
         for i in 0..saved_len {
             self.solution_prev[i] = read_f32_le(&mut cur);
         }
+
+        // Objective of the restored solution: the reference for the first
+        // ObjectiveChange check, so the criterion is meaningful from iteration 1
+        self.convolve_solution();
+        self.prev_objective = self.compute_objective();
+        self.reconvolution_stale = true;
+        true
+    }
+}
+
+// Rust-only API: data-carrying enums and shared handles cannot cross the
+// wasm-bindgen boundary.
+impl Solver {
+    /// Shared cancel flag for stopping a `step_batch` from another thread; storing
+    /// `true` is equivalent to `request_cancel`.
+    pub fn cancel_handle(&self) -> Arc<AtomicBool> {
+        Arc::clone(&self.cancel_flag)
+    }
+
+    /// Select how the baseline is estimated during iteration. Default `Mean`.
+    pub fn set_baseline_mode(&mut self, mode: BaselineMode) {
+        self.baseline_mode = mode;
+        self.reconvolution_stale = true;
+    }
+
+    pub fn baseline_mode(&self) -> BaselineMode {
+        self.baseline_mode
+    }
+
+    /// Select the bandpass transition-band shape. Default `Cosine`. Takes effect on
+    /// the next `apply_filter`.
+    pub fn set_filter_taper(&mut self, shape: TaperShape) {
+        self.bandpass.set_taper_shape(shape);
+    }
+
+    pub fn filter_taper(&self) -> TaperShape {
+        self.bandpass.taper_shape()
+    }
+}
+
+// Native-only borrowed views, e.g. for benchmark harnesses that time the solver
+// core without the copies the wasm getters make (see `get_kernel` for why those
+// must own their data).
+#[cfg(not(target_arch = "wasm32"))]
+impl Solver {
+    /// Active-region solution, borrowed (`get_solution` without the copy).
+    pub fn solution_slice(&self) -> &[f32] {
+        &self.solution[..self.active_len]
+    }
+
+    /// Active-region reconvolution K*s, borrowed; computed lazily if stale, like
+    /// `get_reconvolution`.
+    pub fn reconvolution_slice(&mut self) -> &[f32] {
+        if self.reconvolution_stale {
+            self.compute_reconvolution();
+        }
+        &self.reconvolution[..self.active_len]
+    }
+
+    /// Active-region working trace after any filtering, borrowed
+    /// (`get_trace_filtered` without the copy).
+    pub fn trace_slice(&self) -> &[f32] {
+        &self.trace[..self.active_len]
+    }
+
+    /// Active-region trace as loaded, borrowed (`get_trace_raw` without the copy).
+    pub fn raw_trace_slice(&self) -> &[f32] {
+        &self.raw_trace[self.window_start..self.window_start + self.active_len]
     }
 }
 
+/// Smallest gain `estimate_gain_ls` returns: keeps g positive, so a trace that
+/// anti-correlates with the fit cannot flip the kernel or blow up the step 1/(L·g²).
+const MIN_GAIN: f64 = 1e-3;
+
+/// Iteration cap for the solve in `pipeline_impulse_response`.
+const IMPULSE_MAX_ITERATIONS: u32 = 10_000;
+
 /// Compute the mean residual (trace - reconvolution) as the raw baseline estimate.
-pub(crate) fn compute_raw_baseline(trace: &[f32], reconvolution: &[f32], n: usize) -> f64 {
+/// Non-finite terms are skipped so one bad sample cannot poison the baseline;
+/// returns the mean over the rest (0 if none) and the number skipped.
+pub(crate) fn compute_raw_baseline(trace: &[f32], reconvolution: &[f32], n: usize) -> (f64, usize) {
     let mut sum = 0.0_f64;
+    let mut skipped = 0;
     for i in 0..n {
-        sum += (trace[i] - reconvolution[i]) as f64;
+        let r = trace[i] - reconvolution[i];
+        if r.is_finite() {
+            sum += r as f64;
+        } else {
+            skipped += 1;
+        }
+    }
+    if skipped == n {
+        (0.0, skipped)
+    } else {
+        (sum / (n - skipped) as f64, skipped)
     }
-    sum / n as f64
 }
 
+/// Serialized state header: active_len (u32) + t_fista (f64) + iteration (u32) +
+/// baseline (f64) + tau_rise, tau_decay, lambda, fs (4×f64) + kernel fingerprint
+/// (2×u32 + 2×f64).
+const STATE_HEADER_LEN: usize = 4 + 8 + 4 + 8 + 4 * 8 + 2 * 4 + 2 * 8;
+
 /// Byte length of serialized solver state for a trace of length `n`.
 fn state_byte_len(n: usize) -> usize {
-    4 + 8 + 4 + 8 + 2 * n * 4 // u32 + f64 + u32 + f64 + 2×n×f32
+    STATE_HEADER_LEN + 2 * n * 4 // header + 2×n×f32
 }
 
 // --- Little-endian cursor read helpers ---
@@ -568,3 +2518,1557 @@ fn read_f64_le(cur: &mut Cursor<&[u8]>) -> f64 {
     cur.read_exact(&mut buf).unwrap();
     f64::from_le_bytes(buf)
 }
+
+/// Decode standard-alphabet base64 (RFC 4648), with or without `=` padding,
+/// skipping ASCII whitespace. `None` on any other character, misplaced padding,
+/// or a dangling single trailing symbol.
+fn decode_base64(text: &str) -> Option<Vec<u8>> {
+    fn sextet(c: u8) -> Option<u32> {
+        match c {
+            b'A'..=b'Z' => Some((c - b'A') as u32),
+            b'a'..=b'z' => Some((c - b'a') as u32 + 26),
+            b'0'..=b'9' => Some((c - b'0') as u32 + 52),
+            b'+' => Some(62),
+            b'/' => Some(63),
+            _ => None,
+        }
+    }
+
+    let symbols: Vec<u8> = text.bytes().filter(|c| !c.is_ascii_whitespace()).collect();
+    let data_len = symbols.len() - symbols.iter().rev().take_while(|&&c| c == b'=').count();
+    let padding = symbols.len() - data_len;
+    if padding > 2 || (padding > 0 && symbols.len() % 4 != 0) || data_len % 4 == 1 {
+        return None;
+    }
+
+    let mut out = Vec::with_capacity(data_len * 3 / 4);
+    for chunk in symbols[..data_len].chunks(4) {
+        let mut acc = 0u32;
+        for &c in chunk {
+            acc = (acc << 6) | sextet(c)?;
+        }
+        acc <<= 6 * (4 - chunk.len()) as u32;
+        let bytes = acc.to_be_bytes();
+        out.extend_from_slice(&bytes[1..chunk.len()]);
+    }
+    Some(out)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::kernel::{build_biexp_kernel, build_kernel, compute_lipschitz};
+    use crate::Solver;
+
+    /// Add `amp * kernel` at each `(index, amp)`, cut at the end of the trace.
+    fn add_spikes(trace: &mut [f32], kernel: &[f32], spikes: &[(usize, f32)]) {
+        let n = trace.len();
+        for &(s, amp) in spikes {
+            for (k, &kv) in kernel.iter().enumerate().take(n.saturating_sub(s)) {
+                trace[s + k] += amp * kv;
+            }
+        }
+    }
+
+    /// Add uniform noise in [-amp/2, amp/2) from a fixed-seed LCG.
+    fn add_noise(trace: &mut [f32], amp: f32, seed: u32) {
+        let mut state = seed;
+        for v in trace.iter_mut() {
+            state = state.wrapping_mul(1_664_525).wrapping_add(1_013_904_223);
+            *v += amp * ((state >> 8) as f32 / (1u32 << 24) as f32 - 0.5);
+        }
+    }
+
+    #[test]
+    fn apply_indicator_preset_sets_taus_and_keeps_lambda() {
+        let mut solver = Solver::new();
+        solver.set_params(0.02, 0.4, 0.05, 30.0);
+
+        assert!(solver.apply_indicator_preset("GCaMP6s", 60.0));
+        assert_eq!(solver.tau_rise, 0.4);
+        assert_eq!(solver.tau_decay, 1.8);
+        assert_eq!(solver.lambda, 0.05);
+        assert_eq!(solver.fs, 60.0);
+        assert_eq!(solver.get_kernel(), build_kernel(0.4, 1.8, 60.0));
+
+        assert!(!solver.apply_indicator_preset("unknown", 30.0));
+        assert_eq!(solver.fs, 60.0, "unknown preset must not change params");
+    }
+
+    #[test]
+    fn percentile_baseline_ignores_unmodeled_transients() {
+        use crate::BaselineMode;
+
+        // Offset 1.0 with slow positive bumps the kernel cannot explain under a huge
+        // lambda (solution stays at zero, so the baseline sees the raw trace).
+        let trace: Vec<f32> = (0..400)
+            .map(|i| if (i / 50) % 2 == 1 { 3.0 } else { 1.0 })
+            .collect();
+
+        let mut solver = Solver::new();
+        solver.set_params(0.02, 0.4, 1e3, 30.0);
+        solver.set_trace(&trace);
+        solver.step_batch(10);
+        assert!(
+            (solver.baseline - 2.0).abs() < 1e-3,
+            "mean {}",
+            solver.baseline
+        );
+
+        solver.set_baseline_percentile(0.2);
+        assert_eq!(solver.baseline_mode(), BaselineMode::Percentile(0.2));
+        solver.set_trace(&trace);
+        solver.step_batch(10);
+        assert!(
+            (solver.baseline - 1.0).abs() < 1e-6,
+            "p20 {}",
+            solver.baseline
+        );
+        assert!((solver.get_baseline() - 1.0).abs() < 1e-6);
+
+        solver.set_baseline_mean();
+        assert_eq!(solver.baseline_mode(), BaselineMode::Mean);
+    }
+
+    #[test]
+    fn power_spectrum_cached_per_trace() {
+        let trace_a: Vec<f32> = (0..256).map(|i| (i as f32 * 0.3).sin() + 1.0).collect();
+        let trace_b: Vec<f32> = (0..256).map(|i| (i as f32 * 0.05).cos()).collect();
+
+        let mut lazy = Solver::new();
+        lazy.set_trace(&trace_a);
+        let raw_a = lazy.get_power_spectrum();
+        assert_eq!(raw_a.len(), 129);
+
+        let mut eager = Solver::new();
+        eager.set_params(0.02, 0.4, 0.01, 30.0);
+        eager.set_eager_spectrum(true);
+        eager.set_trace(&trace_a);
+        assert_eq!(eager.bandpass.get_power_spectrum(256), &raw_a[..]);
+
+        // Filtering keeps the raw-trace spectrum, even when applied repeatedly
+        eager.set_filter_enabled(true);
+        assert!(eager.apply_filter());
+        assert!(eager.apply_filter());
+        assert_eq!(eager.get_power_spectrum(), raw_a);
+
+        // ... and when an earlier step already changed the trace
+        let mut median = Solver::new();
+        median.set_params(0.02, 0.4, 0.01, 30.0);
+        median.set_trace(&trace_a);
+        assert!(median.median_filter(3));
+        median.set_filter_enabled(true);
+        assert!(median.apply_filter());
+        assert_eq!(median.get_power_spectrum(), raw_a);
+
+        // A new trace of the same length must not reuse the old spectrum
+        eager.set_trace(&trace_b);
+        lazy.set_trace(&trace_b);
+        let raw_b = lazy.get_power_spectrum();
+        assert_ne!(raw_b, raw_a);
+        assert_eq!(eager.get_power_spectrum(), raw_b);
+    }
+
+    #[test]
+    fn spectrum_interleaves_frequency_and_power() {
+        let trace: Vec<f32> = (0..64).map(|i| (i as f32 * 0.4).sin()).collect();
+        let mut solver = Solver::new();
+        solver.set_trace(&trace);
+        let spectrum = solver.get_spectrum();
+        let power = solver.get_power_spectrum();
+        let freqs = solver.get_spectrum_frequencies();
+        assert_eq!(spectrum.len(), 2 * power.len());
+        for i in 0..power.len() {
+            assert_eq!(spectrum[2 * i], freqs[i]);
+            assert_eq!(spectrum[2 * i + 1], power[i]);
+        }
+        solver.set_trace(&[1.0; 4]);
+        assert!(solver.get_spectrum().is_empty());
+    }
+
+    #[test]
+    fn log_spectrum_matches_axis() {
+        let trace: Vec<f32> = (0..300).map(|i| (i as f32 * 0.9).sin()).collect();
+        let mut solver = Solver::new();
+        solver.set_trace(&trace);
+        let power = solver.get_power_spectrum_log(20);
+        let freqs = solver.get_spectrum_log_frequencies(20);
+        assert_eq!(power.len(), 20);
+        assert_eq!(freqs.len(), 20);
+        assert!(freqs.windows(2).all(|w| w[1] > w[0]));
+        assert!(freqs[0] > 0.1 && freqs[19] < 15.0);
+        // Peak bin holds the 0.9 rad/sample line: 0.9·30/2π ≈ 4.3 Hz
+        let peak = (0..20)
+            .max_by(|&a, &b| power[a].total_cmp(&power[b]))
+            .unwrap();
+        assert!((freqs[peak] - 4.3).abs() < 1.0, "{}", freqs[peak]);
+
+        solver.set_trace(&[0.0; 4]);
+        assert!(solver.get_power_spectrum_log(20).is_empty());
+        assert!(solver.get_spectrum_log_frequencies(20).is_empty());
+    }
+
+    #[test]
+    fn dominant_frequency_finds_mains_line() {
+        // 30 Hz sampling, 300 samples: bins are 0.1 Hz apart
+        let fs = 30.0_f32;
+        let trace: Vec<f32> = (0..300)
+            .map(|i| {
+                let t = i as f32 / fs;
+                5.0 + 0.2 * (std::f32::consts::TAU * 0.5 * t).sin()
+                    + (std::f32::consts::TAU * 12.0 * t).sin()
+            })
+            .collect();
+        let mut solver = Solver::new();
+        solver.set_params(0.02, 0.4, 0.01, fs as f64);
+        solver.set_trace(&trace);
+        // The large DC offset is ignored
+        assert!((solver.get_dominant_frequency() - 12.0).abs() < 1e-3);
+
+        solver.set_trace(&[1.0; 4]);
+        assert_eq!(solver.get_dominant_frequency(), 0.0);
+    }
+
+    #[test]
+    fn set_solution_seeds_warm_start() {
+        let kernel = build_kernel(0.02, 0.4, 30.0);
+        let mut trace = vec![0.0_f32; 150];
+        add_spikes(&mut trace, &kernel, &[(30, 1.0)]);
+        let mut cold = Solver::new();
+        let solution = cold.solve(&trace, 100, 10);
+
+        let mut warm = Solver::new();
+        warm.set_trace(&trace);
+        let mut seed = solution.clone();
+        seed[0] = -2.0;
+        seed.push(7.0); // past the active length
+        warm.set_solution(&seed);
+        assert_eq!(warm.solution[0], 0.0, "negative values are clamped");
+        assert_eq!(warm.solution[..150], warm.solution_prev[..150]);
+        assert_eq!(warm.t_fista, 1.0);
+        assert!(!warm.converged());
+
+        warm.step_batch(10);
+        assert!(
+            warm.converged(),
+            "seeded solver should converge immediately"
+        );
+
+        // A short seed zero-fills the rest
+        warm.set_solution(&[1.0, 2.0]);
+        assert_eq!(warm.solution[..3], [1.0, 2.0, 0.0]);
+        assert!(warm.solution[2..150].iter().all(|&v| v == 0.0));
+    }
+
+    #[test]
+    fn forward_convolve_matches_reconvolution() {
+        let mut solver = Solver::new();
+        solver.set_params(0.02, 0.4, 0.01, 30.0);
+        let trace: Vec<f32> = (0..120)
+            .map(|i| if i % 40 == 10 { 1.0 } else { 0.0 })
+            .collect();
+        solver.set_trace(&trace);
+
+        let mut spikes = vec![0.0_f32; 120];
+        spikes[10] = 1.0;
+        let predicted = solver.forward_convolve(&spikes);
+        let kernel = solver.get_kernel();
+        for (i, &k) in kernel.iter().enumerate().take(120 - 10) {
+            assert!((predicted[10 + i] - k).abs() < 1e-5, "index {}", i);
+        }
+        assert!(predicted[..10].iter().all(|&v| v.abs() < 1e-5));
+
+        // Same result as the fit's own reconvolution for the solution
+        solver.step_batch(20);
+        let solution = solver.get_solution();
+        let recon = solver.get_reconvolution();
+        let predicted = solver.forward_convolve(&solution);
+        for (a, b) in predicted.iter().zip(&recon) {
+            assert!((a - b).abs() < 1e-5);
+        }
+        // Short input is zero-padded
+        assert_eq!(solver.forward_convolve(&[]), vec![0.0; 120]);
+    }
+
+    #[test]
+    fn get_results_concatenates_solution_and_fit() {
+        let mut solver = Solver::new();
+        let trace: Vec<f32> = (0..90)
+            .map(|i| if i % 30 == 3 { 1.0 } else { 0.2 })
+            .collect();
+        solver.set_trace(&trace);
+        solver.step_batch(15);
+        let results = solver.get_results();
+        assert_eq!(results.len(), 180);
+        assert_eq!(results[..90], solver.get_solution()[..]);
+        assert_eq!(results[90..], solver.get_reconvolution_with_baseline()[..]);
+    }
+
+    #[test]
+    fn demo_trace_is_deterministic_and_matches_spikes() {
+        let mut solver = Solver::new();
+        let trace = solver.generate_demo_trace(300, 6, 0.0, 42);
+        assert_eq!(trace, solver.generate_demo_trace(300, 6, 0.0, 42));
+        assert_ne!(trace, solver.generate_demo_trace(300, 6, 0.0, 43));
+
+        let spikes = solver.generate_demo_spikes(300, 6, 42);
+        assert_eq!(spikes.iter().filter(|&&s| s == 1.0).count(), 6);
+        // Noise-free demo trace is exactly the spikes through the kernel
+        solver.set_trace(&trace);
+        let predicted = solver.forward_convolve(&spikes);
+        for (a, b) in trace.iter().zip(&predicted) {
+            assert!((a - b).abs() < 1e-4);
+        }
+
+        // Seeded noise
+        let noisy = solver.generate_demo_trace(300, 6, 0.1, 42);
+        assert_eq!(noisy, solver.generate_demo_trace(300, 6, 0.1, 42));
+        let std = (noisy
+            .iter()
+            .zip(&trace)
+            .map(|(a, b)| (a - b).powi(2))
+            .sum::<f32>()
+            / 300.0)
+            .sqrt();
+        assert!((std - 0.1).abs() < 0.02, "noise std {}", std);
+    }
+
+    #[test]
+    fn fft_len_follows_padding() {
+        let mut solver = Solver::new();
+        assert_eq!(solver.get_fft_len(), 0);
+        let k_len = solver.get_kernel().len();
+        solver.set_trace(&vec![0.0; 1000]);
+        let linear = 1000 + k_len - 1;
+        assert_eq!(solver.get_fft_len(), linear.next_power_of_two());
+
+        solver.set_fft_padding(crate::FftPadding::FastSize);
+        let fast = solver.get_fft_len();
+        assert!(fast >= linear && fast <= linear.next_power_of_two());
+    }
+
+    #[test]
+    fn empty_traces_interleave_with_real_ones() {
+        let trace: Vec<f32> = (0..120)
+            .map(|i| if i % 40 == 5 { 1.0 } else { 0.1 })
+            .collect();
+        let mut reference = Solver::new();
+        reference.set_params(0.05, 0.8, 0.01, 30.0);
+        let expected = reference.solve(&trace, 200, 10);
+
+        let mut solver = Solver::new();
+        solver.set_filter_enabled(true);
+        solver.set_eager_spectrum(true);
+        solver.set_trace(&trace);
+        solver.step_batch(10);
+        for round in 0..3 {
+            solver.set_trace(&[]);
+            assert!(!solver.converged());
+            assert_eq!(solver.iteration_count(), 0);
+            assert!(solver.step_batch(10));
+            assert_eq!(solver.iteration_count(), 0);
+            assert_eq!(solver.get_status(), crate::ConvergenceStatus::EmptyTrace);
+            assert!(!solver.apply_filter());
+            solver.subtract_baseline();
+            // Kernel changes while empty must reach the FFT engine
+            solver.set_params(0.05, 0.8 + 0.1 * round as f64, 0.01, 30.0);
+            assert!(solver.get_solution().is_empty());
+            assert!(solver.get_results().is_empty());
+            assert!(solver.get_reconvolution_full().is_empty());
+            assert!(solver.get_power_spectrum().is_empty());
+            assert_eq!(solver.get_dominant_frequency(), 0.0);
+            assert_eq!(solver.get_residual_std(), 0.0);
+            assert_eq!(solver.get_snr(), 0.0);
+            assert!(solver.detect_merged_events(0.0).is_empty());
+            assert!(solver.event_variance_explained(0.0).is_empty());
+            assert!(solver.load_state(&solver.export_state()));
+            assert!(solver.check_adjoint().is_nan());
+        }
+        solver.set_filter_enabled(false);
+        solver.set_params(0.05, 0.8, 0.01, 30.0);
+        let solution = solver.solve(&trace, 200, 10);
+        assert_eq!(solution, expected);
+    }
+
+    #[test]
+    fn baseline_series_matches_fit_offset() {
+        let trace: Vec<f32> = (0..200)
+            .map(|i| if i % 50 == 10 { 1.0 } else { 0.0 } + if i < 100 { 0.5 } else { 1.5 })
+            .collect();
+        let mut solver = Solver::new();
+        solver.solve(&trace, 100, 10);
+        let series = solver.get_baseline_series();
+        assert_eq!(series, vec![solver.get_baseline() as f32; 200]);
+
+        solver.set_baseline_tv_lambda(0.5);
+        solver.solve(&trace, 200, 10);
+        let series = solver.get_baseline_series();
+        assert!(
+            series[150] - series[50] > 0.5,
+            "{} vs {}",
+            series[50],
+            series[150]
+        );
+        let recon = solver.get_reconvolution();
+        let with_baseline = solver.get_reconvolution_with_baseline();
+        for i in 0..200 {
+            assert!((with_baseline[i] - recon[i] - series[i]).abs() < 1e-5);
+        }
+    }
+
+    #[test]
+    fn reset_solution_reproduces_cold_solve() {
+        let trace: Vec<f32> = (0..150)
+            .map(|i| if i % 50 == 7 { 1.0 } else { 0.2 })
+            .collect();
+        let mut solver = Solver::new();
+        let cold = solver.solve(&trace, 100, 10);
+        let iterations = solver.iteration_count();
+        let fft_len = solver.get_fft_len();
+
+        solver.reset_solution();
+        assert_eq!(solver.iteration_count(), 0);
+        assert!(!solver.converged());
+        assert_eq!(solver.baseline, 0.0);
+        assert!(solver.get_solution().iter().all(|&v| v == 0.0));
+        assert_eq!(solver.get_trace(), trace);
+        assert_eq!(solver.get_fft_len(), fft_len);
+
+        while !solver.step_batch(10) {}
+        assert_eq!(solver.get_solution(), cold);
+        assert_eq!(solver.iteration_count(), iterations);
+    }
+
+    #[test]
+    fn window_matches_solving_the_slice_directly() {
+        let trace: Vec<f32> = (0..600)
+            .map(|i| {
+                if i % 97 == 10 {
+                    3.0
+                } else {
+                    0.1 * ((i as f32) * 0.3).sin()
+                }
+            })
+            .collect();
+        let run = |s: &mut Solver| {
+            s.step_batch(300);
+            (s.get_solution(), s.get_baseline())
+        };
+
+        let mut direct = Solver::new();
+        direct.set_params(0.02, 0.4, 0.05, 30.0);
+        direct.set_trace(&trace[150..400]);
+        let (want, want_b) = run(&mut direct);
+
+        let mut windowed = Solver::new();
+        windowed.set_params(0.02, 0.4, 0.05, 30.0);
+        windowed.set_trace(&trace);
+        assert_eq!(windowed.active_length(), 600);
+        assert!(windowed.set_window(150, 250));
+        assert_eq!(windowed.get_window_start(), 150);
+        assert_eq!(windowed.active_length(), 250);
+        assert_eq!(windowed.get_trace_raw(), trace[150..400].to_vec());
+        let (got, got_b) = run(&mut windowed);
+        assert_eq!(got.len(), 250);
+        for (a, b) in got.iter().zip(&want) {
+            assert!((a - b).abs() < 1e-5, "{a} vs {b}");
+        }
+        assert!((got_b - want_b).abs() < 1e-5);
+
+        // Out-of-range windows are rejected and leave the window alone
+        assert!(!windowed.set_window(500, 101));
+        assert!(!windowed.set_window(usize::MAX, 2));
+        assert_eq!(windowed.get_window_start(), 150);
+        assert_eq!(windowed.active_length(), 250);
+        assert_eq!(windowed.get_solution().len(), 250);
+
+        // The full trace is still resident
+        assert!(windowed.set_window(0, 600));
+        assert_eq!(windowed.get_trace_raw(), trace);
+    }
+
+    #[test]
+    fn kernel_too_long_for_trace_tracks_params_and_trace() {
+        let mut solver = Solver::new();
+        assert!(!solver.kernel_too_long_for_trace());
+
+        solver.set_params(0.02, 0.4, 0.01, 30.0);
+        solver.set_trace(&[0.0; 200]);
+        assert!(solver.get_kernel().len() < 200);
+        assert!(!solver.kernel_too_long_for_trace());
+
+        // Long decay: the kernel outgrows the trace
+        solver.set_params(0.02, 20.0, 0.01, 30.0);
+        assert!(solver.get_kernel().len() > 200);
+        assert!(solver.kernel_too_long_for_trace());
+
+        // A long enough trace clears the warning
+        solver.set_trace(&vec![0.0; solver.get_kernel().len() + 1]);
+        assert!(!solver.kernel_too_long_for_trace());
+    }
+
+    #[test]
+    fn median_filter_removes_single_sample_outliers() {
+        let mut trace: Vec<f32> = (0..200).map(|i| 0.05 * ((i as f32) * 0.7).sin()).collect();
+        // Genuine transient: fast rise, slow decay over many samples
+        for i in 100..140 {
+            trace[i] += 2.0 * (-((i - 100) as f32) / 10.0).exp();
+        }
+        let mut dirty = trace.clone();
+        for &i in &[20, 55, 170] {
+            dirty[i] += 10.0;
+        }
+
+        let mut solver = Solver::new();
+        solver.set_trace(&dirty);
+        assert!(!solver.median_filter(4));
+        assert!(!solver.median_filter(1));
+        assert_eq!(solver.get_trace_filtered(), dirty);
+
+        assert!(solver.median_filter(3));
+        let cleaned = solver.get_trace_filtered();
+        for &i in &[20, 55, 170] {
+            assert!(
+                cleaned[i].abs() < 0.2,
+                "outlier at {i} survived: {}",
+                cleaned[i]
+            );
+        }
+        // The transient keeps its peak (within one sample's decay) and its tail
+        assert!(cleaned[101] > 1.7, "peak {}", cleaned[101]);
+        assert!((cleaned[120] - trace[120]).abs() < 0.1);
+        // The raw trace is untouched
+        assert_eq!(solver.get_trace_raw(), dirty);
+    }
+
+    #[test]
+    fn fixed_baseline_is_used_instead_of_estimated() {
+        let mut spikes = vec![0.0_f32; 300];
+        for &i in &[30, 120, 210] {
+            spikes[i] = 1.0;
+        }
+        let mut solver = Solver::new();
+        solver.set_params(0.02, 0.4, 0.01, 30.0);
+        solver.set_trace(&spikes);
+        let clean = solver.forward_convolve(&spikes);
+        let trace: Vec<f32> = clean.iter().map(|v| v + 0.5).collect();
+
+        solver.set_trace(&trace);
+        assert!(!solver.baseline_is_fixed());
+        assert!(!solver.set_fixed_baseline(f64::NAN));
+        assert!(solver.set_fixed_baseline(0.5));
+        assert!(solver.baseline_is_fixed());
+        solver.step_batch(500);
+        assert_eq!(solver.baseline, 0.5);
+        assert_eq!(solver.get_baseline(), 0.5);
+        assert!(solver.get_baseline_series().iter().all(|&b| b == 0.5));
+
+        // Pinned runs are reproducible and survive a cold restart
+        let first = solver.get_solution();
+        solver.reset_solution();
+        assert_eq!(solver.get_baseline(), 0.5);
+        solver.step_batch(500);
+        assert_eq!(solver.get_solution(), first);
+
+        // A wrong pin is honoured too: the spikes absorb the offset
+        solver.set_fixed_baseline(0.0);
+        solver.step_batch(500);
+        assert_eq!(solver.get_baseline(), 0.0);
+        let absorbed: f32 = solver.get_solution().iter().sum();
+        assert!(absorbed > first.iter().sum::<f32>() + 1.0);
+
+        solver.clear_fixed_baseline();
+        assert!(!solver.baseline_is_fixed());
+        solver.reset_solution();
+        solver.step_batch(500);
+        assert!((solver.get_baseline() - 0.5).abs() < 0.05);
+    }
+
+    #[test]
+    fn oversampled_kernel_interleaves_the_solver_kernel() {
+        let mut solver = Solver::new();
+        solver.set_params(0.05, 0.3, 0.01, 10.0);
+        let kernel = solver.get_kernel();
+        assert_eq!(solver.get_kernel_oversampled(1), kernel);
+        assert!(solver.get_kernel_oversampled(0).is_empty());
+
+        let fine = solver.get_kernel_oversampled(4);
+        assert!(fine.len() >= 4 * (kernel.len() - 1));
+        // Every 4th fine sample is a coarse sample, and the peak stays at 1
+        for (i, &k) in kernel.iter().enumerate().take(fine.len() / 4) {
+            assert!(
+                (fine[4 * i] - k).abs() < 0.02,
+                "i={i}: {} vs {k}",
+                fine[4 * i]
+            );
+        }
+        let peak = fine.iter().cloned().fold(0.0_f32, f32::max);
+        assert!((peak - 1.0).abs() < 1e-6);
+
+        // Display only: the solving kernel is untouched
+        assert_eq!(solver.get_kernel(), kernel);
+    }
+
+    #[test]
+    fn preprocessing_log_records_applied_steps() {
+        let trace: Vec<f32> = (0..400).map(|i| 1.0 + ((i as f32) * 0.2).sin()).collect();
+        let mut solver = Solver::new();
+        solver.set_trace(&trace);
+        assert!(solver.preprocessing_log().is_empty());
+
+        // Skipped steps are not recorded
+        assert!(!solver.median_filter(2));
+        solver.set_filter_enabled(false);
+        assert!(!solver.apply_filter());
+        assert!(solver.preprocessing_log().is_empty());
+
+        assert!(solver.median_filter(5));
+        solver.set_filter_enabled(true);
+        assert!(solver.apply_filter());
+        solver.subtract_baseline();
+        let log = solver.preprocessing_log();
+        assert_eq!(log.len(), 3);
+        assert_eq!(log[0], "median_filter(window=5)");
+        assert!(log[1].starts_with("bandpass(hp="), "{}", log[1]);
+        assert!(
+            log[2].starts_with("subtract_baseline(window="),
+            "{}",
+            log[2]
+        );
+
+        solver.set_lp_filter_enabled(false);
+        assert!(solver.apply_filter());
+        assert!(solver.preprocessing_log()[3].starts_with("highpass(hp="));
+
+        // A new trace starts a fresh record
+        solver.set_trace(&trace);
+        assert!(solver.preprocessing_log().is_empty());
+    }
+
+    #[test]
+    fn solution_in_spikes_counts_calibrated_events() {
+        // Doublet and single AP, each AP a transient of peak 0.25 ΔF/F
+        let mut spikes = vec![0.0_f32; 300];
+        spikes[50] = 0.25;
+        spikes[200] = 0.5;
+        let mut solver = Solver::new();
+        solver.set_params(0.02, 0.4, 0.001, 30.0);
+        solver.set_trace(&spikes);
+        let trace = solver.forward_convolve(&spikes);
+        solver.set_trace(&trace);
+        solver.step_until_converged(20_000);
+
+        assert_eq!(solver.get_solution_in_spikes(), solver.get_solution());
+        assert!(!solver.set_single_spike_amplitude(0.0));
+        assert!(!solver.set_single_spike_amplitude(f32::NAN));
+        assert!(solver.set_single_spike_amplitude(0.25));
+
+        let counts = solver.get_solution_in_spikes();
+        let single: f32 = counts[45..60].iter().sum();
+        let doublet: f32 = counts[195..210].iter().sum();
+        assert!((single - 1.0).abs() < 0.1, "single {single}");
+        assert!((doublet - 2.0).abs() < 0.2, "doublet {doublet}");
+    }
+
+    #[test]
+    fn native_slices_match_owned_getters() {
+        let trace: Vec<f32> = (0..300)
+            .map(|i| if i % 60 == 5 { 2.0 } else { 0.1 })
+            .collect();
+        let mut solver = Solver::new();
+        solver.set_trace(&trace);
+        assert!(solver.set_window(20, 250));
+        solver.set_filter_enabled(true);
+        solver.apply_filter();
+        solver.step_batch(50);
+
+        assert_eq!(solver.solution_slice(), solver.get_solution().as_slice());
+        assert_eq!(solver.trace_slice(), solver.get_trace_filtered().as_slice());
+        assert_eq!(solver.raw_trace_slice(), &trace[20..270]);
+        let reconv = solver.get_reconvolution();
+        solver.reconvolution_stale = true;
+        assert_eq!(solver.reconvolution_slice(), reconv.as_slice());
+    }
+
+    #[test]
+    fn spectrum_enbw_converts_power_to_density() {
+        use crate::SpectrumWindow;
+
+        // Uniform white noise in [-1, 1]: variance 1/3, two-sided PSD σ²/fs
+        let n = 4096;
+        let mut trace = vec![0.0_f32; n];
+        add_noise(&mut trace, 2.0, 99);
+        let mut solver = Solver::new();
+        solver.set_params(0.02, 0.4, 0.01, 20.0);
+        assert_eq!(solver.get_spectrum_enbw(), 0.0);
+        solver.set_trace(&trace);
+
+        let df = 20.0 / n as f32;
+        assert!((solver.get_spectrum_enbw() - df).abs() < 1e-6);
+        for window in [SpectrumWindow::Rectangular, SpectrumWindow::Hann] {
+            solver.set_spectrum_window(window);
+            let enbw = solver.get_spectrum_enbw();
+            if window == SpectrumWindow::Hann {
+                assert!(
+                    (enbw / df - 1.5).abs() < 0.01,
+                    "Hann ENBW {} bins",
+                    enbw / df
+                );
+            }
+            let power = solver.get_power_spectrum();
+            let mean_psd = power[1..power.len() - 1]
+                .iter()
+                .map(|&p| p as f64 / ((n * n) as f64 * enbw as f64))
+                .sum::<f64>()
+                / (power.len() - 2) as f64;
+            let expected = (1.0 / 3.0) / 20.0;
+            assert!(
+                (mean_psd / expected - 1.0).abs() < 0.05,
+                "{window:?}: {mean_psd} vs {expected}"
+            );
+        }
+    }
+
+    #[test]
+    fn kernel_taper_is_opt_in_and_tightens_lipschitz() {
+        let mut solver = Solver::new();
+        solver.set_params(0.02, 0.4, 0.01, 30.0);
+        let plain = solver.get_kernel();
+        let plain_lipschitz = solver.lipschitz_constant;
+
+        assert!(!solver.set_kernel_taper(0.6));
+        assert!(!solver.set_kernel_taper(f64::NAN));
+        assert!(solver.set_kernel_taper(0.05));
+        let tapered = solver.get_kernel();
+        assert_eq!(tapered.len(), plain.len());
+        assert_ne!(tapered, plain);
+        assert!(solver.lipschitz_constant <= plain_lipschitz);
+
+        // Persists across set_params; 0 restores the truncated kernel
+        solver.set_params(0.02, 0.4, 0.05, 30.0);
+        assert_eq!(solver.get_kernel(), tapered);
+        assert!(solver.set_kernel_taper(0.0));
+        assert_eq!(solver.get_kernel(), plain);
+    }
+
+    #[test]
+    fn solution_prev_exposes_extrapolated_point() {
+        let trace: Vec<f32> = (0..200)
+            .map(|i| if i % 50 == 10 { 1.0 } else { 0.0 })
+            .collect();
+        let mut solver = Solver::new();
+        solver.set_trace(&trace);
+        solver.step_batch(8);
+        let y = solver.get_solution_prev();
+        assert_eq!(y.len(), 200);
+        assert_ne!(y, solver.get_solution()); // momentum has moved y_k off x_k
+        solver.reset_momentum();
+        assert_eq!(solver.get_solution_prev(), solver.get_solution());
+    }
+
+    #[test]
+    fn set_trace_base64_decodes_little_endian_f32() {
+        // [1.0, -2.5, 0.0]: 0000803F 000020C0 00000000
+        let mut solver = Solver::new();
+        assert!(solver.set_trace_base64("AACAPwAAIMAAAAAA"));
+        assert_eq!(solver.get_trace_raw(), vec![1.0, -2.5, 0.0]);
+
+        // Unpadded, padded, and wrapped forms agree: [1.0] is AACAPw==
+        assert!(solver.set_trace_base64("AACAPw"));
+        assert_eq!(solver.get_trace_raw(), vec![1.0]);
+        assert!(solver.set_trace_base64("AACA\nPw=="));
+        assert_eq!(solver.get_trace_raw(), vec![1.0]);
+        assert!(solver.set_trace_base64(""));
+        assert!(solver.get_trace_raw().is_empty());
+
+        // Invalid text or a partial sample leaves the previous trace loaded
+        solver.set_trace(&[4.0, 5.0]);
+        assert!(!solver.set_trace_base64("AACAP$=="));
+        assert!(!solver.set_trace_base64("AACA")); // 3 bytes
+        assert!(!solver.set_trace_base64("AACAPw==="));
+        assert!(!solver.set_trace_base64("AACAP"));
+        assert_eq!(solver.get_trace_raw(), vec![4.0, 5.0]);
+    }
+
+    #[test]
+    fn fft_op_count_tracks_cached_work() {
+        use crate::ConvMode;
+
+        let trace: Vec<f32> = (0..200)
+            .map(|i| if i % 40 == 5 { 1.0 } else { 0.0 })
+            .collect();
+        let mut solver = Solver::new();
+        solver.set_conv_mode(ConvMode::Fft);
+        solver.set_params(0.02, 0.4, 0.01, 30.0);
+        solver.set_trace(&trace);
+        solver.step_batch(10);
+        assert!(solver.get_fft_op_count() > 0);
+
+        // Lambda-only change: no kernel spectrum rebuild
+        solver.reset_fft_op_count();
+        assert_eq!(solver.get_fft_op_count(), 0);
+        solver.set_params(0.02, 0.4, 0.05, 30.0);
+        assert_eq!(solver.get_fft_op_count(), 0);
+
+        // Reconvolution is computed once (forward + inverse), then cached
+        solver.step_batch(1);
+        solver.reset_fft_op_count();
+        let first = solver.get_reconvolution();
+        assert_eq!(solver.get_fft_op_count(), 2);
+        assert_eq!(solver.get_reconvolution(), first);
+        assert_eq!(solver.get_fft_op_count(), 2);
+
+        // The bandpass filter counts too
+        solver.set_filter_enabled(true);
+        solver.reset_fft_op_count();
+        assert!(solver.apply_filter());
+        assert_eq!(solver.get_fft_op_count(), 2);
+        let filtered = solver.get_trace();
+
+        // Reloading the same trace reuses the cached filter result
+        solver.set_trace(&trace);
+        solver.reset_fft_op_count();
+        assert!(solver.apply_filter());
+        assert_eq!(solver.get_fft_op_count(), 0);
+        assert_eq!(solver.get_trace(), filtered);
+        assert!(!solver.get_power_spectrum().is_empty());
+        assert_eq!(solver.get_fft_op_count(), 0);
+    }
+
+    #[test]
+    fn zero_area_custom_kernel_keeps_l1_penalty() {
+        use crate::{ConvMode, LambdaScale};
+
+        // Fast positive lobe minus a slow negative one with the same area
+        let fast = build_kernel(0.02, 0.2, 30.0);
+        let slow = build_kernel(0.05, 1.0, 30.0);
+        let k_len = slow.len();
+        let fast_area: f32 = fast.iter().sum();
+        let slow_area: f32 = slow.iter().sum();
+        let kernel: Vec<f32> = (0..k_len)
+            .map(|i| fast.get(i).copied().unwrap_or(0.0) - slow[i] * fast_area / slow_area)
+            .collect();
+
+        let n = 300;
+        let mut trace = vec![0.0_f32; n];
+        add_spikes(&mut trace, &kernel, &[(40, 1.0), (160, 1.0)]);
+        add_noise(&mut trace, 0.05, 11);
+
+        let mut solver = Solver::new();
+        solver.set_conv_mode(ConvMode::Fft);
+        solver.set_params(0.02, 0.4, 0.02, 30.0);
+        assert_eq!(solver.get_lambda_scale(), LambdaScale::DcGain);
+        assert!(solver.set_kernel(&kernel));
+        assert_eq!(solver.get_kernel(), kernel);
+        assert!(solver.get_kernel_dc_gain().abs() < 1e-3);
+        assert_eq!(solver.get_lambda_scale(), LambdaScale::L1Norm);
+        assert!((solver.get_effective_lambda() - 0.02 * solver.get_kernel_l1()).abs() < 1e-9);
+
+        solver.set_trace(&trace);
+        solver.step_until_converged(5000);
+        let active = solver.count_spikes(0.05);
+        assert!((2..=6).contains(&active), "{} active samples", active);
+        let spikes = solver.get_spike_indices(0.5);
+        assert!(
+            spikes.contains(&40) && spikes.contains(&160),
+            "{:?}",
+            spikes
+        );
+
+        // Invalid kernels are rejected; set_params returns to the tau model
+        assert!(!solver.set_kernel(&[]));
+        assert!(!solver.set_kernel(&[0.0, 0.0]));
+        assert!(!solver.set_kernel(&[1.0, f32::NAN]));
+        assert_eq!(solver.get_kernel(), kernel);
+        solver.set_params(0.02, 0.4, 0.02, 30.0);
+        assert_eq!(solver.get_kernel(), build_kernel(0.02, 0.4, 30.0));
+        assert_eq!(solver.get_lambda_scale(), LambdaScale::DcGain);
+    }
+
+    #[test]
+    fn pipeline_impulse_response_replays_preprocessing() {
+        let mut solver = Solver::new();
+        solver.set_params(0.02, 0.4, 0.01, 30.0);
+        solver.set_trace(&[0.0; 50]);
+        let plain = solver.pipeline_impulse_response(300);
+        assert_eq!(plain.len(), 300);
+        let peak = (0..300)
+            .max_by(|&a, &b| plain[a].total_cmp(&plain[b]))
+            .unwrap();
+        assert_eq!(peak, 150);
+        assert!(plain[150] > 0.8, "peak {}", plain[150]);
+        assert_eq!(solver.get_trace_raw().len(), 300);
+        assert!(solver.preprocessing_log().is_empty());
+
+        // Steps applied to the previous trace run on the impulse too
+        solver.set_trace(&[0.0; 50]);
+        assert!(solver.median_filter(3));
+        solver.set_filter_enabled(true);
+        assert!(solver.apply_filter());
+        let filtered = solver.pipeline_impulse_response(300);
+        assert_eq!(filtered.len(), 300);
+        let log = solver.preprocessing_log();
+        assert_eq!(log.len(), 2);
+        assert_eq!(log[0], "median_filter(window=3)");
+        assert!(log[1].starts_with("bandpass("), "{:?}", log);
+        assert!(filtered[148..=152].iter().sum::<f32>() > 0.5);
+    }
+
+    #[test]
+    fn rescale_on_kernel_change_speeds_reconvergence() {
+        // Dense firing: overlapping transients, so the fit is set by their area
+        let kernel = build_kernel(0.02, 0.5, 30.0);
+        let n = 600;
+        let mut trace = vec![0.2_f32; n];
+        let mut state: u32 = 3;
+        for s in 0..n {
+            state = state.wrapping_mul(1_664_525).wrapping_add(1_013_904_223);
+            if state >> 24 < 64 {
+                add_spikes(&mut trace, &kernel, &[(s, 1.0)]);
+            }
+        }
+        // Converge with tau_decay 0.4, nudge it to 0.5, count the refit iterations
+        let refit = |rescale: bool| {
+            let mut solver = Solver::new();
+            solver.set_params(0.02, 0.4, 0.01, 30.0);
+            solver.set_trace(&trace);
+            assert!(solver.step_until_converged(5000));
+            let before = solver.get_solution();
+            let old_dc = solver.get_kernel_dc_gain();
+
+            solver.set_rescale_on_kernel_change(rescale);
+            solver.set_params(0.02, 0.5, 0.01, 30.0);
+            let ratio = (old_dc / solver.get_kernel_dc_gain()) as f32;
+            if rescale {
+                for (a, b) in solver.get_solution().iter().zip(&before) {
+                    assert!((a - b * ratio).abs() < 1e-6);
+                }
+            } else {
+                assert_eq!(solver.get_solution(), before);
+            }
+            solver.reset_momentum();
+            solver.converged = false;
+            let start = solver.iteration_count();
+            assert!(solver.step_until_converged(5000));
+            solver.iteration_count() - start
+        };
+        let (plain, rescaled) = (refit(false), refit(true));
+        assert!(rescaled < plain, "rescaled {rescaled} vs plain {plain}");
+    }
+
+    #[test]
+    fn amplitude_histogram_bins_nonzero_values() {
+        let mut solver = Solver::new();
+        solver.set_trace(&[0.0; 8]);
+        solver.solution[..8].copy_from_slice(&[0.0, 0.1, 0.3, 0.3, 0.99, 1.0, 1.5, 1e-8]);
+        assert_eq!(solver.amplitude_histogram(4, 1.0), vec![1, 2, 0, 2]);
+        assert_eq!(solver.amplitude_histogram(1, 2.0), vec![6]);
+        assert!(solver.amplitude_histogram(0, 1.0).is_empty());
+        assert!(solver.amplitude_histogram(4, 0.0).is_empty());
+        assert!(solver.amplitude_histogram(4, f32::NAN).is_empty());
+
+        // ΔF units scale by the kernel DC gain
+        let dc = solver.get_kernel_dc_gain() as f32;
+        assert_eq!(solver.amplitude_histogram_df(4, dc), vec![1, 2, 0, 2]);
+        assert_eq!(solver.amplitude_histogram_df(4, 1.6 * dc), vec![3, 0, 2, 1]);
+    }
+
+    #[test]
+    fn fit_kernel_taus_reports_custom_kernel_kinetics() {
+        let mut solver = Solver::new();
+        solver.set_params(0.02, 0.4, 0.01, 30.0);
+        let taus = solver.fit_kernel_taus();
+        assert_eq!(taus.len(), 2);
+        assert!((taus[0] - 0.02).abs() < 0.002 && (taus[1] - 0.4).abs() < 0.01);
+
+        assert!(solver.set_kernel(&build_kernel(0.08, 1.0, 30.0)));
+        let taus = solver.fit_kernel_taus();
+        assert!((taus[0] - 0.08).abs() < 0.008 && (taus[1] - 1.0).abs() < 0.02);
+
+        assert!(solver.set_kernel(&[1.0]));
+        assert!(solver.fit_kernel_taus().is_empty());
+    }
+
+    #[test]
+    fn warm_start_from_neighbor_roi_converges_faster() {
+        let kernel = build_kernel(0.02, 0.4, 30.0);
+        let n = 600;
+        let make_trace = |scale: f32, seed: u32| {
+            let mut trace = vec![0.0_f32; n];
+            let spikes: Vec<(usize, f32)> = [40, 130, 210, 330, 400, 520]
+                .iter()
+                .enumerate()
+                .map(|(j, &s)| (s, scale * (1.0 + 0.3 * j as f32)))
+                .collect();
+            add_spikes(&mut trace, &kernel, &spikes);
+            add_noise(&mut trace, 0.05, seed);
+            trace
+        };
+        let first = make_trace(1.0, 1);
+        let second = make_trace(0.9, 2);
+
+        let mut solver = Solver::new();
+        solver.set_params(0.02, 0.4, 0.01, 30.0);
+        solver.set_trace(&first);
+        assert!(solver.step_until_converged(5000));
+        let neighbor = solver.get_solution();
+
+        solver.set_trace(&second);
+        assert!(solver.step_until_converged(5000));
+        let cold_iters = solver.iteration_count();
+
+        solver.set_trace(&second);
+        solver.warm_start_from(&neighbor);
+        assert_eq!(solver.get_solution(), neighbor);
+        assert!(solver.step_until_converged(5000));
+        let warm_iters = solver.iteration_count();
+        assert!(
+            warm_iters < cold_iters,
+            "warm {} vs cold {}",
+            warm_iters,
+            cold_iters
+        );
+
+        // Shorter vectors are zero-padded, negatives clamped
+        solver.set_trace(&second);
+        solver.warm_start_from(&[-1.0, 2.0]);
+        let seeded = solver.get_solution();
+        assert_eq!(seeded.len(), n);
+        assert_eq!(&seeded[..3], &[0.0, 2.0, 0.0]);
+    }
+
+    #[test]
+    fn nan_trace_sample_does_not_poison_baseline() {
+        let kernel = build_kernel(0.02, 0.4, 30.0);
+        let mut trace = vec![0.5_f32; 300];
+        add_spikes(&mut trace, &kernel, &[(100, 1.0)]);
+        let mut solver = Solver::new();
+        solver.set_params(0.02, 0.4, 0.01, 30.0);
+        solver.set_trace(&trace);
+        assert!(solver.step_until_converged(5000));
+        let clean = solver.get_baseline();
+        assert_eq!(solver.get_baseline_nonfinite_count(), 0);
+
+        // A bad sample appearing after the solve (the display path re-estimates)
+        solver.raw_trace[5] = f32::NAN;
+        solver.trace[5] = f32::NAN;
+        solver.reconvolution_stale = true;
+        let baseline = solver.get_baseline();
+        assert!(baseline.is_finite());
+        assert!((baseline - clean).abs() < 0.01, "{} vs {}", baseline, clean);
+        assert_eq!(solver.get_baseline_nonfinite_count(), 1);
+        assert!(solver
+            .get_reconvolution_with_baseline()
+            .iter()
+            .all(|v| v.is_finite()));
+
+        assert_eq!(
+            crate::compute_raw_baseline(&[f32::NAN, f32::INFINITY], &[0.0, 0.0], 2),
+            (0.0, 2)
+        );
+    }
+
+    #[test]
+    fn total_activity_sums_solution_and_fit_area() {
+        let kernel = build_kernel(0.02, 0.4, 30.0);
+        let n = 400;
+        let mut trace = vec![0.0_f32; n];
+        add_spikes(&mut trace, &kernel, &[(40, 1.0), (150, 2.0)]);
+        let mut solver = Solver::new();
+        solver.set_params(0.02, 0.4, 0.01, 30.0);
+        assert_eq!(solver.get_total_activity(), 0.0);
+        solver.set_trace(&trace);
+        assert!(solver.step_until_converged(5000));
+
+        let total = solver.get_total_activity();
+        let summed: f64 = solver.get_solution().iter().map(|&v| v as f64).sum();
+        assert!((total - summed).abs() < 1e-9);
+
+        let area: f64 = solver.get_reconvolution().iter().map(|&v| v as f64).sum();
+        let df = solver.get_total_activity_df();
+        assert!((df - total * solver.get_kernel_dc_gain()).abs() < 1e-9);
+        assert!((df - area).abs() < 1e-3 * area, "{} vs {}", df, area);
+    }
+
+    #[test]
+    fn longer_kernel_after_set_trace_rebuilds_fft() {
+        let trace: Vec<f32> = (0..100).map(|i| if i == 20 { 1.0 } else { 0.0 }).collect();
+
+        // Kernel grows past the padded FFT length sized for the first kernel
+        let mut solver = Solver::new();
+        solver.set_params(0.02, 0.2, 0.01, 30.0);
+        solver.set_trace(&trace);
+        solver.step_batch(5);
+        solver.set_params(0.1, 2.5, 0.01, 30.0);
+        solver.set_trace(&trace);
+        solver.step_batch(5);
+        solver.set_params(0.2, 4.0, 0.01, 30.0);
+        assert!(solver.fft.fft_len() >= 100 + solver.kernel.len() - 1);
+        solver.step_batch(20);
+
+        let reconv = solver.get_reconvolution();
+        assert_eq!(reconv.len(), 100);
+        assert!(reconv.iter().all(|v| v.is_finite()));
+    }
+
+    #[test]
+    fn check_adjoint_consistent_in_both_modes() {
+        use crate::ConvMode;
+
+        let mut solver = Solver::new();
+        assert!(solver.check_adjoint().is_nan(), "no trace loaded");
+
+        solver.set_params(0.05, 0.8, 0.01, 30.0);
+        solver.set_trace(&vec![0.0_f32; 300]);
+        let err = solver.check_adjoint();
+        assert!(err < 1e-4, "FFT adjoint rel_err {}", err);
+
+        solver.set_conv_mode(ConvMode::BandedAR2);
+        let err = solver.check_adjoint();
+        assert!(err < 1e-4, "BandedAR2 adjoint rel_err {}", err);
+    }
+
+    #[test]
+    fn reconvolution_full_keeps_tail_past_window() {
+        use crate::ConvMode;
+
+        let kernel = build_kernel(0.02, 0.4, 30.0);
+        let n = 120;
+        for mode in [ConvMode::Fft, ConvMode::BandedAR2] {
+            let mut solver = Solver::new();
+            solver.set_params(0.02, 0.4, 0.01, 30.0);
+            solver.set_conv_mode(mode);
+            solver.set_trace(&vec![0.0_f32; n]);
+            solver.solution[n - 5] = 1.0; // spike near the window end
+
+            let full = solver.get_reconvolution_full();
+            let cropped = solver.get_reconvolution();
+            assert_eq!(full.len(), n + kernel.len() - 1);
+            for i in 0..n {
+                assert!((full[i] - cropped[i]).abs() < 1e-5, "{:?} at {}", mode, i);
+            }
+            // Tail beyond the crop is the rest of the kernel response
+            let tail: f32 = full[n..].iter().sum();
+            assert!(tail > 1.0, "{:?}: tail mass {} missing", mode, tail);
+            if mode == ConvMode::Fft {
+                for k in 0..kernel.len() {
+                    assert!((full[n - 5 + k] - kernel[k]).abs() < 1e-5);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn filtering_is_non_destructive() {
+        let trace: Vec<f32> = (0..300)
+            .map(|i| 2.0 + (i as f32 * 0.02).sin() + if i % 60 == 10 { 1.0 } else { 0.0 })
+            .collect();
+
+        let mut solver = Solver::new();
+        solver.set_params(0.02, 0.4, 0.01, 30.0);
+        solver.set_trace(&trace);
+        solver.set_filter_enabled(true);
+        assert!(solver.apply_filter());
+        solver.subtract_baseline();
+
+        assert_eq!(solver.get_trace_raw(), trace);
+        let filtered = solver.get_trace_filtered();
+        assert_ne!(filtered, trace);
+        assert_eq!(solver.get_trace(), filtered);
+
+        // Solving against the raw trace re-enables baseline estimation
+        solver.set_use_filtered_trace(false);
+        assert_eq!(solver.get_trace(), trace);
+        for _ in 0..100 {
+            if solver.step_batch(10) {
+                break;
+            }
+        }
+        assert!(
+            solver.baseline > 1.0,
+            "raw-trace baseline {}",
+            solver.baseline
+        );
+        assert_eq!(solver.get_trace_filtered(), filtered);
+    }
+
+    #[test]
+    fn fit_kernel_from_autocorrelation_recovers_taus() {
+        use crate::kernel::tau_to_ar2;
+
+        // AR(2) calcium driven by sparse pseudo-random spikes
+        let (g1, g2) = tau_to_ar2(0.1, 0.8, 30.0);
+        let n = 20_000;
+        let mut state = 12345_u32;
+        let mut c = vec![0.0_f32; n];
+        for t in 0..n {
+            state ^= state << 13;
+            state ^= state >> 17;
+            state ^= state << 5;
+            let spike = if state % 20 == 0 { 1.0 } else { 0.0 };
+            let c1 = if t >= 1 { c[t - 1] as f64 } else { 0.0 };
+            let c2 = if t >= 2 { c[t - 2] as f64 } else { 0.0 };
+            c[t] = (g1 * c1 + g2 * c2 + spike) as f32;
+        }
+
+        let mut solver = Solver::new();
+        solver.set_params(0.02, 0.4, 0.05, 30.0);
+        solver.set_trace(&c);
+        let taus = solver.fit_kernel_from_autocorrelation();
+        assert_eq!(taus.len(), 2);
+        assert!((taus[0] - 0.1).abs() < 0.02, "tau_rise {}", taus[0]);
+        assert!((taus[1] - 0.8).abs() < 0.08, "tau_decay {}", taus[1]);
+        assert_eq!(solver.tau_rise, taus[0]);
+        assert_eq!(solver.tau_decay, taus[1]);
+        assert_eq!(solver.lambda, 0.05);
+
+        // Alternating trace: negative lag-1 correlation, no decaying kernel
+        let alternating: Vec<f32> = (0..200).map(|i| (i % 2) as f32).collect();
+        solver.set_trace(&alternating);
+        assert!(solver.fit_kernel_from_autocorrelation().is_empty());
+        assert_eq!(
+            solver.tau_decay, taus[1],
+            "failed fit must not change params"
+        );
+    }
+
+    #[test]
+    fn effective_lambda_scales_with_dc_gain() {
+        let mut solver = Solver::new();
+        solver.set_params(0.02, 0.4, 0.01, 30.0);
+        let dc_fast = solver.get_kernel_dc_gain();
+        let expected: f64 = build_kernel(0.02, 0.4, 30.0)
+            .iter()
+            .map(|&k| k as f64)
+            .sum();
+        assert!((dc_fast - expected).abs() < 1e-9);
+        assert!((solver.get_effective_lambda() - 0.01 * dc_fast).abs() < 1e-12);
+
+        // Same slider value, slower decay: larger area, stronger penalty
+        solver.set_params(0.02, 1.6, 0.01, 30.0);
+        assert!(solver.get_kernel_dc_gain() > 3.0 * dc_fast);
+        assert!(solver.get_effective_lambda() > 3.0 * 0.01 * dc_fast);
+    }
+
+    #[test]
+    fn count_spikes_counts_active_region_above_threshold() {
+        let mut solver = Solver::new();
+        solver.set_trace(&[0.0; 8]);
+        solver.solution[..8].copy_from_slice(&[0.0, 0.5, 0.0, 0.05, 1.2, 0.0, 0.1, 0.0]);
+        assert_eq!(solver.count_spikes(0.0), 4);
+        assert_eq!(solver.count_spikes(0.1), 2);
+        assert_eq!(solver.count_spikes(2.0), 0);
+        assert_eq!(solver.get_spike_indices(0.0), vec![1, 3, 4, 6]);
+        assert_eq!(solver.get_spike_indices(0.1), vec![1, 4]);
+        let gain = solver.get_kernel_dc_gain() as f32;
+        assert_eq!(
+            solver.get_event_df_amplitudes(0.1),
+            vec![0.5 * gain, 1.2 * gain]
+        );
+
+        // Stale samples beyond the active region are ignored
+        solver.set_trace(&[0.0; 4]);
+        solver.solution[4] = 9.0;
+        assert_eq!(solver.count_spikes(0.0), 0);
+        assert!(solver.get_spike_indices(0.0).is_empty());
+    }
+
+    #[test]
+    fn window_shorter_than_kernel_is_well_defined() {
+        use crate::ConvMode;
+
+        let kernel = build_kernel(0.02, 0.4, 30.0);
+        for mode in [ConvMode::Fft, ConvMode::BandedAR2] {
+            for n in [1_usize, 5, 20] {
+                assert!(n < kernel.len());
+                let mut trace: Vec<f32> = kernel[..n].iter().map(|&k| k + 0.3).collect();
+                trace.rotate_right(n.min(2));
+                let mut solver = Solver::new();
+                solver.set_params(0.02, 0.4, 0.001, 30.0);
+                solver.set_conv_mode(mode);
+                solver.set_trace(&trace);
+
+                let mut converged = false;
+                for _ in 0..500 {
+                    if solver.step_batch(10) {
+                        converged = true;
+                        break;
+                    }
+                }
+                assert!(converged, "{:?} n={} did not converge", mode, n);
+                let solution = solver.get_solution();
+                let reconv = solver.get_reconvolution();
+                assert_eq!(solution.len(), n);
+                assert_eq!(reconv.len(), n);
+                assert!(solution.iter().chain(&reconv).all(|v| v.is_finite()));
+                assert!(solver.get_baseline().is_finite());
+            }
+        }
+    }
+
+    #[test]
+    fn solution_sparse_interleaves_nonzeros() {
+        let mut solver = Solver::new();
+        solver.set_trace(&[0.0; 6]);
+        solver.solution[..6].copy_from_slice(&[0.0, 0.5, 1e-8, 0.0, 1.25, 0.0]);
+        assert_eq!(solver.get_solution_sparse(), vec![1.0, 0.5, 4.0, 1.25]);
+
+        solver.set_trace(&[0.0; 6]);
+        assert!(solver.get_solution_sparse().is_empty());
+    }
+
+    #[test]
+    fn lambda_only_set_params_skips_kernel_rebuild() {
+        let mut solver = Solver::new();
+        solver.set_params(0.02, 0.4, 0.01, 30.0);
+        solver.set_trace(&vec![0.5_f32; 200]);
+        let kernel = solver.get_kernel();
+        let fft_len = solver.fft.fft_len();
+
+        // Sentinels would be overwritten by any kernel rebuild
+        solver.lipschitz_constant = -1.0;
+        solver.kernel_dc_gain = -2.0;
+        solver.set_params(0.02, 0.4, 0.5, 30.0);
+        assert_eq!(solver.lambda, 0.5);
+        assert_eq!(solver.lipschitz_constant, -1.0);
+        assert_eq!(solver.kernel_dc_gain, -2.0);
+        assert_eq!(solver.get_kernel(), kernel);
+        assert_eq!(solver.fft.fft_len(), fft_len);
+
+        // Any tau change rebuilds
+        solver.set_params(0.02, 0.5, 0.5, 30.0);
+        assert!(solver.lipschitz_constant > 0.0);
+        assert!(solver.kernel_dc_gain > 0.0);
+
+        // Leaving a bi-exponential kernel with the same taus must rebuild too
+        solver.set_params_biexp(0.02, 0.5, 2.0, 0.5, 0.5, 30.0);
+        solver.set_params(0.02, 0.5, 0.5, 30.0);
+        assert_eq!(solver.get_kernel(), build_kernel(0.02, 0.5, 30.0));
+    }
+
+    #[test]
+    fn unchanged_kernel_samples_skip_derived_state() {
+        let mut solver = Solver::new();
+        solver.set_trace(&vec![0.5_f32; 200]);
+        let kernel = solver.get_kernel();
+
+        // Re-applying settings that leave the samples unchanged reuses everything
+        solver.lipschitz_constant = -1.0;
+        solver.kernel_dc_gain = -2.0;
+        solver.set_onset_delay(0.0);
+        solver.set_acausal_smoothing(0.0);
+        assert_eq!(solver.get_kernel(), kernel);
+        assert_eq!(solver.lipschitz_constant, -1.0);
+        assert_eq!(solver.kernel_dc_gain, -2.0);
+
+        // New samples refresh the derived state
+        solver.set_onset_delay(0.1);
+        assert_eq!(solver.lipschitz_constant, compute_lipschitz(&solver.kernel));
+        let dc: f64 = solver.kernel.iter().map(|&k| k as f64).sum();
+        assert_eq!(solver.kernel_dc_gain, dc);
+    }
+
+    #[test]
+    fn acausal_smoothing_recovers_unshifted_spike() {
+        let fs = 30.0;
+        let sigma_samples = 2.0;
+        let kernel = build_kernel(0.02, 0.4, fs);
+        let n = 300;
+        let mut clean = vec![0.0_f32; n];
+        add_spikes(&mut clean, &kernel, &[(100, 1.0)]);
+        // Zero-phase Gaussian smoothing, as an upstream pipeline would apply
+        let half = 8_i64;
+        let weights: Vec<f32> = (-half..=half)
+            .map(|x| (-0.5 * (x * x) as f32 / (sigma_samples * sigma_samples) as f32).exp())
+            .collect();
+        let area: f32 = weights.iter().sum();
+        let trace: Vec<f32> = (0..n as i64)
+            .map(|t| {
+                (-half..=half)
+                    .filter(|&x| (0..n as i64).contains(&(t + x)))
+                    .map(|x| clean[(t + x) as usize] * weights[(x + half) as usize] / area)
+                    .sum()
+            })
+            .collect();
+
+        let mut solver = Solver::new();
+        solver.set_params(0.02, 0.4, 0.001, fs);
+        solver.set_acausal_smoothing(sigma_samples / fs);
+        assert_eq!(solver.get_kernel_offset(), 8);
+        let solution = solver.solve(&trace, 500, 10);
+        let argmax = solution
+            .iter()
+            .enumerate()
+            .max_by(|a, b| a.1.total_cmp(b.1))
+            .unwrap()
+            .0;
+        assert_eq!(argmax, 100);
+
+        // The fit reproduces the early (pre-spike) rise of the smoothed trace
+        let reconv = solver.get_reconvolution();
+        assert!(reconv[97] > 0.01 && (reconv[97] - trace[97]).abs() < 0.02);
+        assert_eq!(
+            solver.get_reconvolution_full().len(),
+            n + solver.get_kernel().len() - 1 - 8
+        );
+
+        solver.set_acausal_smoothing(0.0);
+        assert_eq!(solver.get_kernel_offset(), 0);
+        assert_eq!(solver.get_kernel(), kernel);
+    }
+
+    #[test]
+    fn tv_baseline_tracks_step_drift() {
+        let kernel = build_kernel(0.02, 0.4, 30.0);
+        let n = 600;
+        let drift = |t: usize| if t < 300 { 0.5_f32 } else { 1.3 };
+        let mut trace: Vec<f32> = (0..n).map(drift).collect();
+        add_spikes(
+            &mut trace,
+            &kernel,
+            &[(60, 1.0), (200, 1.0), (400, 1.0), (520, 1.0)],
+        );
+
+        // Spike mass the fit invents to explain the step at t = 300
+        let spurious = |solution: &[f32]| solution[280..340].iter().sum::<f32>();
+
+        let mut solver = Solver::new();
+        solver.set_params(0.02, 0.4, 0.01, 30.0);
+        let scalar_spurious = spurious(&solver.solve(&trace, 300, 20));
+        let scalar_std = solver.get_residual_std();
+
+        solver.set_baseline_tv_lambda(2.0);
+        let tv_spurious = spurious(&solver.solve(&trace, 300, 20));
+        assert!(solver.get_residual_std() < scalar_std);
+        assert!(
+            tv_spurious < 0.1 && scalar_spurious > 0.5,
+            "TV {} vs scalar {}",
+            tv_spurious,
+            scalar_spurious
+        );
+
+        let fit = solver.get_reconvolution_with_baseline();
+        let reconv = solver.get_reconvolution();
+        for t in (0..n).filter(|t| !(290..310).contains(t)) {
+            let b = fit[t] - reconv[t];
+            assert!((b - drift(t)).abs() < 0.1, "baseline at {}: {}", t, b);
+        }
+        let mean_drift = (0..n).map(|t| drift(t) as f64).sum::<f64>() / n as f64;
+        assert!((solver.get_baseline() - mean_drift).abs() < 0.05);
+
+        // Zero disables the TV term: back to a constant offset
+        solver.set_baseline_tv_lambda(0.0);
+        solver.solve(&trace, 300, 20);
+        let fit = solver.get_reconvolution_with_baseline();
+        let reconv = solver.get_reconvolution();
+        assert!(((fit[0] - reconv[0]) - (fit[n - 1] - reconv[n - 1])).abs() < 1e-6);
+    }
+
+    #[test]
+    fn resample_updates_fs_and_kernel() {
+        let trace: Vec<f32> = (0..300).map(|i| 1.0 + (i as f32 * 0.05).sin()).collect();
+        let mut solver = Solver::new();
+        solver.set_params(0.02, 0.4, 0.01, 30.0);
+        solver.set_trace(&trace);
+
+        solver.resample(45.0);
+        assert_eq!(solver.get_trace_raw().len(), 450);
+        assert_eq!(solver.get_kernel(), build_kernel(0.02, 0.4, 45.0));
+
+        solver.resample(30.0);
+        let back = solver.get_trace_raw();
+        assert_eq!(back.len(), 300);
+        for (i, (&a, &b)) in trace.iter().zip(&back).enumerate() {
+            assert!((a - b).abs() < 5e-3, "index {}: {} vs {}", i, a, b);
+        }
+        assert_eq!(solver.iteration_count(), 0);
+
+        solver.resample(0.0);
+        assert_eq!(solver.get_trace_raw().len(), 300);
+    }
+
+    #[test]
+    fn kernel_norm_accessors() {
+        let mut solver = Solver::new();
+        solver.set_params(0.02, 0.4, 0.01, 30.0);
+        let kernel = solver.get_kernel();
+        assert!((solver.get_kernel_peak() - 1.0).abs() < 1e-6);
+        assert!((solver.get_kernel_l1() - solver.get_kernel_dc_gain()).abs() < 1e-9);
+        let l2: f64 = kernel
+            .iter()
+            .map(|&k| k as f64 * k as f64)
+            .sum::<f64>()
+            .sqrt();
+        assert!((solver.get_kernel_l2() - l2).abs() < 1e-9);
+        assert!(solver.get_kernel_l2() < solver.get_kernel_l1());
+
+        // An onset delay or slow component keeps the peak at 1.0
+        solver.set_params_biexp(0.02, 0.4, 2.0, 0.7, 0.01, 30.0);
+        solver.set_onset_delay(0.1);
+        assert!((solver.get_kernel_peak() - 1.0).abs() < 1e-6);
+    }
+
+    #[test]
+    fn load_state_rejects_kernel_mismatch() {
+        let trace: Vec<f32> = (0..120)
+            .map(|i| if i % 30 == 5 { 1.0 } else { 0.0 })
+            .collect();
+        let mut solver = Solver::new();
+        solver.set_params(0.02, 0.4, 0.01, 30.0);
+        solver.solve(&trace, 50, 10);
+        let state = solver.export_state();
+
+        // Same kernel, different lambda: restored
+        let mut other = Solver::new();
+        other.set_params(0.02, 0.4, 0.05, 30.0);
+        other.set_trace(&trace);
+        assert!(other.load_state(&state));
+        assert_eq!(other.get_solution(), solver.get_solution());
+        assert_eq!(other.iteration_count(), solver.iteration_count());
+
+        // Different tau or fs: cold start
+        for (tau_decay, fs) in [(0.5, 30.0), (0.4, 60.0)] {
+            let mut other = Solver::new();
+            other.set_params(0.02, tau_decay, 0.01, fs);
+            other.set_trace(&trace);
+            assert!(!other.load_state(&state));
+            assert_eq!(other.iteration_count(), 0);
+            assert!(other.get_solution().iter().all(|&v| v == 0.0));
+        }
+
+        // Same taus and fs, but another kernel shape: cold start
+        let reshapes: [fn(&mut Solver); 5] = [
+            |s| s.set_onset_delay(0.1),
+            |s| s.set_acausal_smoothing(0.05),
+            |s| s.set_params_biexp(0.02, 0.4, 2.0, 0.7, 0.01, 30.0),
+            |s| assert!(s.set_kernel_taper(0.2)),
+            |s| assert!(s.set_kernel(&build_kernel(0.05, 0.8, 30.0))),
+        ];
+        for reshape in reshapes {
+            let mut other = Solver::new();
+            other.set_params(0.02, 0.4, 0.01, 30.0);
+            reshape(&mut other);
+            other.set_trace(&trace);
+            assert!(!other.load_state(&state));
+            assert_eq!(other.iteration_count(), 0);
+        }
+
+        assert!(!other.load_state(&[]));
+        assert!(!other.load_state(&state[..40]));
+    }
+
+    #[test]
+    fn set_params_biexp_updates_kernel_derived_state() {
+        let mut solver = Solver::new();
+        solver.set_params_biexp(0.02, 0.3, 2.0, 0.6, 0.01, 30.0);
+
+        let kernel = build_biexp_kernel(0.02, 0.3, 2.0, 0.6, 30.0);
+        assert_eq!(solver.get_kernel(), kernel);
+        let dc: f64 = kernel.iter().map(|&k| k as f64).sum();
+        assert!((solver.kernel_dc_gain - dc).abs() < 1e-9);
+        assert_eq!(solver.lipschitz_constant, compute_lipschitz(&kernel));
+
+        // A plain set_params returns to the double-exponential kernel
+        solver.set_params(0.02, 0.3, 0.01, 30.0);
+        assert_eq!(solver.get_kernel(), build_kernel(0.02, 0.3, 30.0));
+    }
+}
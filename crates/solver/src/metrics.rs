@@ -0,0 +1,659 @@
+use crate::{Solver, SPIKE_EPS};
+
+/// Minimum amplitude of a hidden follow-up event, relative to the flagged event,
+/// for `detect_merged_events`.
+const MERGE_MIN_FRACTION: f64 = 0.2;
+/// Minimum matched-filter SNR of a hidden follow-up event.
+const MERGE_MIN_SNR: f64 = 3.0;
+
+#[cfg(feature = "jsbindings")]
+use wasm_bindgen::prelude::*;
+
+/// Post-fit quality metrics and validation against reference spike trains.
+///
+/// Getters that need the fit refresh the reconvolution lazily (like
+/// `get_reconvolution`), so no extra convolution runs when it is already fresh.
+#[cfg_attr(feature = "jsbindings", wasm_bindgen)]
+impl Solver {
+    /// Standard deviation of the fit residual `trace - (K*s + b)` over the active
+    /// region, with b the displayed (EMA) baseline or, when active, the TV baseline
+    /// series. Returns 0.0 for an empty trace.
+    pub fn get_residual_std(&mut self) -> f64 {
+        let n = self.active_len;
+        if n == 0 {
+            return 0.0;
+        }
+        let (sum, sum_sq) = self.residual_sums();
+        let mean = sum / n as f64;
+        (sum_sq / n as f64 - mean * mean).max(0.0).sqrt()
+    }
+
+    /// Coefficient of determination `1 - RSS/TSS`: the residual sum of squares
+    /// (residual as in `get_residual_std`) against the trace's sum of squares about
+    /// its mean. An all-zero solution with the mean baseline is the baseline-only
+    /// model and scores 0; a biased baseline (e.g. a low percentile) can score
+    /// below 0. Returns 0.0 for an empty or constant trace, where there is no
+    /// variance to explain.
+    pub fn get_r_squared(&mut self) -> f64 {
+        let n = self.active_len;
+        if n == 0 {
+            return 0.0;
+        }
+        let (_, rss) = self.residual_sums();
+        let trace = self.active_trace();
+        let mean = trace.iter().map(|&y| y as f64).sum::<f64>() / n as f64;
+        let tss: f64 = trace.iter().map(|&y| (y as f64 - mean).powi(2)).sum();
+        if tss > 0.0 {
+            1.0 - rss / tss
+        } else {
+            0.0
+        }
+    }
+
+    /// Signal-to-noise ratio: the median reconstructed peak amplitude of the
+    /// nonzero spikes (spike value × kernel peak × `get_gain`) divided by
+    /// `get_residual_std`.
+    ///
+    /// Returns 0.0 when the solution has no spikes above `SPIKE_EPS`, and
+    /// `f64::INFINITY` for a spiking solution with a perfect fit.
+    pub fn get_snr(&mut self) -> f64 {
+        let n = self.active_len;
+        let kernel_peak =
+            self.kernel.iter().fold(0.0_f32, |m, &v| m.max(v.abs())) as f64 * self.gain;
+        let mut amplitudes: Vec<f64> = self.solution[..n]
+            .iter()
+            .filter(|v| v.abs() > SPIKE_EPS)
+            .map(|&v| v.abs() as f64 * kernel_peak)
+            .collect();
+        if amplitudes.is_empty() {
+            return 0.0;
+        }
+        amplitudes.sort_unstable_by(f64::total_cmp);
+        let mid = amplitudes.len() / 2;
+        let median = if amplitudes.len() % 2 == 0 {
+            0.5 * (amplitudes[mid - 1] + amplitudes[mid])
+        } else {
+            amplitudes[mid]
+        };
+        let noise = self.get_residual_std();
+        if noise > 0.0 {
+            median / noise
+        } else {
+            f64::INFINITY
+        }
+    }
+
+    /// Effective degrees of freedom of the fit, for AIC/BIC-style comparisons across
+    /// lambda: the number of solution samples above `SPIKE_EPS` (the lasso DOF)
+    /// plus the baseline's — 1 for the scalar baseline, or the number of constant
+    /// segments of the TV baseline series when that is active.
+    pub fn get_effective_dof(&self) -> u32 {
+        let n = self.active_len;
+        let spikes = self.solution[..n]
+            .iter()
+            .filter(|v| v.abs() > SPIKE_EPS)
+            .count() as u32;
+        let baseline = if self.tv_baseline_active() && n > 0 {
+            let series = &self.baseline_series[..n];
+            1 + series.windows(2).filter(|w| w[0] != w[1]).count() as u32
+        } else {
+            1
+        };
+        spikes + baseline
+    }
+
+    /// Fraction of trace variance explained by each event above `threshold`
+    /// (aligned with `get_spike_indices`): the increase in the residual sum of
+    /// squares if that event's kernel contribution alone were removed, others held
+    /// fixed, divided by the trace's total sum of squares about its mean. Lets
+    /// events be ranked by importance rather than by amplitude alone. All zeros
+    /// for a constant trace.
+    pub fn event_variance_explained(&mut self, threshold: f32) -> Vec<f32> {
+        let n = self.active_len;
+        let events = self.get_spike_indices(threshold);
+        if events.is_empty() {
+            return Vec::new();
+        }
+        let fit = self.get_reconvolution_with_baseline();
+        let trace = self.active_trace();
+        let mean = trace.iter().map(|&y| y as f64).sum::<f64>() / n as f64;
+        let total: f64 = trace.iter().map(|&y| (y as f64 - mean).powi(2)).sum();
+        if total == 0.0 {
+            return vec![0.0; events.len()];
+        }
+        let offset = self.kernel_offset;
+        events
+            .iter()
+            .map(|&t| {
+                let s = self.solution[t as usize] as f64 * self.gain;
+                // ΔRSS = Σ (r + c)² - r² = Σ c² + 2·r·c, with r = y - fit
+                let mut delta = 0.0_f64;
+                for (j, &k) in self.kernel.iter().enumerate() {
+                    let i = t as usize + j;
+                    if i < offset {
+                        continue;
+                    }
+                    let i = i - offset;
+                    if i >= n {
+                        break;
+                    }
+                    let c = s * k as f64;
+                    let r = trace[i] as f64 - fit[i] as f64;
+                    delta += c * c + 2.0 * r * c;
+                }
+                (delta / total) as f32
+            })
+            .collect()
+    }
+
+    /// Flag events that likely hide a second, closely following spike (merged into
+    /// one overweighted sample, typically because lambda is too high).
+    ///
+    /// Each event (a run of consecutive samples above `threshold`) is refit locally
+    /// against the data it explains: once as a single kernel and once as two
+    /// kernels, the second started 1 to 2 rise-times (kernel peak offsets) after
+    /// the first. The event is flagged when the pair reduces the squared error by
+    /// `MERGE_MIN_SNR²` residual variances and the second amplitude is at least
+    /// `MERGE_MIN_FRACTION` of the first. Returns each flagged event's largest
+    /// sample index, ascending.
+    pub fn detect_merged_events(&mut self, threshold: f32) -> Vec<u32> {
+        let n = self.active_len;
+        let events = self.get_spike_indices(threshold);
+        if events.is_empty() {
+            return events;
+        }
+        let fit = self.get_reconvolution_with_baseline();
+        let noise = self.get_residual_std();
+        let min_gain = MERGE_MIN_SNR * MERGE_MIN_SNR * (noise * noise).max(f64::MIN_POSITIVE);
+        let residual: Vec<f64> = self
+            .active_trace()
+            .iter()
+            .zip(&fit)
+            .map(|(&y, &f)| y as f64 - f as f64)
+            .collect();
+
+        let kernel: Vec<f64> = self.kernel[self.kernel_offset..]
+            .iter()
+            .map(|&k| k as f64)
+            .collect();
+        let peak = kernel
+            .iter()
+            .enumerate()
+            .fold(0, |best, (i, &k)| if k > kernel[best] { i } else { best });
+        // Capped so `kernel[lag..]` stays in range for short or late-peaked kernels
+        let max_lag = (2 * peak.max(1)).min(kernel.len() - 1);
+
+        let mut merged = Vec::new();
+        let mut i = 0;
+        while i < events.len() {
+            // Group consecutive samples into one event
+            let first = events[i] as usize;
+            let mut last = first;
+            while i + 1 < events.len() && events[i + 1] as usize == last + 1 {
+                i += 1;
+                last += 1;
+            }
+            i += 1;
+
+            // Local data: the residual plus this event's own contribution
+            let window_end = n.min(last + kernel.len());
+            let mut local = residual[first..window_end].to_vec();
+            for t in first..=last {
+                let s = self.solution[t] as f64 * self.gain;
+                for (j, &k) in kernel.iter().take(window_end - t).enumerate() {
+                    local[t - first + j] += s * k;
+                }
+            }
+            // Kernel started at window offset `p`, as a slice over the window
+            let shifted = |p: usize| -> &[f64] { &kernel[..kernel.len().min(local.len() - p)] };
+            let dot =
+                |p: usize| -> f64 { shifted(p).iter().zip(&local[p..]).map(|(k, y)| k * y).sum() };
+            let energy = |p: usize| -> f64 { shifted(p).iter().map(|k| k * k).sum() };
+
+            // Best single-kernel and two-kernel least-squares fits (SSE reductions)
+            let mut single = 0.0_f64;
+            let mut pair = 0.0_f64;
+            let mut pair_ok = false;
+            for p1 in 0..=last - first {
+                let (d1, e1) = (dot(p1), energy(p1));
+                if d1 > 0.0 && e1 > 0.0 {
+                    single = single.max(d1 * d1 / e1);
+                }
+                for lag in 1..=max_lag {
+                    let p2 = p1 + lag;
+                    if p2 >= local.len() {
+                        break;
+                    }
+                    let (d2, e2) = (dot(p2), energy(p2));
+                    let cross: f64 = shifted(p2)
+                        .iter()
+                        .zip(&kernel[lag..])
+                        .map(|(a, b)| a * b)
+                        .sum();
+                    let det = e1 * e2 - cross * cross;
+                    if det <= 1e-12 * e1 * e2 {
+                        continue;
+                    }
+                    let a1 = (e2 * d1 - cross * d2) / det;
+                    let a2 = (e1 * d2 - cross * d1) / det;
+                    if a1 <= 0.0 || a2 < MERGE_MIN_FRACTION * a1 {
+                        continue;
+                    }
+                    let reduction = a1 * d1 + a2 * d2;
+                    if reduction > pair {
+                        pair = reduction;
+                        pair_ok = true;
+                    }
+                }
+            }
+            if pair_ok && pair - single >= min_gain {
+                let peak_t = (first..=last)
+                    .max_by(|&a, &b| self.solution[a].total_cmp(&self.solution[b]))
+                    .unwrap();
+                merged.push(peak_t as u32);
+            }
+        }
+        merged
+    }
+
+    /// Spike-triggered average of the baseline-subtracted trace, for checking the
+    /// kernel shape: `STA[j]` is the mean of `trace[i + j] - b[i + j]` over every
+    /// sample `i` above `threshold` (as `get_spike_indices`), for j in 0..window,
+    /// with b as in `get_baseline_series`. Events too close to the end contribute
+    /// only to the lags that exist. For well-separated events it follows
+    /// `get_kernel` scaled by the mean amplitude; a delayed or broader peak
+    /// points at the onset delay or tau_rise. Empty if no sample is above
+    /// `threshold` or `window` is 0.
+    pub fn spike_triggered_average(&mut self, threshold: f32, window: usize) -> Vec<f32> {
+        let n = self.active_len;
+        let events = self.get_spike_indices(threshold);
+        if events.is_empty() || window == 0 {
+            return Vec::new();
+        }
+        let baseline = self.get_baseline_series();
+        let trace = self.active_trace();
+        let mut sum = vec![0.0_f64; window];
+        let mut count = vec![0_u32; window];
+        for &t in &events {
+            let t = t as usize;
+            for j in 0..window.min(n - t) {
+                sum[j] += trace[t + j] as f64 - baseline[t + j] as f64;
+                count[j] += 1;
+            }
+        }
+        sum.iter()
+            .zip(&count)
+            .map(|(&s, &c)| if c > 0 { (s / c as f64) as f32 } else { 0.0 })
+            .collect()
+    }
+
+    /// Cross-correlate the solution with a reference spike train (e.g. from paired
+    /// electrophysiology) to measure a systematic timing offset.
+    ///
+    /// Returns `[peak_lag, c(-max_lag), ..., c(max_lag)]`: the lag with the largest
+    /// correlation, then `2 * max_lag + 1` values
+    /// `c(lag) = Σ_t s[t + lag]·ref[t] / (||s||·||ref||)` over samples where both
+    /// exist. A positive peak lag means the solution fires later than the reference
+    /// (e.g. an onset delay missing from the kernel). Samples past the shorter of
+    /// the two lengths are ignored; with an all-zero input every value is 0.
+    pub fn align_to_reference(&self, reference: &[f32], max_lag: usize) -> Vec<f64> {
+        let n = self.active_len.min(reference.len());
+        let solution = &self.solution[..n];
+        let reference = &reference[..n];
+        let norm = |v: &[f32]| v.iter().map(|&x| x as f64 * x as f64).sum::<f64>().sqrt();
+        let scale = norm(solution) * norm(reference);
+
+        let mut out = Vec::with_capacity(2 * max_lag + 2);
+        out.push(0.0);
+        let (mut best_lag, mut best) = (0_i64, f64::NEG_INFINITY);
+        for lag in -(max_lag as i64)..=max_lag as i64 {
+            let c = if scale > 0.0 {
+                let shift = lag.unsigned_abs() as usize;
+                let sum: f64 = if shift >= n {
+                    0.0
+                } else if lag >= 0 {
+                    (0..n - shift)
+                        .map(|t| solution[t + shift] as f64 * reference[t] as f64)
+                        .sum()
+                } else {
+                    (0..n - shift)
+                        .map(|t| solution[t] as f64 * reference[t + shift] as f64)
+                        .sum()
+                };
+                sum / scale
+            } else {
+                0.0
+            };
+            // Ties resolve to the smallest |lag|
+            if c > best || (c == best && lag.abs() < best_lag.abs()) {
+                best = c;
+                best_lag = lag;
+            }
+            out.push(c);
+        }
+        out[0] = best_lag as f64;
+        out
+    }
+}
+
+impl Solver {
+    /// `(Σr, Σr²)` of the fit residual `trace - (K*s + b)` over the active region,
+    /// refreshing the reconvolution if stale.
+    fn residual_sums(&mut self) -> (f64, f64) {
+        if self.reconvolution_stale {
+            self.compute_reconvolution();
+        }
+        let n = self.active_len;
+        let tv = self.tv_baseline_active();
+        let trace = self.active_trace();
+        let mut sum = 0.0_f64;
+        let mut sum_sq = 0.0_f64;
+        for i in 0..n {
+            let b = if tv {
+                self.baseline_series[i] as f64
+            } else {
+                self.baseline_ema
+            };
+            let r = trace[i] as f64 - (self.reconvolution[i] as f64 + b);
+            sum += r;
+            sum_sq += r * r;
+        }
+        (sum, sum_sq)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::kernel::build_kernel;
+    use crate::Solver;
+
+    /// Three unit spikes plus uniform noise in [-noise, noise] from a fixed LCG.
+    fn noisy_trace(noise: f32) -> Vec<f32> {
+        let kernel = build_kernel(0.02, 0.4, 30.0);
+        let n = 600;
+        let mut trace = vec![0.0_f32; n];
+        for &s in &[50, 250, 450] {
+            for (k, &kv) in kernel.iter().enumerate() {
+                if s + k < n {
+                    trace[s + k] += kv;
+                }
+            }
+        }
+        let mut state: u32 = 12345;
+        for v in trace.iter_mut() {
+            state = state.wrapping_mul(1_664_525).wrapping_add(1_013_904_223);
+            let u = (state >> 8) as f32 / (1u32 << 24) as f32;
+            *v += noise * (2.0 * u - 1.0);
+        }
+        trace
+    }
+
+    #[test]
+    fn residual_std_matches_added_noise() {
+        let kernel = build_kernel(0.02, 0.4, 30.0);
+        let n = 600;
+        let mut trace = vec![0.0_f32; n];
+        for &s in &[50, 250, 450] {
+            for (k, &kv) in kernel.iter().enumerate() {
+                if s + k < n {
+                    trace[s + k] += kv;
+                }
+            }
+        }
+        // Zero-mean alternating "noise" with std 0.05
+        for (i, v) in trace.iter_mut().enumerate() {
+            *v += if i % 2 == 0 { 0.05 } else { -0.05 };
+        }
+
+        let mut solver = Solver::new();
+        solver.set_params(0.02, 0.4, 0.01, 30.0);
+        solver.solve(&trace, 300, 10);
+        let std = solver.get_residual_std();
+        assert!((std - 0.05).abs() < 0.01, "residual std {}", std);
+
+        // Fresh reconvolution: a second call gives the identical value
+        assert!(!solver.reconvolution_stale);
+        assert_eq!(solver.get_residual_std(), std);
+    }
+
+    #[test]
+    fn snr_drops_with_noise() {
+        let mut solver = Solver::new();
+        solver.set_params(0.02, 0.4, 0.05, 30.0);
+        solver.solve(&noisy_trace(0.05), 300, 10);
+        let snr_low_noise = solver.get_snr();
+        solver.solve(&noisy_trace(0.2), 300, 10);
+        let snr_high_noise = solver.get_snr();
+        assert!(snr_low_noise > 0.0 && snr_high_noise > 0.0);
+        assert!(
+            snr_high_noise < snr_low_noise,
+            "{} vs {}",
+            snr_high_noise,
+            snr_low_noise
+        );
+
+        // No spikes: SNR is 0
+        solver.solve(&[0.0_f32; 200], 10, 10);
+        assert_eq!(solver.get_snr(), 0.0);
+    }
+
+    #[test]
+    fn r_squared_scores_fit_quality() {
+        let mut solver = Solver::new();
+        solver.set_params(0.02, 0.4, 0.01, 30.0);
+        solver.solve(&noisy_trace(0.02), 300, 10);
+        let good = solver.get_r_squared();
+        assert!(good > 0.9 && good <= 1.0, "R² {good}");
+
+        solver.set_trace(&[]);
+        assert_eq!(solver.get_r_squared(), 0.0);
+        solver.solve(&[3.0_f32; 100], 10, 10);
+        assert_eq!(solver.get_r_squared(), 0.0);
+    }
+
+    /// A maxed-out sparsity slider zeroes the solution: every metric must stay
+    /// finite and describe the baseline-only model.
+    #[test]
+    fn all_zero_solution_gives_clean_metrics() {
+        let trace = noisy_trace(0.05);
+        let mean = trace.iter().map(|&v| v as f64).sum::<f64>() / trace.len() as f64;
+        let mut solver = Solver::new();
+        solver.set_params(0.02, 0.4, 1e6, 30.0);
+        solver.solve(&trace, 300, 10);
+        assert!(solver.get_solution().iter().all(|&v| v == 0.0));
+
+        assert!((solver.get_baseline() - mean).abs() < 1e-5);
+        assert!(solver.get_r_squared().abs() < 1e-5);
+        assert_eq!(solver.get_snr(), 0.0);
+        let std = solver.get_residual_std();
+        assert!(std.is_finite() && std > 0.0);
+        assert_eq!(solver.get_effective_dof(), 1);
+        assert!(solver.event_variance_explained(0.0).is_empty());
+        assert!(solver.detect_merged_events(0.0).is_empty());
+        assert!(solver.get_event_df_amplitudes(0.0).is_empty());
+        assert!(solver
+            .align_to_reference(&trace, 3)
+            .iter()
+            .all(|v| v.is_finite()));
+        assert!(solver.get_solution_in_spikes().iter().all(|&v| v == 0.0));
+    }
+
+    #[test]
+    fn effective_dof_counts_spikes_and_baseline() {
+        let mut solver = Solver::new();
+        solver.set_trace(&[0.0; 100]);
+        assert_eq!(solver.get_effective_dof(), 1);
+        solver.solution[10] = 0.5;
+        solver.solution[60] = 1e-9; // below SPIKE_EPS
+        solver.solution[80] = 2.0;
+        assert_eq!(solver.get_effective_dof(), 3);
+
+        // TV baseline: one DOF per constant segment
+        solver.set_baseline_tv_lambda(1.0);
+        solver.baseline_series[..100].fill(0.0);
+        solver.baseline_series[40..70].fill(0.3);
+        assert_eq!(solver.get_effective_dof(), 2 + 3);
+    }
+
+    #[test]
+    fn event_variance_explained_ranks_events() {
+        let kernel = build_kernel(0.02, 0.4, 30.0);
+        let n = 400;
+        let mut trace = vec![0.0_f32; n];
+        for &(s, a) in &[(50, 2.0), (200, 0.5)] {
+            for (k, &kv) in kernel.iter().enumerate() {
+                if s + k < n {
+                    trace[s + k] += a * kv;
+                }
+            }
+        }
+        let mut solver = Solver::new();
+        solver.set_params(0.02, 0.4, 0.001, 30.0);
+        solver.solve(&trace, 500, 20);
+        let events = solver.get_spike_indices(0.1);
+        let explained = solver.event_variance_explained(0.1);
+        assert_eq!(events, vec![50, 200]);
+        assert_eq!(explained.len(), 2);
+        assert!(explained[0] > 4.0 * explained[1], "{:?}", explained);
+        // Disjoint events on a near-perfect fit together explain about everything
+        let sum: f32 = explained.iter().sum();
+        assert!((sum - 1.0).abs() < 0.15, "sum {}", sum);
+
+        assert!(solver.event_variance_explained(10.0).is_empty());
+    }
+
+    /// Spikes 3 samples apart at 50/53, a lone double spike at 200, light noise.
+    fn merged_pair_trace() -> Vec<f32> {
+        let kernel = build_kernel(0.02, 0.4, 30.0);
+        let n = 400;
+        let mut trace = vec![0.0_f32; n];
+        for &(s, a) in &[(50, 1.0), (53, 1.0), (200, 2.0)] {
+            for (k, &kv) in kernel.iter().enumerate() {
+                if s + k < n {
+                    trace[s + k] += a * kv;
+                }
+            }
+        }
+        let mut state: u32 = 7;
+        for v in trace.iter_mut() {
+            state = state.wrapping_mul(1_664_525).wrapping_add(1_013_904_223);
+            *v += 0.02 * ((state >> 8) as f32 / (1u32 << 24) as f32 - 0.5);
+        }
+        trace
+    }
+
+    #[test]
+    fn merged_events_flagged_at_high_lambda() {
+        let trace = merged_pair_trace();
+        // Low lambda resolves both spikes: nothing to flag
+        let mut solver = Solver::new();
+        solver.set_params(0.02, 0.4, 0.01, 30.0);
+        solver.solve(&trace, 500, 20);
+        assert!(solver.get_spike_indices(0.5).contains(&53));
+        assert!(solver.detect_merged_events(0.05).is_empty());
+
+        // High lambda smears the pair into one event; the lone spike is not flagged
+        solver.set_params(0.02, 0.4, 0.5, 30.0);
+        solver.solve(&trace, 500, 20);
+        assert!(solver.get_spike_indices(0.05).contains(&200));
+        let merged = solver.detect_merged_events(0.05);
+        assert_eq!(merged.len(), 1, "{:?}", merged);
+        assert!((50..=53).contains(&merged[0]));
+        assert!(solver.detect_merged_events(10.0).is_empty());
+    }
+
+    #[test]
+    fn merged_events_handles_short_custom_kernels() {
+        for kernel in [&[1.0_f32][..], &[0.2, 0.5, 1.0]] {
+            let mut solver = Solver::new();
+            solver.set_trace(&[0.0; 100]);
+            assert!(solver.set_kernel(kernel));
+            // A multi-sample event longer than the kernel
+            for t in 40..44 {
+                solver.solution[t] = 1.0;
+            }
+            let merged = solver.detect_merged_events(0.5);
+            assert!(
+                merged.iter().all(|&t| (40..44).contains(&t)),
+                "{:?}",
+                merged
+            );
+        }
+    }
+
+    #[test]
+    fn merged_events_account_for_gain() {
+        let trace = merged_pair_trace();
+        let mut solver = Solver::new();
+        solver.set_params(0.02, 0.4, 0.1, 30.0);
+        solver.solve(&trace, 500, 20);
+        // Moderate lambda resolves the pair: nothing to flag
+        assert!(solver.detect_merged_events(0.05).is_empty());
+
+        // A 20x trace with gain estimation: the event is added back as g*s, so an
+        // un-scaled s would leave the pair in the residual and flag it
+        let scaled: Vec<f32> = trace.iter().map(|&v| 20.0 * v).collect();
+        let mut solver = Solver::new();
+        solver.set_params(0.02, 0.4, 2.0, 30.0);
+        solver.set_gain_estimation(true);
+        solver.solve(&scaled, 500, 20);
+        let gain = solver.get_gain() as f32;
+        assert!(gain > 10.0, "gain {}", gain);
+        assert!(solver.get_spike_indices(1.0 / gain).contains(&53));
+        assert!(solver.detect_merged_events(1.0 / gain).is_empty());
+    }
+
+    #[test]
+    fn spike_triggered_average_recovers_kernel() {
+        let kernel = build_kernel(0.02, 0.4, 30.0);
+        let n = 400;
+        let mut trace = vec![0.3_f32; n];
+        for &s in &[50, 200, 390] {
+            for (k, &kv) in kernel.iter().enumerate() {
+                if s + k < n {
+                    trace[s + k] += kv;
+                }
+            }
+        }
+        let mut solver = Solver::new();
+        solver.set_params(0.02, 0.4, 0.001, 30.0);
+        solver.solve(&trace, 500, 20);
+        assert_eq!(solver.get_spike_indices(0.5), vec![50, 200, 390]);
+
+        let sta = solver.spike_triggered_average(0.5, 30);
+        assert_eq!(sta.len(), 30);
+        let peak = |v: &[f32]| (0..v.len()).max_by(|&a, &b| v[a].total_cmp(&v[b])).unwrap();
+        assert_eq!(peak(&sta), peak(&kernel[..30]));
+        for (j, (&a, &k)) in sta.iter().zip(&kernel).enumerate() {
+            assert!((a - k).abs() < 0.05, "lag {}: {} vs {}", j, a, k);
+        }
+
+        assert!(solver.spike_triggered_average(10.0, 30).is_empty());
+        assert!(solver.spike_triggered_average(0.5, 0).is_empty());
+    }
+
+    #[test]
+    fn align_to_reference_finds_lag() {
+        let mut solver = Solver::new();
+        solver.set_trace(&[0.0; 100]);
+        let mut reference = vec![0.0_f32; 100];
+        for &t in &[10, 40, 75] {
+            reference[t] = 1.0;
+            solver.solution[t + 3] = 0.8;
+        }
+
+        let xc = solver.align_to_reference(&reference, 5);
+        assert_eq!(xc.len(), 12);
+        assert_eq!(xc[0], 3.0);
+        // c(+3) sits at index 1 + (3 + 5): perfect alignment after the shift
+        assert!((xc[9] - 1.0).abs() < 1e-9, "{}", xc[9]);
+        assert_eq!(xc[1 + 5], 0.0);
+
+        // Lags beyond the shift range
+        let xc = solver.align_to_reference(&reference, 2);
+        assert_eq!(xc[0], 0.0);
+        assert!(xc[1..].iter().all(|&c| c == 0.0));
+
+        assert_eq!(solver.align_to_reference(&[0.0; 100], 1), vec![0.0; 4]);
+    }
+}
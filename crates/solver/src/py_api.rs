@@ -1,11 +1,13 @@
 use numpy::{PyArray1, PyReadonlyArray1, PyReadonlyArray2, PyUntypedArrayMethods};
 use pyo3::prelude::*;
 
+use crate::batch::run_to_convergence;
 use crate::kernel::{build_kernel, compute_lipschitz};
 use crate::simulate;
-use crate::{biexp_fit, indeca, kernel_est, upsample, Constraint, ConvMode, Solver};
+use crate::{
+    biexp_fit, indeca, kernel_est, solve_batch, upsample, BatchConfig, Constraint, ConvMode, Solver,
+};
 
-const BATCH_SIZE: u32 = 100;
 const CONTIGUOUS_ERR: &str =
     "array must be C-contiguous; call numpy.ascontiguousarray() before passing";
 
@@ -36,22 +38,13 @@ fn parse_constraint(s: &str) -> PyResult<Constraint> {
     match s {
         "nonneg" => Ok(Constraint::NonNegative),
         "box01" => Ok(Constraint::Box01),
+        "signed" => Ok(Constraint::Signed),
         _ => Err(pyo3::exceptions::PyValueError::new_err(
-            "constraint must be 'nonneg' or 'box01'",
+            "constraint must be 'nonneg', 'box01' or 'signed'",
         )),
     }
 }
 
-/// Run the solver in batches until convergence or max_iters is reached.
-fn run_to_convergence(solver: &mut Solver, max_iters: u32) {
-    let n_batches = max_iters.div_ceil(BATCH_SIZE);
-    for _ in 0..n_batches {
-        if solver.step_batch(BATCH_SIZE) {
-            break;
-        }
-    }
-}
-
 /// Python-facing wrapper around the Rust FISTA Solver.
 ///
 /// Exposes the same API as the WASM bindings but with numpy array I/O.
@@ -284,49 +277,42 @@ fn deconvolve_batch<'py>(
     Vec<u32>,
     Vec<bool>,
 )> {
-    let shape = traces.shape();
-    let n_cells = shape[0];
+    let config = BatchConfig {
+        tau_rise,
+        tau_decay,
+        lambda: lambda_,
+        fs,
+        conv_mode: parse_conv_mode(conv_mode)?,
+        constraint: parse_constraint(constraint)?,
+        hp_enabled,
+        lp_enabled,
+        max_iters,
+    };
 
-    let mut solver = Solver::new();
-    solver.set_params(tau_rise, tau_decay, lambda_, fs);
-    configure_solver_options(&mut solver, conv_mode, constraint)?;
+    let traces_f32: Vec<Vec<f32>> = traces
+        .as_array()
+        .rows()
+        .into_iter()
+        .map(|row| row.iter().map(|&v| v as f32).collect())
+        .collect();
 
-    if hp_enabled || lp_enabled {
-        solver.set_hp_filter_enabled(hp_enabled);
-        solver.set_lp_filter_enabled(lp_enabled);
-    }
+    // Release the GIL while solving; with the `parallel` feature the traces
+    // are spread across rayon worker threads.
+    let results = py.allow_threads(|| solve_batch(&traces_f32, &config));
 
+    let n_cells = results.len();
     let mut activities = Vec::with_capacity(n_cells);
     let mut baselines = Vec::with_capacity(n_cells);
     let mut reconvolutions = Vec::with_capacity(n_cells);
     let mut iterations = Vec::with_capacity(n_cells);
     let mut convergeds = Vec::with_capacity(n_cells);
 
-    let traces_ref = traces.as_array();
-    let n_timepoints = shape[1];
-    let mut trace_f32: Vec<f32> = Vec::with_capacity(n_timepoints);
-
-    for cell_idx in 0..n_cells {
-        trace_f32.clear();
-        trace_f32.extend(traces_ref.row(cell_idx).iter().map(|&v| v as f32));
-        solver.set_trace(&trace_f32);
-
-        if hp_enabled || lp_enabled {
-            solver.apply_filter();
-        }
-
-        solver.subtract_baseline();
-
-        run_to_convergence(&mut solver, max_iters);
-
-        activities.push(PyArray1::from_vec(py, solver.get_solution()));
-        baselines.push(solver.get_baseline());
-        reconvolutions.push(PyArray1::from_vec(
-            py,
-            solver.get_reconvolution_with_baseline(),
-        ));
-        iterations.push(solver.iteration_count());
-        convergeds.push(solver.converged());
+    for result in results {
+        activities.push(PyArray1::from_vec(py, result.solution));
+        baselines.push(result.baseline);
+        reconvolutions.push(PyArray1::from_vec(py, result.reconvolution));
+        iterations.push(result.iterations);
+        convergeds.push(result.converged);
     }
 
     Ok((
@@ -695,6 +695,64 @@ fn convolve_binary_spikes(spikes: &[u8], kernel: &[f32]) -> Vec<f32> {
     out
 }
 
+// ── Demo traces ──────────────────────────────────────────────────
+
+/// Seed the PRNG from a 64-bit seed (both halves contribute).
+fn demo_rng(seed: u64) -> Xorshift32 {
+    Xorshift32::new((seed ^ (seed >> 32)) as u32)
+}
+
+/// Place `min(n_spikes, n)` unit spikes at distinct uniformly random samples.
+fn place_demo_spikes(n: usize, n_spikes: usize, rng: &mut Xorshift32) -> Vec<f32> {
+    let mut spikes = vec![0.0_f32; n];
+    let mut order: Vec<usize> = (0..n).collect();
+    // Partial Fisher-Yates: the first n_spikes entries are a uniform sample
+    for i in 0..n_spikes.min(n) {
+        let j = i + (rng.next_f64() * (n - i) as f64) as usize;
+        order.swap(i, j.min(n - 1));
+        spikes[order[i]] = 1.0;
+    }
+    spikes
+}
+
+/// Ground-truth spike train of `demo_trace` for the same `n`, `n_spikes`, `seed`.
+pub(crate) fn demo_spikes(n: usize, n_spikes: usize, seed: u64) -> Vec<f32> {
+    place_demo_spikes(n, n_spikes, &mut demo_rng(seed))
+}
+
+/// Deterministic example trace: `demo_spikes` convolved with `kernel` (whose
+/// first `offset` samples precede t = 0) plus Gaussian noise of std `noise`.
+pub(crate) fn demo_trace(
+    n: usize,
+    n_spikes: usize,
+    noise: f32,
+    seed: u64,
+    kernel: &[f32],
+    offset: usize,
+) -> Vec<f32> {
+    let mut rng = demo_rng(seed);
+    let spikes = place_demo_spikes(n, n_spikes, &mut rng);
+    let mut trace = vec![0.0_f32; n];
+    for (t, _) in spikes.iter().enumerate().filter(|&(_, &s)| s > 0.0) {
+        for (j, &k) in kernel.iter().enumerate() {
+            let i = t + j;
+            if i < offset {
+                continue;
+            }
+            if i - offset >= n {
+                break;
+            }
+            trace[i - offset] += k;
+        }
+    }
+    if noise > 0.0 {
+        for v in trace.iter_mut() {
+            *v += noise * rng.gaussian() as f32;
+        }
+    }
+    trace
+}
+
 // ── Saturation ───────────────────────────────────────────────────
 
 fn apply_saturation(signal: &mut [f32], hill_n: f64, k_d: f64) {
@@ -0,0 +1,32 @@
+//! Monotonic wall clock for per-batch profiling.
+//!
+//! wasm32 uses `performance.now()` from the global scope (window or worker);
+//! native builds use `std::time::Instant`. Only differences between readings
+//! are meaningful.
+
+#[cfg(target_arch = "wasm32")]
+thread_local! {
+    static PERFORMANCE: Option<web_sys::Performance> = {
+        use web_sys::js_sys;
+        use web_sys::wasm_bindgen::JsCast;
+        js_sys::Reflect::get(&js_sys::global(), &"performance".into())
+            .ok()
+            .and_then(|p| p.dyn_into::<web_sys::Performance>().ok())
+    };
+}
+
+/// Current time in microseconds. Returns 0.0 if no clock is available.
+#[cfg(target_arch = "wasm32")]
+pub(crate) fn now_micros() -> f64 {
+    PERFORMANCE.with(|p| p.as_ref().map_or(0.0, |p| p.now() * 1000.0))
+}
+
+/// Current time in microseconds since the first call in this process.
+#[cfg(not(target_arch = "wasm32"))]
+pub(crate) fn now_micros() -> f64 {
+    use std::sync::OnceLock;
+    use std::time::Instant;
+
+    static START: OnceLock<Instant> = OnceLock::new();
+    START.get_or_init(Instant::now).elapsed().as_secs_f64() * 1e6
+}
@@ -74,7 +74,8 @@ class DeconvolutionResult(NamedTuple):
     Attributes
     ----------
     activity : np.ndarray
-        Non-negative deconvolved activity estimates, same shape as input traces.
+        Deconvolved activity estimates, same shape as input traces. Non-negative
+        except with ``constraint='signed'``.
     baseline : float | np.ndarray
         Estimated scalar baseline (per-trace if multi-trace input).
     reconvolution : np.ndarray
@@ -177,13 +178,15 @@ def run_deconvolution(
     conv_mode : str, optional
         Convolution mode: ``'fft'`` (default) or ``'banded'`` (O(T) AR2).
     constraint : str, optional
-        Constraint type: ``'nonneg'`` (default, L1 + non-negative) or
-        ``'box01'`` (box constraint [0, 1], no L1 penalty).
+        Constraint type: ``'nonneg'`` (default, L1 + non-negative),
+        ``'box01'`` (box constraint [0, 1], no L1 penalty), or ``'signed'``
+        (L1 soft-threshold allowing negative events).
 
     Returns
     -------
     np.ndarray
-        Non-negative activity estimates, same shape as input ``traces``.
+        Activity estimates, same shape as input ``traces``. Non-negative except
+        with ``constraint='signed'``.
     """
     single_trace = traces.ndim == 1
     traces_2d = np.atleast_2d(np.asarray(traces, dtype=np.float64))
@@ -233,8 +236,9 @@ def run_deconvolution_full(
     conv_mode : str, optional
         Convolution mode: ``'fft'`` (default) or ``'banded'`` (O(T) AR2).
     constraint : str, optional
-        Constraint type: ``'nonneg'`` (default, L1 + non-negative) or
-        ``'box01'`` (box constraint [0, 1], no L1 penalty).
+        Constraint type: ``'nonneg'`` (default, L1 + non-negative),
+        ``'box01'`` (box constraint [0, 1], no L1 penalty), or ``'signed'``
+        (L1 soft-threshold allowing negative events).
 
     Returns
     -------